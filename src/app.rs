@@ -8,6 +8,10 @@ use tokio::sync::{Mutex, broadcast};
 pub const DEFAULT_DATA_ROOT: &str = "/jffs/camofy";
 pub const DEFAULT_HOST: &str = "0.0.0.0";
 pub const DEFAULT_PORT: u16 = 3000;
+/// 全局 HTTP 客户端的 User-Agent（订阅可按需覆盖）。
+pub const DEFAULT_USER_AGENT: &str = "clash-verge/v2.4.3";
+/// 全局 HTTP 请求超时（秒）。
+pub const DEFAULT_HTTP_TIMEOUT_SECS: u64 = 300;
 
 #[derive(Clone)]
 pub struct AuthSession {
@@ -41,6 +45,11 @@ pub fn app_state() -> &'static AppState {
         .expect("app state is initialized before the server starts")
 }
 
+/// 全局状态尚未初始化（如单元测试中直接调用合并逻辑）时返回 None。
+pub fn try_app_state() -> Option<&'static AppState> {
+    APP_STATE.get()
+}
+
 pub fn data_root() -> PathBuf {
     use std::path::Path;
 
@@ -88,3 +97,41 @@ pub fn current_timestamp() -> String {
         .unwrap_or_default();
     now.as_secs().to_string()
 }
+
+/// 为同一目标文件生成互不冲突的临时文件名后缀（进程 ID 加递增序号），
+/// 避免并发写入同一文件时相互覆盖对方的临时文件。
+pub(crate) fn unique_temp_suffix() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static NEXT_TEMP_ID: AtomicU64 = AtomicU64::new(0);
+    let id = NEXT_TEMP_ID.fetch_add(1, Ordering::Relaxed);
+    format!("{}-{id}", std::process::id())
+}
+
+/// 先写同目录下的临时文件再重命名，避免中途失败留下不完整的文件；父目录不存在时自动创建。
+pub(crate) fn write_file_atomically(
+    path: &std::path::Path,
+    content: impl AsRef<[u8]>,
+) -> Result<(), String> {
+    use std::fs;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| format!("failed to create dir {}: {err}", parent.display()))?;
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(format!(".{}.tmp", unique_temp_suffix()));
+    let tmp_path = PathBuf::from(tmp_path);
+
+    fs::write(&tmp_path, content)
+        .map_err(|err| format!("failed to write {}: {err}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).map_err(|err| {
+        let _ = fs::remove_file(&tmp_path);
+        format!(
+            "failed to move {} to {}: {err}",
+            tmp_path.display(),
+            path.display()
+        )
+    })
+}
@@ -17,6 +17,10 @@ pub struct SettingsDto {
     pub subscription_auto_update: Option<crate::ScheduledTaskConfig>,
     #[serde(default)]
     pub geoip_auto_update: Option<crate::ScheduledTaskConfig>,
+    #[serde(default)]
+    pub provider_auto_update: Option<crate::ScheduledTaskConfig>,
+    pub core_supervisor: crate::supervisor::CoreSupervisorConfig,
+    pub core_health: crate::health::CoreHealthConfig,
 }
 
 #[derive(Deserialize)]
@@ -27,6 +31,12 @@ pub struct UpdateSettingsRequest {
     pub subscription_auto_update: Option<crate::ScheduledTaskConfig>,
     #[serde(default)]
     pub geoip_auto_update: Option<crate::ScheduledTaskConfig>,
+    #[serde(default)]
+    pub provider_auto_update: Option<crate::ScheduledTaskConfig>,
+    #[serde(default)]
+    pub core_supervisor: Option<crate::supervisor::CoreSupervisorConfig>,
+    #[serde(default)]
+    pub core_health: Option<crate::health::CoreHealthConfig>,
 }
 
 #[derive(Deserialize)]
@@ -113,6 +123,9 @@ pub async fn get_settings() -> Json<ApiResponse<SettingsDto>> {
         password_set: cfg.panel_password_hash.is_some(),
         subscription_auto_update: cfg.subscription_auto_update,
         geoip_auto_update: cfg.geoip_auto_update,
+        provider_auto_update: cfg.provider_auto_update,
+        core_supervisor: cfg.core_supervisor,
+        core_health: cfg.core_health,
     };
     Json(ApiResponse {
         code: "ok".to_string(),
@@ -161,6 +174,23 @@ pub async fn update_settings(
 
     let sub_task = body.subscription_auto_update.clone();
     let geoip_task = body.geoip_auto_update.clone();
+    let provider_task = body.provider_auto_update.clone();
+    let core_supervisor = body.core_supervisor.clone();
+    if let Some(Err(err)) = core_supervisor.as_ref().map(|cfg| cfg.validate()) {
+        return Json(ApiResponse {
+            code: "settings_invalid_core_supervisor".to_string(),
+            message: err,
+            data: None,
+        });
+    }
+    let core_health = body.core_health.clone();
+    if let Some(Err(err)) = core_health.as_ref().map(|cfg| cfg.validate()) {
+        return Json(ApiResponse {
+            code: "settings_invalid_core_health".to_string(),
+            message: err,
+            data: None,
+        });
+    }
 
     let result = with_app_config_mut(|config: &mut AppConfig| {
         if let Some(hash) = new_password_hash.as_ref() {
@@ -173,11 +203,23 @@ pub async fn update_settings(
         if let Some(task) = geoip_task {
             config.geoip_auto_update = Some(task);
         }
+        if let Some(task) = provider_task {
+            config.provider_auto_update = Some(task);
+        }
+        if let Some(supervisor) = core_supervisor {
+            config.core_supervisor = supervisor;
+        }
+        if let Some(health) = core_health {
+            config.core_health = health;
+        }
 
         SettingsDto {
             password_set: config.panel_password_hash.is_some(),
             subscription_auto_update: config.subscription_auto_update.clone(),
             geoip_auto_update: config.geoip_auto_update.clone(),
+            provider_auto_update: config.provider_auto_update.clone(),
+            core_supervisor: config.core_supervisor.clone(),
+            core_health: config.core_health.clone(),
         }
     });
 
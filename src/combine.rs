@@ -0,0 +1,240 @@
+//! 多订阅合并（combined 模式）。
+//!
+//! 以主订阅（`active_subscription_id`）为基础保留其 rules / dns 等全部字段，
+//! 再从其他订阅中提取 `proxies` 追加进来：
+//! - 节点重名时以“名称 | 订阅名”的形式去重；
+//! - 为每个订阅生成一个 select 组，另外生成一个覆盖全部节点的 url-test 组；
+//! - 生成的组会被插入到主订阅第一个 select 组的最前面，便于直接选择。
+
+use std::collections::HashSet;
+
+use serde_yaml::{Mapping, Sequence, Value};
+
+/// 覆盖所有订阅节点的自动测速组名称。
+pub(crate) const COMBINED_AUTO_GROUP: &str = "全部订阅自动选择";
+const COMBINED_TEST_URL: &str = "https://www.gstatic.com/generate_204";
+const COMBINED_TEST_INTERVAL: u64 = 300;
+
+/// 参与合并的单个订阅。
+pub(crate) struct CombineSource<'a> {
+    pub name: &'a str,
+    pub config: &'a Value,
+}
+
+fn to_mapping(value: &Value, name: &str) -> Result<Mapping, String> {
+    match value {
+        Value::Mapping(m) => Ok(m.clone()),
+        Value::Null => Ok(Mapping::new()),
+        other => Err(format!(
+            "subscription '{name}' root must be mapping, got {other:?}"
+        )),
+    }
+}
+
+fn sequence_field(map: &Mapping, field: &str) -> Sequence {
+    match map.get(field) {
+        Some(Value::Sequence(seq)) => seq.clone(),
+        _ => Sequence::new(),
+    }
+}
+
+fn item_name(item: &Value) -> Option<&str> {
+    item.get("name").and_then(|v| v.as_str())
+}
+
+fn unique_name(base: &str, suffix: &str, used: &HashSet<String>) -> String {
+    if !used.contains(base) {
+        return base.to_string();
+    }
+    let candidate = format!("{base} | {suffix}");
+    if !used.contains(&candidate) {
+        return candidate;
+    }
+    let mut idx = 2;
+    loop {
+        let candidate = format!("{base} | {suffix} {idx}");
+        if !used.contains(&candidate) {
+            return candidate;
+        }
+        idx += 1;
+    }
+}
+
+fn name_list(names: &[String]) -> Value {
+    Value::Sequence(names.iter().cloned().map(Value::String).collect())
+}
+
+/// 将主订阅与其他订阅合并为一份有效配置。
+pub(crate) fn combine_subscriptions(
+    primary: CombineSource<'_>,
+    others: &[CombineSource<'_>],
+) -> Result<Value, String> {
+    let mut result = to_mapping(primary.config, primary.name)?;
+
+    let mut proxies = sequence_field(&result, "proxies");
+    let mut groups = sequence_field(&result, "proxy-groups");
+
+    // 已占用的名称（节点与代理组共享同一命名空间）
+    let mut used: HashSet<String> = proxies
+        .iter()
+        .chain(groups.iter())
+        .filter_map(item_name)
+        .map(str::to_string)
+        .collect();
+
+    let mut per_source: Vec<(String, Vec<String>)> = Vec::with_capacity(others.len() + 1);
+    per_source.push((
+        primary.name.to_string(),
+        proxies.iter().filter_map(item_name).map(str::to_string).collect(),
+    ));
+
+    for source in others {
+        let map = to_mapping(source.config, source.name)?;
+        let mut names = Vec::new();
+
+        for mut proxy in sequence_field(&map, "proxies") {
+            let Some(original) = item_name(&proxy).map(str::to_string) else {
+                continue;
+            };
+            let name = unique_name(&original, source.name, &used);
+            if name != original
+                && let Some(m) = proxy.as_mapping_mut()
+            {
+                m.insert(Value::String("name".into()), Value::String(name.clone()));
+            }
+            used.insert(name.clone());
+            names.push(name);
+            proxies.push(proxy);
+        }
+
+        per_source.push((source.name.to_string(), names));
+    }
+
+    let mut generated: Vec<String> = Vec::new();
+    let mut generated_groups = Sequence::new();
+
+    let all_names: Vec<String> = per_source
+        .iter()
+        .flat_map(|(_, names)| names.iter().cloned())
+        .collect();
+    if !all_names.is_empty() {
+        let auto_name = unique_name(COMBINED_AUTO_GROUP, "combined", &used);
+        used.insert(auto_name.clone());
+
+        let mut auto = Mapping::new();
+        auto.insert("name".into(), Value::String(auto_name.clone()));
+        auto.insert("type".into(), Value::String("url-test".into()));
+        auto.insert("url".into(), Value::String(COMBINED_TEST_URL.into()));
+        auto.insert("interval".into(), Value::Number(COMBINED_TEST_INTERVAL.into()));
+        auto.insert("proxies".into(), name_list(&all_names));
+        generated_groups.push(Value::Mapping(auto));
+        generated.push(auto_name);
+    }
+
+    for (source_name, names) in &per_source {
+        if names.is_empty() {
+            continue;
+        }
+        let group_name = unique_name(source_name, "订阅", &used);
+        used.insert(group_name.clone());
+
+        let mut group = Mapping::new();
+        group.insert("name".into(), Value::String(group_name.clone()));
+        group.insert("type".into(), Value::String("select".into()));
+        group.insert("proxies".into(), name_list(names));
+        generated_groups.push(Value::Mapping(group));
+        generated.push(group_name);
+    }
+
+    // 将生成的组挂到主订阅的第一个 select 组下，便于在面板中直接切换。
+    if let Some(entry) = groups
+        .iter_mut()
+        .find(|g| g.get("type").and_then(|v| v.as_str()) == Some("select"))
+        && let Some(Value::Sequence(members)) = entry.get_mut("proxies")
+    {
+        let mut prefixed: Sequence = generated.iter().cloned().map(Value::String).collect();
+        prefixed.append(members);
+        *members = prefixed;
+    }
+
+    groups.extend(generated_groups);
+
+    result.insert("proxies".into(), Value::Sequence(proxies));
+    result.insert("proxy-groups".into(), Value::Sequence(groups));
+
+    Ok(Value::Mapping(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{COMBINED_AUTO_GROUP, CombineSource, combine_subscriptions};
+    use serde_yaml::Value;
+
+    fn names(value: &Value, field: &str) -> Vec<String> {
+        value[field]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.get("name").and_then(|n| n.as_str()))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn combines_proxies_and_generates_groups() {
+        let primary: Value = serde_yaml::from_str(
+            r#"
+proxies:
+  - { name: HK-01, type: ss }
+proxy-groups:
+  - { name: 节点选择, type: select, proxies: [HK-01] }
+rules:
+  - MATCH,节点选择
+dns:
+  enable: true
+"#,
+        )
+        .unwrap();
+        let backup: Value = serde_yaml::from_str(
+            r#"
+proxies:
+  - { name: HK-01, type: vmess }
+  - { name: JP-01, type: vmess }
+proxy-groups:
+  - { name: 备用选择, type: select, proxies: [HK-01, JP-01] }
+rules:
+  - MATCH,DIRECT
+"#,
+        )
+        .unwrap();
+
+        let combined = combine_subscriptions(
+            CombineSource {
+                name: "主力",
+                config: &primary,
+            },
+            &[CombineSource {
+                name: "备用",
+                config: &backup,
+            }],
+        )
+        .expect("combine");
+
+        assert_eq!(names(&combined, "proxies"), vec!["HK-01", "HK-01 | 备用", "JP-01"]);
+        assert_eq!(
+            names(&combined, "proxy-groups"),
+            vec!["节点选择", COMBINED_AUTO_GROUP, "主力", "备用"]
+        );
+        // rules / dns 来自主订阅
+        assert_eq!(combined["rules"][0].as_str(), Some("MATCH,节点选择"));
+        assert_eq!(combined["dns"]["enable"].as_bool(), Some(true));
+        // 生成的组被挂到主订阅第一个 select 组的最前面
+        let members: Vec<&str> = combined["proxy-groups"][0]["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+        assert_eq!(members, vec![COMBINED_AUTO_GROUP, "主力", "备用", "HK-01"]);
+    }
+}
@@ -1,6 +1,12 @@
+use std::time::Duration;
+
 use crate::app::{app_state, current_timestamp};
 use crate::{AppEvent, ConfigChangeReason, CoreReloadResult};
 
+/// 重载 / 启动后观察内核是否稳定运行的时间窗口。
+/// 在此期间内核退出视为新配置导致崩溃，将触发自动回滚。
+const CORE_STABLE_GRACE: Duration = Duration::from_secs(5);
+
 /// 在配置内容发生变更（已成功写入磁盘并生成 `merged.yaml`）后，
 /// 根据当前内核运行状态选择性地通知 Mihomo 重新加载配置，并通过全局事件总线上报结果。
 ///
@@ -8,10 +14,13 @@ use crate::{AppEvent, ConfigChangeReason, CoreReloadResult};
 /// - 调用方应当在调用本函数前确保：
 ///   - 相关的 app.json 变更已经写入（通过 `with_app_config_mut` 等）
 ///   - `merged.yaml` 已根据最新配置生成
-/// - 本函数不会修改配置文件，仅负责：
+/// - 本函数负责：
 ///   - 检测 Mihomo 是否在运行
 ///   - 若在运行，则通过 Unix Socket 调用控制接口执行配置重载
+///     （`merged.yaml` 在生成时已通过 `mihomo -t` 测试，这里不再重复测试）
 ///   - 将重载结果通过 `AppEvent::ConfigApplied` 广播出去
+///   - 重载失败时自动回滚到上一份可用的 merged.yaml（见 `history` 模块）；
+///     重载成功后若内核在短时间内退出，同样回滚并重新拉起内核
 pub async fn reload_core_if_running(
     reason: ConfigChangeReason,
 ) -> CoreReloadResult {
@@ -22,18 +31,12 @@ pub async fn reload_core_if_running(
     let result = if !running {
         CoreReloadResult::NotRunning
     } else {
-        match crate::mihomo::reload_config_with_merged(&state.data_root).await {
-            Ok(()) => CoreReloadResult::Reloaded,
-            Err(err) => {
-                tracing::error!("failed to reload mihomo config: {err}");
-                CoreReloadResult::ReloadFailed { message: err }
-            }
-        }
+        reload_running_core().await
     };
 
     // 发送配置应用事件（供后续 WebSocket 等实时通道使用）
     let event = AppEvent::ConfigApplied {
-        reason,
+        reason: reason.clone(),
         core_reload: result.clone(),
         timestamp: current_timestamp(),
     };
@@ -53,16 +56,158 @@ pub async fn reload_core_if_running(
         tracing::debug!("failed to broadcast AppEvent::CoreStatusChanged: {err}");
     }
 
-    // 若配置重载成功，则在后台尝试基于当前配置组合恢复已保存的代理选择。
-    if matches!(result, CoreReloadResult::Reloaded) {
-        tokio::spawn(async {
-            if let Err(err) = crate::mihomo::apply_saved_proxy_selection().await {
-                tracing::warn!(
-                    "failed to apply saved proxy selections after config reload: {err}"
-                );
-            }
-        });
+    match &result {
+        // 若配置重载成功，则在后台尝试基于当前配置组合恢复已保存的代理选择，
+        // 并观察内核是否稳定运行。
+        CoreReloadResult::Reloaded => {
+            tokio::spawn(async {
+                if let Err(err) = crate::mihomo::apply_saved_proxy_selection().await {
+                    tracing::warn!(
+                        "failed to apply saved proxy selections after config reload: {err}"
+                    );
+                }
+            });
+
+            tokio::spawn(confirm_or_roll_back_after_reload(reason));
+        }
+        CoreReloadResult::ReloadFailed { message } => {
+            roll_back_merged_config(reason, message.clone()).await;
+        }
+        CoreReloadResult::NotRunning | CoreReloadResult::Skipped { .. } => {}
     }
 
     result
 }
+
+/// 调用控制接口让运行中的内核重载 merged.yaml。
+async fn reload_running_core() -> CoreReloadResult {
+    let state = app_state();
+
+    match crate::mihomo::reload_config_with_merged(&state.data_root).await {
+        Ok(()) => CoreReloadResult::Reloaded,
+        Err(err) => {
+            tracing::error!("failed to reload mihomo config: {err}");
+            CoreReloadResult::ReloadFailed { message: err }
+        }
+    }
+}
+
+fn record_good_config() {
+    match crate::history::record_good_merged_config(&app_state().data_root) {
+        Ok(Some(rev)) => tracing::info!("recorded merged config {rev} as last known good"),
+        Ok(None) => {}
+        Err(err) => tracing::warn!("failed to record last known good merged config: {err}"),
+    }
+}
+
+/// 内核启动后，若在观察窗口内保持运行，则将当前 merged.yaml 记录为可用配置。
+pub(crate) fn spawn_record_good_config_after_grace() {
+    tokio::spawn(async {
+        let started_at = current_secs();
+        tokio::time::sleep(CORE_STABLE_GRACE).await;
+        let (running, _) = crate::core::core_running_status(&app_state().data_root);
+        if running && !crashed_since(started_at) {
+            record_good_config();
+        }
+    });
+}
+
+fn current_secs() -> u64 {
+    current_timestamp().parse::<u64>().unwrap_or_default()
+}
+
+/// 观察窗口内内核是否崩溃过：supervisor 可能已在窗口结束前将其重新拉起，
+/// 仅凭“仍在运行”无法判断配置是否可用。
+fn crashed_since(since: u64) -> bool {
+    crate::supervisor::last_failure_time().is_some_and(|t| t >= since)
+}
+
+/// 重载成功后观察内核：稳定运行则记录为可用配置；
+/// 若内核在观察窗口内退出（且不是用户主动停止），则视为新配置导致崩溃并回滚。
+async fn confirm_or_roll_back_after_reload(reason: ConfigChangeReason) {
+    let reloaded_at = current_secs();
+    tokio::time::sleep(CORE_STABLE_GRACE).await;
+
+    let (running, _) = crate::core::core_running_status(&app_state().data_root);
+    if running && !crashed_since(reloaded_at) {
+        record_good_config();
+        return;
+    }
+
+    // stop_core 会清除自动启动标记，据此区分用户主动停止与异常退出
+    if !crate::get_app_config_snapshot().core_auto_start {
+        return;
+    }
+
+    roll_back_merged_config(
+        reason,
+        "core exited shortly after config reload".to_string(),
+    )
+    .await;
+}
+
+/// 回滚只恢复 merged.yaml，随 `ConfigRolledBack` 事件提示用户。
+const ROLLBACK_SOURCES_WARNING: &str = "only merged.yaml was rolled back; profiles, subscriptions and \
+     overrides are unchanged, so the next config regeneration will rebuild the rejected config until they are fixed";
+
+/// 将 merged.yaml 恢复为上一份可用配置并重新应用，随后广播 `AppEvent::ConfigRolledBack`。
+///
+/// 内核仍在运行时通过控制接口重载；内核已退出且 supervisor 已安排重启时交由其使用
+/// 恢复后的配置拉起，避免两处同时启动内核；否则直接使用恢复后的配置重新拉起。
+async fn roll_back_merged_config(reason: ConfigChangeReason, error: String) {
+    let state = app_state();
+
+    let restored_revision = match crate::history::rollback_merged_config(&state.data_root) {
+        Ok(Some(rev)) => rev,
+        Ok(None) => {
+            tracing::warn!("no last known good merged config to roll back to: {error}");
+            return;
+        }
+        Err(err) => {
+            tracing::error!("failed to roll back merged config: {err}");
+            return;
+        }
+    };
+    tracing::warn!("rolled back merged config to {restored_revision} after failure: {error}");
+
+    let (running, _) = crate::core::core_running_status(&state.data_root);
+    let core_reload = if running {
+        reload_running_core().await
+    } else if crate::supervisor::restart_pending() {
+        CoreReloadResult::Skipped {
+            reason: "core restart is scheduled by the supervisor".to_string(),
+        }
+    } else if state
+        .core_operation
+        .lock()
+        .await
+        .as_ref()
+        .is_some_and(|op| matches!(op.status, crate::CoreOperationStatus::Running))
+    {
+        // 用户发起的启动 / 停止正在进行，由其决定内核状态
+        CoreReloadResult::Skipped {
+            reason: "another core operation is in progress".to_string(),
+        }
+    } else {
+        let axum::Json(resp) = crate::core::start_core_with_current_config().await;
+        if resp.code == "ok" {
+            CoreReloadResult::Reloaded
+        } else {
+            CoreReloadResult::ReloadFailed {
+                message: resp.message,
+            }
+        }
+    };
+
+    let event = AppEvent::ConfigRolledBack {
+        reason,
+        error,
+        restored_revision,
+        core_reload,
+        warning: ROLLBACK_SOURCES_WARNING.to_string(),
+        timestamp: current_timestamp(),
+    };
+    if let Err(err) = state.events_tx.send(event) {
+        tracing::debug!("failed to broadcast AppEvent::ConfigRolledBack: {err}");
+    }
+}
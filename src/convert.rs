@@ -0,0 +1,912 @@
+//! 订阅格式转换：将 base64 / 纯文本 URI 列表、SIP008 与 sing-box JSON 转换为 Mihomo YAML。
+//!
+//! Clash / Mihomo YAML 原样保留；其余格式逐条解析为 Mihomo 节点，
+//! 并生成只包含一个默认选择组与 `MATCH` 规则的最小配置。无法识别的条目会被跳过并记录日志。
+
+use std::collections::{HashMap, HashSet};
+
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use serde_yaml::{Mapping, Value};
+
+/// 转换生成的默认选择组名称。
+const DEFAULT_GROUP: &str = "节点选择";
+
+/// sing-box 中不对应具体节点的出站类型，转换时直接忽略。
+const SING_BOX_NON_PROXY_OUTBOUNDS: &[&str] = &["direct", "block", "dns", "selector", "urltest"];
+
+/// 拉取订阅时识别出的内容格式，记录在 `ProfileMeta.subscription_format` 中。
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SubscriptionFormat {
+    /// Clash / Mihomo YAML，原样保存
+    Clash,
+    /// base64 编码的 URI 列表
+    Base64Uris,
+    /// 未编码的 URI 列表（每行一个）
+    UriList,
+    Sip008,
+    SingBox,
+}
+
+pub(crate) struct ConvertedSubscription {
+    pub format: SubscriptionFormat,
+    /// 写入 subscription.yaml 的内容
+    pub content: String,
+    /// 因格式不支持或解析失败而跳过的节点条目数
+    pub skipped: usize,
+}
+
+/// 识别订阅内容格式，并在需要时转换为 Mihomo YAML。
+pub(crate) fn convert_subscription(body: &str) -> Result<ConvertedSubscription, String> {
+    let trimmed = body.trim_start_matches('\u{feff}').trim();
+    if trimmed.is_empty() {
+        return Err("subscription content is empty".to_string());
+    }
+
+    if trimmed.starts_with('{')
+        && let Ok(json) = serde_json::from_str::<Json>(trimmed)
+    {
+        if json.get("servers").is_some_and(Json::is_array) {
+            return synthesize(SubscriptionFormat::Sip008, parse_sip008(&json));
+        }
+        if json.get("outbounds").is_some_and(Json::is_array) {
+            return synthesize(SubscriptionFormat::SingBox, parse_sing_box(&json));
+        }
+    }
+
+    if looks_like_uri_list(trimmed) {
+        return synthesize(SubscriptionFormat::UriList, parse_uri_list(trimmed));
+    }
+
+    if let Some(decoded) = decode_base64_str(trimmed)
+        && looks_like_uri_list(&decoded)
+    {
+        return synthesize(SubscriptionFormat::Base64Uris, parse_uri_list(&decoded));
+    }
+
+    match serde_yaml::from_str::<Value>(trimmed) {
+        Ok(Value::Mapping(_)) => Ok(ConvertedSubscription {
+            format: SubscriptionFormat::Clash,
+            content: body.to_string(),
+            skipped: 0,
+        }),
+        _ => Err("unrecognized subscription format".to_string()),
+    }
+}
+
+fn synthesize(
+    format: SubscriptionFormat,
+    (mut proxies, skipped): (Vec<Mapping>, usize),
+) -> Result<ConvertedSubscription, String> {
+    if proxies.is_empty() {
+        return Err(format!(
+            "no supported proxies found in subscription ({skipped} entries skipped)"
+        ));
+    }
+    dedupe_names(&mut proxies);
+
+    let names: Vec<Value> = proxies
+        .iter()
+        .filter_map(|p| p.get("name").cloned())
+        .collect();
+
+    let mut group = Mapping::new();
+    group.insert("name".into(), DEFAULT_GROUP.into());
+    group.insert("type".into(), "select".into());
+    group.insert("proxies".into(), Value::Sequence(names));
+
+    let mut config = Mapping::new();
+    config.insert(
+        "proxies".into(),
+        Value::Sequence(proxies.into_iter().map(Value::Mapping).collect()),
+    );
+    config.insert(
+        "proxy-groups".into(),
+        Value::Sequence(vec![Value::Mapping(group)]),
+    );
+    config.insert(
+        "rules".into(),
+        Value::Sequence(vec![format!("MATCH,{DEFAULT_GROUP}").into()]),
+    );
+
+    let content = serde_yaml::to_string(&Value::Mapping(config))
+        .map_err(|err| format!("failed to serialize converted subscription: {err}"))?;
+    Ok(ConvertedSubscription {
+        format,
+        content,
+        skipped,
+    })
+}
+
+/// Mihomo 要求节点名称唯一，重名节点依次追加序号。
+fn dedupe_names(proxies: &mut [Mapping]) {
+    let mut seen = HashSet::new();
+    for proxy in proxies {
+        let base = proxy
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let mut name = base.clone();
+        let mut n = 2;
+        while !seen.insert(name.clone()) {
+            name = format!("{base} {n}");
+            n += 1;
+        }
+        proxy.insert("name".into(), name.into());
+    }
+}
+
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    let cleaned: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+    let cleaned = cleaned.trim_end_matches('=');
+    STANDARD_NO_PAD
+        .decode(cleaned)
+        .or_else(|_| URL_SAFE_NO_PAD.decode(cleaned))
+        .ok()
+}
+
+fn decode_base64_str(input: &str) -> Option<String> {
+    decode_base64(input).and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+fn percent_decode(input: &str) -> String {
+    percent_decode_str(input).decode_utf8_lossy().into_owned()
+}
+
+fn uri_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// 首个有效行形如 `<scheme>://...` 即视为 URI 列表。
+fn looks_like_uri_list(text: &str) -> bool {
+    uri_lines(text).next().is_some_and(|line| {
+        line.split_once("://").is_some_and(|(scheme, _)| {
+            !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+    })
+}
+
+fn parse_uri_list(text: &str) -> (Vec<Mapping>, usize) {
+    let mut proxies = Vec::new();
+    let mut skipped = 0;
+    for line in uri_lines(text) {
+        match parse_proxy_uri(line) {
+            Ok(proxy) => proxies.push(proxy),
+            Err(err) => {
+                tracing::warn!("skip subscription entry: {err}");
+                skipped += 1;
+            }
+        }
+    }
+    (proxies, skipped)
+}
+
+/// 将单个分享链接解析为 Mihomo 节点。
+fn parse_proxy_uri(uri: &str) -> Result<Mapping, String> {
+    let Some((scheme, rest)) = uri.split_once("://") else {
+        return Err(format!("not a proxy uri: {uri}"));
+    };
+    match scheme.to_ascii_lowercase().as_str() {
+        "ss" => parse_ss(rest),
+        "vmess" => parse_vmess(rest),
+        "trojan" => parse_trojan(rest),
+        "vless" => parse_vless(rest),
+        "hysteria2" | "hy2" => parse_hysteria2(rest),
+        other => Err(format!("unsupported proxy scheme '{other}'")),
+    }
+}
+
+/// 形如 `userinfo@host:port?query#name` 的链接各部分（均已做百分号解码）。
+struct UriParts {
+    user: String,
+    server: String,
+    port: u16,
+    params: HashMap<String, String>,
+    name: String,
+}
+
+impl UriParts {
+    fn param(&self, key: &str) -> Option<String> {
+        self.params.get(key).filter(|v| !v.is_empty()).cloned()
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.params
+            .get(key)
+            .is_some_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+    }
+}
+
+fn split_uri(rest: &str) -> Result<UriParts, String> {
+    let (rest, name) = match rest.split_once('#') {
+        Some((rest, fragment)) => (rest, percent_decode(fragment)),
+        None => (rest, String::new()),
+    };
+    let (rest, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let rest = rest.trim_end_matches('/');
+    let (user, host_port) = match rest.rsplit_once('@') {
+        Some((user, host_port)) => (percent_decode(user), host_port),
+        None => (String::new(), rest),
+    };
+    let (server, port) = split_host_port(host_port)?;
+
+    let params = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect();
+
+    Ok(UriParts {
+        user,
+        server,
+        port,
+        params,
+        name,
+    })
+}
+
+fn split_host_port(input: &str) -> Result<(String, u16), String> {
+    let (host, port) = match input.strip_prefix('[') {
+        Some(rest) => rest.split_once("]:"),
+        None => input.rsplit_once(':'),
+    }
+    .ok_or_else(|| format!("missing port in '{input}'"))?;
+    if host.is_empty() {
+        return Err(format!("missing server in '{input}'"));
+    }
+    let port = port
+        .parse::<u16>()
+        .map_err(|err| format!("invalid port in '{input}': {err}"))?;
+    Ok((host.to_string(), port))
+}
+
+fn new_proxy(name: &str, kind: &str, server: &str, port: u16) -> Mapping {
+    let name = if name.trim().is_empty() {
+        format!("{kind} {server}:{port}")
+    } else {
+        name.trim().to_string()
+    };
+    let mut proxy = Mapping::new();
+    proxy.insert("name".into(), name.into());
+    proxy.insert("type".into(), kind.into());
+    proxy.insert("server".into(), server.into());
+    proxy.insert("port".into(), u64::from(port).into());
+    proxy
+}
+
+fn set_opt(proxy: &mut Mapping, key: &str, value: Option<String>) {
+    if let Some(value) = value.filter(|v| !v.is_empty()) {
+        proxy.insert(key.into(), value.into());
+    }
+}
+
+/// TLS 相关字段；vmess / vless 需显式开启 tls 且 SNI 字段名为 `servername`。
+struct TlsOptions {
+    sni: Option<String>,
+    insecure: bool,
+    alpn: Vec<String>,
+    fingerprint: Option<String>,
+}
+
+fn apply_tls(proxy: &mut Mapping, kind: &str, tls: TlsOptions) {
+    let sni_key = match kind {
+        "vmess" | "vless" => {
+            proxy.insert("tls".into(), true.into());
+            "servername"
+        }
+        _ => "sni",
+    };
+    set_opt(proxy, sni_key, tls.sni);
+    if tls.insecure {
+        proxy.insert("skip-cert-verify".into(), true.into());
+    }
+    if !tls.alpn.is_empty() {
+        proxy.insert(
+            "alpn".into(),
+            Value::Sequence(tls.alpn.into_iter().map(Value::from).collect()),
+        );
+    }
+    set_opt(proxy, "client-fingerprint", tls.fingerprint);
+}
+
+fn split_list(value: Option<String>) -> Vec<String> {
+    value
+        .map(|v| {
+            v.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 传输层设置（ws / grpc / h2），tcp 无需额外字段。
+fn apply_transport(
+    proxy: &mut Mapping,
+    network: &str,
+    host: Option<String>,
+    path: Option<String>,
+    service_name: Option<String>,
+) -> Result<(), String> {
+    match network {
+        "" | "tcp" => return Ok(()),
+        "ws" => {
+            let mut opts = Mapping::new();
+            opts.insert("path".into(), path.unwrap_or_else(|| "/".to_string()).into());
+            if let Some(host) = host {
+                let mut headers = Mapping::new();
+                headers.insert("Host".into(), host.into());
+                opts.insert("headers".into(), Value::Mapping(headers));
+            }
+            proxy.insert("ws-opts".into(), Value::Mapping(opts));
+        }
+        "grpc" => {
+            let mut opts = Mapping::new();
+            set_opt(&mut opts, "grpc-service-name", service_name);
+            proxy.insert("grpc-opts".into(), Value::Mapping(opts));
+        }
+        "h2" | "http" => {
+            let mut opts = Mapping::new();
+            if let Some(host) = host {
+                opts.insert("host".into(), Value::Sequence(vec![host.into()]));
+            }
+            set_opt(&mut opts, "path", path);
+            proxy.insert("h2-opts".into(), Value::Mapping(opts));
+            proxy.insert("network".into(), "h2".into());
+            return Ok(());
+        }
+        other => return Err(format!("unsupported transport '{other}'")),
+    }
+    proxy.insert("network".into(), network.into());
+    Ok(())
+}
+
+/// 解析 SIP003 插件参数（`obfs-local;obfs=http;obfs-host=...`）。
+fn parse_ss_plugin(plugin: &str, opts: &str) -> Result<(&'static str, Mapping), String> {
+    let opts: HashMap<&str, &str> = opts
+        .split(';')
+        .filter(|item| !item.is_empty())
+        .map(|item| item.split_once('=').unwrap_or((item, "")))
+        .collect();
+    let mut mapping = Mapping::new();
+
+    match plugin {
+        "obfs-local" | "simple-obfs" | "obfs" => {
+            let mode = opts.get("obfs").copied().unwrap_or("http");
+            mapping.insert("mode".into(), mode.into());
+            if let Some(host) = opts.get("obfs-host") {
+                mapping.insert("host".into(), (*host).into());
+            }
+            Ok(("obfs", mapping))
+        }
+        "v2ray-plugin" => {
+            let mode = opts.get("mode").copied().unwrap_or("websocket");
+            mapping.insert("mode".into(), mode.into());
+            for key in ["host", "path"] {
+                if let Some(value) = opts.get(key) {
+                    mapping.insert(key.into(), (*value).into());
+                }
+            }
+            if opts.contains_key("tls") {
+                mapping.insert("tls".into(), true.into());
+            }
+            Ok(("v2ray-plugin", mapping))
+        }
+        other => Err(format!("unsupported shadowsocks plugin '{other}'")),
+    }
+}
+
+fn shadowsocks_proxy(
+    name: &str,
+    server: &str,
+    port: u16,
+    cipher: &str,
+    password: &str,
+    plugin: Option<&str>,
+) -> Result<Mapping, String> {
+    let mut proxy = new_proxy(name, "ss", server, port);
+    proxy.insert("cipher".into(), cipher.into());
+    proxy.insert("password".into(), password.into());
+    proxy.insert("udp".into(), true.into());
+
+    if let Some(plugin) = plugin.filter(|p| !p.is_empty()) {
+        let (name, opts) = plugin.split_once(';').unwrap_or((plugin, ""));
+        let (plugin, opts) = parse_ss_plugin(name, opts)?;
+        proxy.insert("plugin".into(), plugin.into());
+        proxy.insert("plugin-opts".into(), Value::Mapping(opts));
+    }
+    Ok(proxy)
+}
+
+/// SIP002（`ss://base64(method:password)@host:port`）与旧格式（`ss://base64(method:password@host:port)`）。
+fn parse_ss(rest: &str) -> Result<Mapping, String> {
+    let (body, fragment) = match rest.split_once('#') {
+        Some((body, fragment)) => (body, Some(fragment)),
+        None => (rest, None),
+    };
+
+    let legacy;
+    let rest = if body.contains('@') {
+        rest
+    } else {
+        let encoded = body.split('?').next().unwrap_or(body).trim_end_matches('/');
+        let decoded =
+            decode_base64_str(encoded).ok_or_else(|| "invalid shadowsocks uri".to_string())?;
+        legacy = match fragment {
+            Some(fragment) => format!("{decoded}#{fragment}"),
+            None => decoded,
+        };
+        &legacy
+    };
+
+    let parts = split_uri(rest)?;
+    let userinfo = if parts.user.contains(':') {
+        parts.user.clone()
+    } else {
+        decode_base64_str(&parts.user)
+            .ok_or_else(|| "invalid shadowsocks userinfo".to_string())?
+    };
+    let Some((cipher, password)) = userinfo.split_once(':') else {
+        return Err("shadowsocks userinfo must be method:password".to_string());
+    };
+
+    shadowsocks_proxy(
+        &parts.name,
+        &parts.server,
+        parts.port,
+        cipher,
+        password,
+        parts.params.get("plugin").map(String::as_str),
+    )
+}
+
+/// 兼容数字与字符串两种写法的 JSON 字段。
+fn json_str(value: &Json, key: &str) -> Option<String> {
+    match value.get(key)? {
+        Json::String(s) if !s.is_empty() => Some(s.clone()),
+        Json::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn json_port(value: &Json, key: &str) -> Result<u16, String> {
+    json_str(value, key)
+        .and_then(|p| p.parse::<u16>().ok())
+        .ok_or_else(|| format!("missing or invalid '{key}'"))
+}
+
+fn parse_vmess(rest: &str) -> Result<Mapping, String> {
+    let encoded = rest.split('#').next().unwrap_or(rest);
+    let json: Json = decode_base64_str(encoded)
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .ok_or_else(|| "vmess uri is not base64 encoded json".to_string())?;
+
+    let server = json_str(&json, "add").ok_or_else(|| "vmess uri missing 'add'".to_string())?;
+    let port = json_port(&json, "port")?;
+    let uuid = json_str(&json, "id").ok_or_else(|| "vmess uri missing 'id'".to_string())?;
+
+    let name = json_str(&json, "ps").unwrap_or_default();
+    let mut proxy = new_proxy(&name, "vmess", &server, port);
+    proxy.insert("uuid".into(), uuid.into());
+    let alter_id = json_str(&json, "aid")
+        .and_then(|a| a.parse::<u64>().ok())
+        .unwrap_or(0);
+    proxy.insert("alterId".into(), alter_id.into());
+    proxy.insert(
+        "cipher".into(),
+        json_str(&json, "scy").unwrap_or_else(|| "auto".to_string()).into(),
+    );
+    proxy.insert("udp".into(), true.into());
+
+    if json_str(&json, "tls").as_deref() == Some("tls") {
+        apply_tls(
+            &mut proxy,
+            "vmess",
+            TlsOptions {
+                sni: json_str(&json, "sni"),
+                insecure: false,
+                alpn: split_list(json_str(&json, "alpn")),
+                fingerprint: json_str(&json, "fp"),
+            },
+        );
+    }
+
+    let network = json_str(&json, "net").unwrap_or_default();
+    let path = json_str(&json, "path");
+    apply_transport(
+        &mut proxy,
+        &network,
+        json_str(&json, "host"),
+        path.clone(),
+        path,
+    )?;
+    Ok(proxy)
+}
+
+fn apply_uri_transport(proxy: &mut Mapping, parts: &UriParts) -> Result<(), String> {
+    let network = parts.param("type").unwrap_or_default();
+    apply_transport(
+        proxy,
+        &network,
+        parts.param("host"),
+        parts.param("path"),
+        parts.param("serviceName"),
+    )
+}
+
+fn parse_trojan(rest: &str) -> Result<Mapping, String> {
+    let parts = split_uri(rest)?;
+    if parts.user.is_empty() {
+        return Err("trojan uri missing password".to_string());
+    }
+
+    let mut proxy = new_proxy(&parts.name, "trojan", &parts.server, parts.port);
+    proxy.insert("password".into(), parts.user.as_str().into());
+    proxy.insert("udp".into(), true.into());
+    apply_tls(
+        &mut proxy,
+        "trojan",
+        TlsOptions {
+            sni: parts.param("sni").or_else(|| parts.param("peer")),
+            insecure: parts.flag("allowInsecure") || parts.flag("insecure"),
+            alpn: split_list(parts.param("alpn")),
+            fingerprint: parts.param("fp"),
+        },
+    );
+    apply_uri_transport(&mut proxy, &parts)?;
+    Ok(proxy)
+}
+
+fn parse_vless(rest: &str) -> Result<Mapping, String> {
+    let parts = split_uri(rest)?;
+    if parts.user.is_empty() {
+        return Err("vless uri missing uuid".to_string());
+    }
+
+    let mut proxy = new_proxy(&parts.name, "vless", &parts.server, parts.port);
+    proxy.insert("uuid".into(), parts.user.as_str().into());
+    proxy.insert("udp".into(), true.into());
+    set_opt(&mut proxy, "flow", parts.param("flow"));
+
+    let security = parts.param("security").unwrap_or_default();
+    if security == "tls" || security == "reality" {
+        apply_tls(
+            &mut proxy,
+            "vless",
+            TlsOptions {
+                sni: parts.param("sni"),
+                insecure: parts.flag("allowInsecure"),
+                alpn: split_list(parts.param("alpn")),
+                fingerprint: parts.param("fp"),
+            },
+        );
+    }
+    if security == "reality" {
+        let mut opts = Mapping::new();
+        set_opt(&mut opts, "public-key", parts.param("pbk"));
+        set_opt(&mut opts, "short-id", parts.param("sid"));
+        proxy.insert("reality-opts".into(), Value::Mapping(opts));
+    }
+
+    apply_uri_transport(&mut proxy, &parts)?;
+    Ok(proxy)
+}
+
+fn parse_hysteria2(rest: &str) -> Result<Mapping, String> {
+    let parts = split_uri(rest)?;
+
+    let mut proxy = new_proxy(&parts.name, "hysteria2", &parts.server, parts.port);
+    proxy.insert("password".into(), parts.user.as_str().into());
+    set_opt(&mut proxy, "ports", parts.param("mport"));
+    apply_tls(
+        &mut proxy,
+        "hysteria2",
+        TlsOptions {
+            sni: parts.param("sni"),
+            insecure: parts.flag("insecure"),
+            alpn: split_list(parts.param("alpn")),
+            fingerprint: None,
+        },
+    );
+    set_opt(&mut proxy, "obfs", parts.param("obfs"));
+    set_opt(&mut proxy, "obfs-password", parts.param("obfs-password"));
+    Ok(proxy)
+}
+
+fn parse_sip008(json: &Json) -> (Vec<Mapping>, usize) {
+    let mut proxies = Vec::new();
+    let mut skipped = 0;
+
+    for server in json["servers"].as_array().into_iter().flatten() {
+        match parse_sip008_server(server) {
+            Ok(proxy) => proxies.push(proxy),
+            Err(err) => {
+                tracing::warn!("skip SIP008 server: {err}");
+                skipped += 1;
+            }
+        }
+    }
+    (proxies, skipped)
+}
+
+fn required_str(value: &Json, key: &str) -> Result<String, String> {
+    json_str(value, key).ok_or_else(|| format!("missing '{key}'"))
+}
+
+/// SIP003 插件名与参数合并为 `plugin;opts` 形式。
+fn json_plugin(value: &Json) -> Option<String> {
+    json_str(value, "plugin").map(|plugin| match json_str(value, "plugin_opts") {
+        Some(opts) => format!("{plugin};{opts}"),
+        None => plugin,
+    })
+}
+
+fn parse_sip008_server(server: &Json) -> Result<Mapping, String> {
+    let name = json_str(server, "remarks").unwrap_or_default();
+    shadowsocks_proxy(
+        &name,
+        &required_str(server, "server")?,
+        json_port(server, "server_port")?,
+        &required_str(server, "method")?,
+        &required_str(server, "password")?,
+        json_plugin(server).as_deref(),
+    )
+}
+
+fn parse_sing_box(json: &Json) -> (Vec<Mapping>, usize) {
+    let mut proxies = Vec::new();
+    let mut skipped = 0;
+
+    for outbound in json["outbounds"].as_array().into_iter().flatten() {
+        match parse_sing_box_outbound(outbound) {
+            Ok(Some(proxy)) => proxies.push(proxy),
+            Ok(None) => {}
+            Err(err) => {
+                tracing::warn!("skip sing-box outbound: {err}");
+                skipped += 1;
+            }
+        }
+    }
+    (proxies, skipped)
+}
+
+fn parse_sing_box_outbound(outbound: &Json) -> Result<Option<Mapping>, String> {
+    let kind = json_str(outbound, "type").unwrap_or_default();
+    if SING_BOX_NON_PROXY_OUTBOUNDS.contains(&kind.as_str()) {
+        return Ok(None);
+    }
+
+    let name = json_str(outbound, "tag").unwrap_or_default();
+    let server = required_str(outbound, "server")?;
+    let port = json_port(outbound, "server_port")?;
+    let required = |key: &str| required_str(outbound, key);
+
+    let (mihomo_kind, mut proxy) = match kind.as_str() {
+        "shadowsocks" => {
+            let proxy = shadowsocks_proxy(
+                &name,
+                &server,
+                port,
+                &required("method")?,
+                &required("password")?,
+                json_plugin(outbound).as_deref(),
+            )?;
+            return Ok(Some(proxy));
+        }
+        "vmess" => {
+            let mut proxy = new_proxy(&name, "vmess", &server, port);
+            proxy.insert("uuid".into(), required("uuid")?.into());
+            let alter_id = outbound["alter_id"].as_u64().unwrap_or(0);
+            proxy.insert("alterId".into(), alter_id.into());
+            proxy.insert(
+                "cipher".into(),
+                json_str(outbound, "security")
+                    .unwrap_or_else(|| "auto".to_string())
+                    .into(),
+            );
+            proxy.insert("udp".into(), true.into());
+            ("vmess", proxy)
+        }
+        "trojan" => {
+            let mut proxy = new_proxy(&name, "trojan", &server, port);
+            proxy.insert("password".into(), required("password")?.into());
+            proxy.insert("udp".into(), true.into());
+            ("trojan", proxy)
+        }
+        "vless" => {
+            let mut proxy = new_proxy(&name, "vless", &server, port);
+            proxy.insert("uuid".into(), required("uuid")?.into());
+            proxy.insert("udp".into(), true.into());
+            set_opt(&mut proxy, "flow", json_str(outbound, "flow"));
+            ("vless", proxy)
+        }
+        "hysteria2" => {
+            let mut proxy = new_proxy(&name, "hysteria2", &server, port);
+            proxy.insert("password".into(), required("password")?.into());
+            let obfs = &outbound["obfs"];
+            set_opt(&mut proxy, "obfs", json_str(obfs, "type"));
+            set_opt(&mut proxy, "obfs-password", json_str(obfs, "password"));
+            ("hysteria2", proxy)
+        }
+        other => return Err(format!("unsupported outbound type '{other}'")),
+    };
+
+    let tls = &outbound["tls"];
+    if tls["enabled"].as_bool() == Some(true) {
+        let alpn = tls["alpn"]
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|a| a.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        apply_tls(
+            &mut proxy,
+            mihomo_kind,
+            TlsOptions {
+                sni: json_str(tls, "server_name"),
+                insecure: tls["insecure"].as_bool() == Some(true),
+                alpn,
+                fingerprint: json_str(&tls["utls"], "fingerprint"),
+            },
+        );
+        let reality = &tls["reality"];
+        if reality["enabled"].as_bool() == Some(true) {
+            let mut opts = Mapping::new();
+            set_opt(&mut opts, "public-key", json_str(reality, "public_key"));
+            set_opt(&mut opts, "short-id", json_str(reality, "short_id"));
+            proxy.insert("reality-opts".into(), Value::Mapping(opts));
+        }
+    }
+
+    let transport = &outbound["transport"];
+    if let Some(network) = json_str(transport, "type") {
+        let host = json_str(&transport["headers"], "Host").or_else(|| match &transport["host"] {
+            Json::Array(hosts) => hosts.first().and_then(|h| h.as_str()).map(str::to_string),
+            Json::String(host) => Some(host.clone()),
+            _ => None,
+        });
+        apply_transport(
+            &mut proxy,
+            &network,
+            host,
+            json_str(transport, "path"),
+            json_str(transport, "service_name"),
+        )?;
+    }
+
+    Ok(Some(proxy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SubscriptionFormat, convert_subscription};
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+
+    fn proxy<'a>(config: &'a serde_yaml::Value, name: &str) -> &'a serde_yaml::Value {
+        config["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .find(|p| p["name"].as_str() == Some(name))
+            .unwrap_or_else(|| panic!("proxy '{name}' not found"))
+    }
+
+    #[test]
+    fn base64_uri_list_is_converted_to_minimal_config() {
+        let vmess = STANDARD.encode(
+            r#"{"v":"2","ps":"vm","add":"vm.example","port":"443","id":"uuid-1","aid":0,"net":"ws","host":"cdn.example","path":"/ws","tls":"tls","sni":"vm.example"}"#,
+        );
+        let list = [
+            format!(
+                "ss://{}@1.2.3.4:8388/?plugin=obfs-local%3Bobfs%3Dhttp%3Bobfs-host%3Dbing.com#SS%20%E8%8A%82%E7%82%B9",
+                STANDARD.encode("aes-128-gcm:pass")
+            ),
+            format!("ss://{}#legacy", STANDARD.encode("chacha20-ietf-poly1305:pw@5.6.7.8:443")),
+            format!("vmess://{vmess}"),
+            "trojan://secret@tr.example:443?sni=sni.example&type=grpc&serviceName=svc&allowInsecure=1#tr".to_string(),
+            "vless://uuid-2@[2001:db8::1]:443?security=reality&sni=www.example&fp=chrome&pbk=KEY&sid=ab&flow=xtls-rprx-vision#vl".to_string(),
+            "hy2://pw@hy.example:8443?sni=hy.example&obfs=salamander&obfs-password=op&insecure=1#hy".to_string(),
+            "ssr://unsupported".to_string(),
+            "trojan://secret@tr.example:443#tr".to_string(),
+        ]
+        .join("\n");
+
+        let converted = convert_subscription(&STANDARD.encode(list)).unwrap();
+        assert_eq!(converted.format, SubscriptionFormat::Base64Uris);
+        assert_eq!(converted.skipped, 1);
+        let config: serde_yaml::Value = serde_yaml::from_str(&converted.content).unwrap();
+
+        let ss = proxy(&config, "SS 节点");
+        assert_eq!(ss["cipher"].as_str(), Some("aes-128-gcm"));
+        assert_eq!(ss["password"].as_str(), Some("pass"));
+        assert_eq!(ss["plugin"].as_str(), Some("obfs"));
+        assert_eq!(ss["plugin-opts"]["host"].as_str(), Some("bing.com"));
+        assert_eq!(proxy(&config, "legacy")["server"].as_str(), Some("5.6.7.8"));
+
+        let vm = proxy(&config, "vm");
+        assert_eq!(vm["port"].as_u64(), Some(443));
+        assert_eq!(vm["tls"].as_bool(), Some(true));
+        assert_eq!(vm["servername"].as_str(), Some("vm.example"));
+        assert_eq!(vm["ws-opts"]["headers"]["Host"].as_str(), Some("cdn.example"));
+
+        let tr = proxy(&config, "tr");
+        assert_eq!(tr["sni"].as_str(), Some("sni.example"));
+        assert_eq!(tr["skip-cert-verify"].as_bool(), Some(true));
+        assert_eq!(tr["grpc-opts"]["grpc-service-name"].as_str(), Some("svc"));
+        assert!(proxy(&config, "tr 2")["network"].is_null());
+
+        let vl = proxy(&config, "vl");
+        assert_eq!(vl["server"].as_str(), Some("2001:db8::1"));
+        assert_eq!(vl["reality-opts"]["public-key"].as_str(), Some("KEY"));
+        assert_eq!(vl["client-fingerprint"].as_str(), Some("chrome"));
+
+        let hy = proxy(&config, "hy");
+        assert_eq!(hy["type"].as_str(), Some("hysteria2"));
+        assert_eq!(hy["obfs-password"].as_str(), Some("op"));
+
+        let group = &config["proxy-groups"][0];
+        assert_eq!(group["type"].as_str(), Some("select"));
+        assert_eq!(group["proxies"].as_sequence().unwrap().len(), 7);
+        assert_eq!(config["rules"][0].as_str(), Some("MATCH,节点选择"));
+    }
+
+    #[test]
+    fn sip008_and_sing_box_json_are_converted() {
+        let sip008 = r#"{"version":1,"servers":[
+            {"id":"1","remarks":"a","server":"1.1.1.1","server_port":8388,"password":"p","method":"aes-256-gcm"},
+            {"id":"2","remarks":"b","server":"2.2.2.2","server_port":8388,"password":"p","method":"aes-256-gcm","plugin":"v2ray-plugin","plugin_opts":"mode=websocket;host=h.example;tls"}
+        ]}"#;
+        let converted = convert_subscription(sip008).unwrap();
+        assert_eq!(converted.format, SubscriptionFormat::Sip008);
+        let config: serde_yaml::Value = serde_yaml::from_str(&converted.content).unwrap();
+        assert_eq!(proxy(&config, "a")["type"].as_str(), Some("ss"));
+        assert_eq!(proxy(&config, "b")["plugin-opts"]["tls"].as_bool(), Some(true));
+
+        let sing_box = r#"{"outbounds":[
+            {"type":"selector","tag":"select","outbounds":["vl"]},
+            {"type":"vless","tag":"vl","server":"v.example","server_port":443,"uuid":"u","flow":"xtls-rprx-vision",
+             "tls":{"enabled":true,"server_name":"v.example","utls":{"enabled":true,"fingerprint":"chrome"},
+                    "reality":{"enabled":true,"public_key":"K","short_id":"s"}}},
+            {"type":"trojan","tag":"tj","server":"t.example","server_port":443,"password":"pw",
+             "tls":{"enabled":true,"server_name":"t.example"},"transport":{"type":"ws","path":"/p","headers":{"Host":"cdn"}}},
+            {"type":"tuic","tag":"tu","server":"x","server_port":1},
+            {"type":"direct","tag":"direct"}
+        ]}"#;
+        let converted = convert_subscription(sing_box).unwrap();
+        assert_eq!(converted.format, SubscriptionFormat::SingBox);
+        assert_eq!(converted.skipped, 1);
+        let config: serde_yaml::Value = serde_yaml::from_str(&converted.content).unwrap();
+        let vl = proxy(&config, "vl");
+        assert_eq!(vl["servername"].as_str(), Some("v.example"));
+        assert_eq!(vl["reality-opts"]["short-id"].as_str(), Some("s"));
+        let tj = proxy(&config, "tj");
+        assert_eq!(tj["sni"].as_str(), Some("t.example"));
+        assert_eq!(tj["ws-opts"]["path"].as_str(), Some("/p"));
+    }
+
+    #[test]
+    fn clash_yaml_is_kept_verbatim_and_unknown_content_rejected() {
+        let yaml = "# comment\nproxies: []\nrules:\n  - MATCH,DIRECT\n";
+        let converted = convert_subscription(yaml).unwrap();
+        assert_eq!(converted.format, SubscriptionFormat::Clash);
+        assert_eq!(converted.content, yaml);
+
+        assert!(convert_subscription("").is_err());
+        assert!(convert_subscription("just some text").is_err());
+        assert!(convert_subscription("ssr://only-unsupported").is_err());
+    }
+}
@@ -9,6 +9,7 @@ use crate::{
     AppEvent, CoreOperationKind, CoreOperationState, CoreOperationStatus,
 };
 use crate::app::{app_state, current_timestamp};
+use crate::process::ProcessIdentity;
 use crate::{save_app_config, AppConfig};
 
 #[derive(Serialize, Deserialize, Default)]
@@ -41,6 +42,10 @@ pub struct CoreInfo {
 pub struct CoreStatusDto {
     running: bool,
     pid: Option<u32>,
+    /// 崩溃重启计数、最近一次退出等守护状态
+    supervisor: crate::supervisor::SupervisorStatusDto,
+    /// 最近一次健康检查得出的整体状态
+    health: crate::health::HealthSummaryDto,
 }
 
 #[derive(Deserialize)]
@@ -112,6 +117,14 @@ fn core_pid_path(root: &PathBuf) -> PathBuf {
     path
 }
 
+/// 内核输出 spool 文件所在目录：位于 tmpfs，避免内核输出持续写入 jffs。
+const CORE_OUTPUT_SPOOL_DIR: &str = "/tmp/camofy";
+
+/// 内核 stdout / stderr 的 spool 文件，camofy 从中读取输出写入 `mihomo.log`。
+fn core_output_spool_path(stream: &str) -> PathBuf {
+    Path::new(CORE_OUTPUT_SPOOL_DIR).join(format!("mihomo.{stream}"))
+}
+
 pub(crate) fn mihomo_log_path(root: &PathBuf) -> PathBuf {
     let mut path = root.clone();
     path.push("log");
@@ -119,6 +132,77 @@ pub(crate) fn mihomo_log_path(root: &PathBuf) -> PathBuf {
     path
 }
 
+/// `mihomo -t` 配置测试的最长等待时间。
+const CORE_CONFIG_TEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+/// 错误信息中保留的 Mihomo 输出上限（字节）。
+const CORE_CONFIG_TEST_OUTPUT_LIMIT: usize = 4096;
+
+/// 使用已安装的 Mihomo 内核以测试模式（`-t`）校验指定配置文件。
+///
+/// - 内核尚未安装时无法测试，直接视为通过；
+/// - 测试失败时返回 Mihomo 的输出（stdout + stderr），便于直接展示给用户；
+/// - 超时后子进程随 future 一起被丢弃并终止（`kill_on_drop`）。
+pub(crate) async fn test_core_config(root: &PathBuf, config_file: &Path) -> Result<(), String> {
+    use std::process::Stdio;
+
+    let core_path = core_binary_path(root);
+    if !core_path.is_file() {
+        tracing::debug!(
+            "core binary not installed at {}, skip config test",
+            core_path.display()
+        );
+        return Ok(());
+    }
+
+    let mut config_dir = root.clone();
+    config_dir.push("config");
+
+    let child = TokioCommand::new(&core_path)
+        .arg("-t")
+        .arg("-d")
+        .arg(config_dir.as_os_str())
+        .arg("-f")
+        .arg(config_file.as_os_str())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| format!("failed to spawn core config test: {err}"))?;
+
+    // wait_with_output 会同时读取 stdout 与 stderr，任一管道写满都不会阻塞子进程
+    let output = match tokio::time::timeout(CORE_CONFIG_TEST_TIMEOUT, child.wait_with_output()).await
+    {
+        Ok(Ok(output)) => output,
+        Ok(Err(err)) => return Err(format!("failed to wait for core config test: {err}")),
+        Err(_) => {
+            return Err(format!(
+                "core config test timed out after {}s",
+                CORE_CONFIG_TEST_TIMEOUT.as_secs()
+            ));
+        }
+    };
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    let mut text = text.trim().to_string();
+    if text.len() > CORE_CONFIG_TEST_OUTPUT_LIMIT {
+        let mut cut = text.len() - CORE_CONFIG_TEST_OUTPUT_LIMIT;
+        while !text.is_char_boundary(cut) {
+            cut += 1;
+        }
+        text = format!("...{}", &text[cut..]);
+    }
+    if text.is_empty() {
+        text = format!("core config test exited with {}", output.status);
+    }
+    Err(text)
+}
+
 fn load_core_meta(root: &PathBuf) -> CoreMeta {
     use std::fs;
     use std::io::ErrorKind;
@@ -181,16 +265,15 @@ pub(crate) fn ensure_controller_secret(root: &PathBuf) -> Result<String, String>
     Ok(secret)
 }
 
-pub(crate) fn read_core_pid(root: &PathBuf) -> Result<u32, String> {
+/// 读取 PID 文件中记录的内核进程身份（PID、启动时间与可执行文件路径）。
+pub(crate) fn read_core_pid_record(root: &PathBuf) -> Result<ProcessIdentity, String> {
     use std::fs;
     use std::io::ErrorKind;
 
     let path = core_pid_path(root);
     match fs::read_to_string(&path) {
-        Ok(content) => content
-            .trim()
-            .parse::<u32>()
-            .map_err(|err| format!("invalid pid in {}: {err}", path.display())),
+        Ok(content) => ProcessIdentity::parse(&content)
+            .map_err(|err| format!("{err} ({})", path.display())),
         Err(err) => {
             if err.kind() == ErrorKind::NotFound {
                 Err("pid_file_not_found".to_string())
@@ -204,7 +287,11 @@ pub(crate) fn read_core_pid(root: &PathBuf) -> Result<u32, String> {
     }
 }
 
-fn write_core_pid(root: &PathBuf, pid: u32) -> Result<(), String> {
+pub(crate) fn read_core_pid(root: &PathBuf) -> Result<u32, String> {
+    read_core_pid_record(root).map(|record| record.pid)
+}
+
+fn write_core_pid(root: &PathBuf, identity: &ProcessIdentity) -> Result<(), String> {
     use std::fs;
 
     let path = core_pid_path(root);
@@ -215,7 +302,10 @@ fn write_core_pid(root: &PathBuf, pid: u32) -> Result<(), String> {
     fs::create_dir_all(parent)
         .map_err(|err| format!("failed to create core dir at {}: {err}", parent.display()))?;
 
-    fs::write(&path, pid.to_string())
+    // 记录启动时间与可执行文件，之后据此识别被其他进程复用的 PID
+    let content = serde_json::to_string(identity)
+        .map_err(|err| format!("failed to serialize pid file: {err}"))?;
+    fs::write(&path, content)
         .map_err(|err| format!("failed to write pid file at {}: {err}", path.display()))
 }
 
@@ -229,15 +319,62 @@ fn remove_core_pid(root: &PathBuf) {
     }
 }
 
-pub(crate) fn is_process_running(pid: u32) -> bool {
+/// 创建（或清空）内核输出的 spool 文件，返回以追加模式打开的写入端。
+fn open_core_output_spool(stream: &str) -> Result<std::fs::File, String> {
+    use std::fs::{self, OpenOptions};
+
+    let path = core_output_spool_path(stream);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| {
+            format!("failed to create core output dir at {}: {err}", parent.display())
+        })?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|err| format!("failed to open core output file {}: {err}", path.display()))?;
+    file.set_len(0)
+        .map_err(|err| format!("failed to truncate core output file {}: {err}", path.display()))?;
+    // 新的内核从头写入，上一个内核的读取进度不再适用
+    let _ = fs::remove_file(crate::logs::spool_offset_path(&path));
+    Ok(file)
+}
+
+/// 跟踪内核的 stdout / stderr spool 文件，直到该进程退出。
+fn spawn_core_output_tasks(root: &PathBuf, identity: ProcessIdentity) {
+    // 为 Mihomo 日志创建共享写入状态，用于在磁盘空间不足时统一关闭文件写入。
+    let log_state = crate::logs::new_shared_log_write_state();
+    let log_path = mihomo_log_path(root);
+
+    for (stream, tap) in [
+        ("stdout", None),
+        // 保留最近的 stderr 输出，内核崩溃时随 CoreCrashed 事件一并上报
+        ("stderr", Some(crate::supervisor::record_core_stderr as fn(&[u8]))),
+    ] {
+        let root = root.clone();
+        let identity = identity.clone();
+        crate::logs::spawn_log_tail_task(
+            core_output_spool_path(stream),
+            log_path.clone(),
+            log_state.clone(),
+            "mihomo",
+            stream,
+            tap,
+            move || is_core_process(&root, &identity),
+        );
+    }
+}
+
+/// PID 文件记录的进程是否就是仍在运行的内核（而非复用了同一 PID 的其他进程）。
+pub(crate) fn is_core_process(root: &PathBuf, record: &ProcessIdentity) -> bool {
     #[cfg(target_family = "unix")]
     {
-        let path = format!("/proc/{pid}");
-        std::fs::metadata(path).is_ok()
+        record.is_alive(Path::new(crate::process::PROC_ROOT), &core_binary_path(root))
     }
     #[cfg(not(target_family = "unix"))]
     {
-        let _ = pid;
+        let _ = (root, record);
         false
     }
 }
@@ -263,9 +400,20 @@ fn ensure_tun_module_loaded() {
 fn ensure_tun_module_loaded() {
 }
 
+/// 最近一次写入 DNS 重定向规则时使用的端口（0 表示尚未写入）。
+/// system 覆盖项修改 `dns.listen` 后，仍需按旧端口删除之前写入的规则。
+#[cfg(target_family = "unix")]
+static APPLIED_DNS_REDIRECT_PORT: std::sync::atomic::AtomicU16 =
+    std::sync::atomic::AtomicU16::new(0);
+
 #[cfg(target_family = "unix")]
 fn apply_dns_redirect_rule() {
     use std::process::Command;
+    use std::sync::atomic::Ordering;
+
+    let port = crate::system_config::dns_listen_port(&app_state().data_root);
+    APPLIED_DNS_REDIRECT_PORT.store(port, Ordering::SeqCst);
+    let port = port.to_string();
 
     let rule = [
         "-t",
@@ -279,7 +427,7 @@ fn apply_dns_redirect_rule() {
         "-j",
         "REDIRECT",
         "--to-ports",
-        "1053",
+        port.as_str(),
     ];
 
     match Command::new("iptables").args(&rule).status() {
@@ -302,6 +450,13 @@ fn apply_dns_redirect_rule() {
 #[cfg(target_family = "unix")]
 fn remove_dns_redirect_rule() {
     use std::process::Command;
+    use std::sync::atomic::Ordering;
+
+    let port = match APPLIED_DNS_REDIRECT_PORT.load(Ordering::SeqCst) {
+        0 => crate::system_config::dns_listen_port(&app_state().data_root),
+        port => port,
+    };
+    let port = port.to_string();
 
     let rule = [
         "-t",
@@ -315,7 +470,7 @@ fn remove_dns_redirect_rule() {
         "-j",
         "REDIRECT",
         "--to-ports",
-        "1053",
+        port.as_str(),
     ];
 
     // 若存在多条相同的 DNS 重定向规则，需要循环删除直到全部清理完毕。
@@ -352,12 +507,12 @@ fn remove_dns_redirect_rule() {
 /// - `(true, Some(pid))`：内核正在运行
 /// - `(false, _)`：未运行或 PID 文件不存在 / 损坏（内部会在必要时尝试清理 PID 文件）
 pub(crate) fn core_running_status(root: &PathBuf) -> (bool, Option<u32>) {
-    match read_core_pid(root) {
-        Ok(pid) => {
-            if is_process_running(pid) {
-                (true, Some(pid))
+    match read_core_pid_record(root) {
+        Ok(record) => {
+            if is_core_process(root, &record) {
+                (true, Some(record.pid))
             } else {
-                // PID 文件存在但进程已经不在了，尝试清理
+                // PID 文件存在但进程已经不在了（或 PID 已被其他进程复用），尝试清理
                 remove_core_pid(root);
                 (false, None)
             }
@@ -548,7 +703,12 @@ pub async fn get_core_status() -> Json<ApiResponse<CoreStatusDto>> {
     let state = app_state();
 
     let (running, pid) = core_running_status(&state.data_root);
-    let data = CoreStatusDto { running, pid };
+    let data = CoreStatusDto {
+        running,
+        pid,
+        supervisor: crate::supervisor::status_snapshot(),
+        health: crate::health::summary_snapshot(),
+    };
 
     Json(ApiResponse {
         code: "ok".to_string(),
@@ -910,6 +1070,20 @@ pub async fn download_core(Json(body): Json<CoreDownloadRequest>) -> Json<ApiRes
 ///
 /// 注意：该函数可能耗时较长；对外 API 应通过异步封装（见 `start_core_async`）调用。
 pub async fn start_core() -> Json<ApiResponse<serde_json::Value>> {
+    // 用户主动启动时重新开始崩溃循环的计数
+    crate::supervisor::reset_after_manual_start();
+    start_core_inner(true).await
+}
+
+/// 直接使用磁盘上现有的 merged.yaml 启动内核，不重新生成配置。
+///
+/// 用于配置回滚后重新拉起内核：此时 merged.yaml 已被恢复为历史可用版本，
+/// 若按当前 app.json 重新生成会再次得到出问题的配置。
+pub(crate) async fn start_core_with_current_config() -> Json<ApiResponse<serde_json::Value>> {
+    start_core_inner(false).await
+}
+
+async fn start_core_inner(regenerate_config: bool) -> Json<ApiResponse<serde_json::Value>> {
     let state = app_state();
 
     // 记录开始启动操作
@@ -954,29 +1128,27 @@ pub async fn start_core() -> Json<ApiResponse<serde_json::Value>> {
     }
 
     // 检查是否已在运行
-    if let Ok(pid) = read_core_pid(&state.data_root) {
-        if is_process_running(pid) {
-            update_core_operation_state(
-                CoreOperationKind::Start,
-                CoreOperationStatus::Error,
-                Some(format!("core is already running with pid {}", pid)),
-                None,
-                true,
-            )
-            .await;
-            return Json(ApiResponse {
-                code: "core_already_running".to_string(),
-                message: format!("core is already running with pid {}", pid),
-                data: None,
-            });
-        } else {
-            // 清理陈旧的 pid 文件
-            remove_core_pid(&state.data_root);
-        }
+    // （陈旧或 PID 已被复用的 pid 文件会在 core_running_status 中清理）
+    if let (true, Some(pid)) = core_running_status(&state.data_root) {
+        update_core_operation_state(
+            CoreOperationKind::Start,
+            CoreOperationStatus::Error,
+            Some(format!("core is already running with pid {}", pid)),
+            None,
+            true,
+        )
+        .await;
+        return Json(ApiResponse {
+            code: "core_already_running".to_string(),
+            message: format!("core is already running with pid {}", pid),
+            data: None,
+        });
     }
 
     // 启动前确保 merged.yaml 已生成（根据当前订阅和用户配置 + core-defaults.yaml）
-    if let Err(err) = crate::user_profiles::generate_merged_config(&state.data_root) {
+    if regenerate_config
+        && let Err(err) = crate::user_profiles::generate_merged_config(&state.data_root).await
+    {
         tracing::error!("failed to generate merged config before core start: {err}");
         update_core_operation_state(
             CoreOperationKind::Start,
@@ -987,9 +1159,9 @@ pub async fn start_core() -> Json<ApiResponse<serde_json::Value>> {
         )
         .await;
         return Json(ApiResponse {
-            code: "config_merge_failed".to_string(),
-            message: err,
-            data: None,
+            code: err.code().to_string(),
+            message: err.to_string(),
+            data: err.issues_data(),
         });
     }
 
@@ -1021,25 +1193,49 @@ pub async fn start_core() -> Json<ApiResponse<serde_json::Value>> {
         config_file.display()
     );
 
-    // 准备 Mihomo 日志输出路径
+    // Mihomo 的 stdout/stderr 写入 spool 文件而不是管道，camofy 重启后内核不受影响，
+    // 日志也可以重新接上。
     use std::process::Stdio;
-    let log_path = mihomo_log_path(&state.data_root);
-    // 为 Mihomo 日志创建共享写入状态，用于在磁盘空间不足时统一关闭文件写入。
-    let log_state = crate::logs::new_shared_log_write_state();
+    let (stdout_spool, stderr_spool) = match (
+        open_core_output_spool("stdout"),
+        open_core_output_spool("stderr"),
+    ) {
+        (Ok(stdout), Ok(stderr)) => (stdout, stderr),
+        (Err(err), _) | (_, Err(err)) => {
+            tracing::error!("{err}");
+            update_core_operation_state(
+                CoreOperationKind::Start,
+                CoreOperationStatus::Error,
+                Some(err.clone()),
+                None,
+                true,
+            )
+            .await;
+            return Json(ApiResponse {
+                code: "core_start_failed".to_string(),
+                message: err,
+                data: None,
+            });
+        }
+    };
 
     // 在真正启动 Mihomo 内核前，尝试加载 tun 内核模块，保证 TUN 模式可用（失败仅记录日志，不中断启动）。
     ensure_tun_module_loaded();
 
-    let mut child = match TokioCommand::new(&core_path)
+    let mut command = TokioCommand::new(&core_path);
+    command
         .arg("-d")
         .arg(config_dir.as_os_str())
         .arg("-f")
         .arg(config_file.as_os_str())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .kill_on_drop(false)
-        .spawn()
-    {
+        .stdout(Stdio::from(stdout_spool))
+        .stderr(Stdio::from(stderr_spool))
+        .kill_on_drop(false);
+    // 放入独立的进程组，camofy 所在进程组收到的 SIGINT / SIGHUP 不会波及内核
+    #[cfg(target_family = "unix")]
+    command.process_group(0);
+
+    let child = match command.spawn() {
         Ok(child) => child,
         Err(err) => {
             let msg = format!("failed to spawn core process: {err}");
@@ -1060,88 +1256,37 @@ pub async fn start_core() -> Json<ApiResponse<serde_json::Value>> {
         }
     };
 
-    // 在后台消费 Mihomo 的 stdout/stderr，并通过统一的日志写入封装落盘。
-    if let Some(stdout) = child.stdout.take() {
-        crate::logs::spawn_log_pipe_task(
-            stdout,
-            log_path.clone(),
-            log_state.clone(),
-            "mihomo",
-            "stdout",
-            true,
-        );
-    }
-
-    if let Some(stderr) = child.stderr.take() {
-        crate::logs::spawn_log_pipe_task(
-            stderr,
-            log_path.clone(),
-            log_state.clone(),
-            "mihomo",
-            "stderr",
-            true,
-        );
-    }
-
     let pid = child.id().unwrap_or(0);
+    let identity = ProcessIdentity::capture(Path::new(crate::process::PROC_ROOT), pid);
     if pid == 0 {
         tracing::warn!("failed to obtain core pid");
-    } else if let Err(err) = write_core_pid(&state.data_root, pid) {
+    } else if let Err(err) = write_core_pid(&state.data_root, &identity) {
         tracing::error!("{err}");
     }
 
-    // 在后台监控 Mihomo 进程的生命周期：
-    // - 若进程异常退出（未通过 stop_core 正常停止），
-    //   则清理 PID 文件并移除所有 DNS 重定向规则，避免 1053 端口仍被转发。
+    // 在后台读取 Mihomo 的输出，并通过统一的日志写入封装落盘。
+    spawn_core_output_tasks(&state.data_root, identity);
+
+    // 在后台监控 Mihomo 进程的生命周期，退出后交由 handle_core_exit 处理。
     let data_root_for_watcher = state.data_root.clone();
     tokio::spawn(async move {
         use tokio::process::Child;
 
         let mut child: Child = child;
 
-        match child.wait().await {
+        let exit_status = match child.wait().await {
             Ok(status) => {
                 tracing::info!("core process (pid {pid}) exited with status: {status}");
+                Some(status)
             }
             Err(err) => {
                 tracing::warn!(
                     "failed to wait for core process (pid {pid}): {err}"
                 );
+                None
             }
-        }
-
-        // 仅当 PID 文件仍指向同一个进程时才执行清理，
-        // 避免与后续再次启动的内核产生竞争。
-        match read_core_pid(&data_root_for_watcher) {
-            Ok(current_pid) if current_pid == pid => {
-                remove_core_pid(&data_root_for_watcher);
-                remove_dns_redirect_rule();
-
-                let state = app_state();
-                let event = AppEvent::CoreStatusChanged {
-                    running: false,
-                    pid: None,
-                    timestamp: current_timestamp(),
-                };
-                if let Err(err) = state.events_tx.send(event) {
-                    tracing::debug!(
-                        "failed to broadcast CoreStatusChanged after core exit: {err}"
-                    );
-                }
-            }
-            Ok(_) => {
-                tracing::info!(
-                    "core pid file changed after process exit; skip dns redirect cleanup for pid {pid}"
-                );
-            }
-            Err(reason) => {
-                if reason != "pid_file_not_found" {
-                    tracing::warn!(
-                        "failed to read core pid in watcher after process exit: {reason}"
-                    );
-                }
-            }
-        }
+        };
+        handle_core_exit(&data_root_for_watcher, pid, exit_status);
     });
 
     // 内核进程成功拉起后，再配置 DNS 重定向 iptables 规则。
@@ -1150,6 +1295,9 @@ pub async fn start_core() -> Json<ApiResponse<serde_json::Value>> {
     // 记忆当前期望的状态为“已启动”，用于下次 camofy 启动时自动拉起内核。
     update_core_auto_start_flag(true);
 
+    // 内核稳定运行一段时间后，将本次使用的 merged.yaml 记录为可用配置。
+    crate::config_manager::spawn_record_good_config_after_grace();
+
     // 在后台尝试根据当前配置组合恢复已保存的代理选择，
     // 避免内核重启后用户手动选择的节点丢失。
     tokio::spawn(async {
@@ -1176,130 +1324,218 @@ pub async fn start_core() -> Json<ApiResponse<serde_json::Value>> {
     })
 }
 
+/// 发送 SIGKILL 后继续等待进程退出的时长。
+#[cfg(target_family = "unix")]
+const CORE_KILL_WAIT: std::time::Duration = std::time::Duration::from_secs(5);
+/// 进程退出后等待 TUN 网卡与监听端口释放的时长。
+#[cfg(target_family = "unix")]
+const CORE_RESOURCE_RELEASE_WAIT: std::time::Duration = std::time::Duration::from_secs(3);
+/// 接管已运行的内核时，等待控制接口响应的时间。
+const CORE_ADOPT_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+/// 轮询被接管内核是否仍在运行的间隔。
+const CORE_ADOPT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// 内核最终被哪种方式停止。
+#[cfg(target_family = "unix")]
+#[derive(Clone, Copy)]
+enum StopMethod {
+    Ipc,
+    Sigterm,
+    Sigkill,
+}
+
+#[cfg(target_family = "unix")]
+impl StopMethod {
+    fn as_str(self) -> &'static str {
+        match self {
+            StopMethod::Ipc => "ipc",
+            StopMethod::Sigterm => "sigterm",
+            StopMethod::Sigkill => "sigkill",
+        }
+    }
+}
+
+/// 向内核进程发送信号并等待其退出；进程已不存在视为已退出。
+#[cfg(target_family = "unix")]
+async fn signal_and_wait(
+    pid: u32,
+    signal: libc::c_int,
+    timeout: std::time::Duration,
+) -> Result<bool, String> {
+    if !crate::process::send_signal(pid, signal)? {
+        return Ok(true);
+    }
+    Ok(crate::process::wait_for_exit(pid, timeout).await)
+}
+
+/// 停止失败时记录操作状态并返回错误响应。
+async fn stop_core_failed(code: &str, msg: String) -> Json<ApiResponse<serde_json::Value>> {
+    tracing::error!("{msg}");
+    update_core_operation_state(
+        CoreOperationKind::Stop,
+        CoreOperationStatus::Error,
+        Some(msg.clone()),
+        None,
+        true,
+    )
+    .await;
+    Json(ApiResponse {
+        code: code.to_string(),
+        message: msg,
+        data: None,
+    })
+}
+
 pub async fn stop_core() -> Json<ApiResponse<serde_json::Value>> {
     let state = app_state();
 
     // 停止内核前优先移除 DNS 转发规则，避免仍有新的 DNS 请求被转发到即将关闭的内核。
     remove_dns_redirect_rule();
 
-    // 优先尝试通过 clash_verge_service_ipc 提供的 IPC 通道优雅停止核心
-    #[cfg(target_family = "unix")]
-    {
-        if let Err(err) = stop_core_via_ipc().await {
-            tracing::warn!("failed to stop core via IPC: {err}");
-        } else {
-            tracing::info!("core stopped via IPC");
+    let pid = match read_core_pid_record(&state.data_root) {
+        Ok(record) if is_core_process(&state.data_root, &record) => Some(record.pid),
+        Ok(record) => {
+            // 进程已不在，或 PID 已被其他进程复用：不能向该 PID 发送信号
+            tracing::info!("core pid file is stale (pid {}), removing it", record.pid);
             remove_core_pid(&state.data_root);
-            update_core_auto_start_flag(false);
-            update_core_operation_state(
-                CoreOperationKind::Stop,
-                CoreOperationStatus::Success,
-                Some("core stopped via IPC".to_string()),
-                None,
-                true,
-            )
-            .await;
-            return Json(ApiResponse {
-                code: "ok".to_string(),
-                message: "stopped".to_string(),
-                data: Some(serde_json::json!({ "via": "ipc" })),
-            });
+            None
         }
-    }
-
-    let pid = match read_core_pid(&state.data_root) {
-        Ok(pid) => pid,
         Err(reason) => {
             if reason != "pid_file_not_found" {
                 tracing::warn!("failed to read core pid when stopping: {reason}");
                 remove_core_pid(&state.data_root);
             }
-            update_core_operation_state(
-                CoreOperationKind::Stop,
-                CoreOperationStatus::Error,
-                Some("core is not running".to_string()),
-                None,
-                true,
-            )
-            .await;
-            return Json(ApiResponse {
-                code: "core_not_running".to_string(),
-                message: "core is not running".to_string(),
-                data: None,
-            });
+            None
         }
     };
 
+    // 标记本次退出为主动停止，避免 supervisor 将其视为崩溃并重启。
+    if let Some(pid) = pid {
+        crate::supervisor::mark_stop_requested(pid);
+    }
+
     #[cfg(target_family = "unix")]
     {
-        use std::process::Command;
-
-        tracing::info!("stopping core with pid {}", pid);
+        let grace = std::time::Duration::from_secs(
+            crate::get_app_config_snapshot().core_supervisor.stop_grace_secs,
+        );
+        // 在内核退出前读取其实际使用的配置，用于之后确认端口与 TUN 网卡已释放
+        let resources = std::fs::read_to_string(crate::user_profiles::merged_config_path(
+            &state.data_root,
+        ))
+        .ok()
+        .and_then(|content| serde_yaml::from_str::<serde_yaml::Value>(&content).ok())
+        .map(|config| crate::process::core_resources(&config))
+        .unwrap_or_default();
+
+        // 优先尝试通过 clash_verge_service_ipc 提供的 IPC 通道优雅停止核心
+        let mut method = None;
+        match stop_core_via_ipc().await {
+            Ok(()) => {
+                tracing::info!("core stop requested via IPC");
+                let exited = match pid {
+                    Some(pid) => crate::process::wait_for_exit(pid, grace).await,
+                    None => true,
+                };
+                if exited {
+                    method = Some(StopMethod::Ipc);
+                } else {
+                    tracing::warn!("core did not exit within {grace:?} after IPC stop");
+                }
+            }
+            Err(err) => tracing::warn!("failed to stop core via IPC: {err}"),
+        }
 
-        let status = Command::new("kill")
-            .arg("-TERM")
-            .arg(pid.to_string())
-            .status();
+        if method.is_none() {
+            let Some(pid) = pid else {
+                return stop_core_failed("core_not_running", "core is not running".to_string())
+                    .await;
+            };
 
-        match status {
-            Ok(status) if status.success() => {
-                // 简单地假设终止成功，后续可以根据需要增加等待和 SIGKILL 逻辑
-                remove_core_pid(&state.data_root);
-                update_core_auto_start_flag(false);
-                update_core_operation_state(
-                    CoreOperationKind::Stop,
-                    CoreOperationStatus::Success,
-                    Some("core stopped via signal".to_string()),
-                    None,
-                    true,
-                )
-                .await;
-                return Json(ApiResponse {
-                    code: "ok".to_string(),
-                    message: "stopped".to_string(),
-                    data: Some(serde_json::json!({ "via": "signal" })),
-                });
-            }
-            Ok(status) => {
-                let msg = format!("kill -TERM exited with status {status}");
-                tracing::error!("{msg}");
-                update_core_operation_state(
-                    CoreOperationKind::Stop,
-                    CoreOperationStatus::Error,
-                    Some(msg.clone()),
-                    None,
-                    true,
-                )
-                .await;
-                return Json(ApiResponse {
-                    code: "core_stop_failed".to_string(),
-                    message: msg,
-                    data: None,
-                });
+            tracing::info!("stopping core with pid {pid} (SIGTERM, grace {grace:?})");
+            update_core_operation_state(
+                CoreOperationKind::Stop,
+                CoreOperationStatus::Running,
+                Some(format!("sent SIGTERM, waiting up to {}s for core to exit", grace.as_secs())),
+                None,
+                false,
+            )
+            .await;
+            match signal_and_wait(pid, libc::SIGTERM, grace).await {
+                Ok(true) => method = Some(StopMethod::Sigterm),
+                Ok(false) => {
+                    tracing::warn!("core (pid {pid}) did not exit within {grace:?}, sending SIGKILL");
+                    update_core_operation_state(
+                        CoreOperationKind::Stop,
+                        CoreOperationStatus::Running,
+                        Some("core did not exit after SIGTERM, sent SIGKILL".to_string()),
+                        None,
+                        false,
+                    )
+                    .await;
+                    match signal_and_wait(pid, libc::SIGKILL, CORE_KILL_WAIT).await {
+                        Ok(true) => method = Some(StopMethod::Sigkill),
+                        Ok(false) => {
+                            // 进程仍在运行：保留 PID 文件，如实报告停止失败
+                            return stop_core_failed(
+                                "core_stop_failed",
+                                format!("core (pid {pid}) is still running after SIGKILL"),
+                            )
+                            .await;
+                        }
+                        Err(err) => return stop_core_failed("core_stop_failed", err).await,
+                    }
+                }
+                Err(err) => return stop_core_failed("core_stop_failed", err).await,
             }
-            Err(err) => {
-                let msg = format!("failed to execute kill: {err}");
-                tracing::error!("{msg}");
-                update_core_operation_state(
-                    CoreOperationKind::Stop,
-                    CoreOperationStatus::Error,
-                    Some(msg.clone()),
-                    None,
-                    true,
-                )
+        }
+
+        let method = method.unwrap_or(StopMethod::Ipc);
+        remove_core_pid(&state.data_root);
+        update_core_auto_start_flag(false);
+
+        // 进程已退出，确认 TUN 网卡与监听端口均已释放
+        let held =
+            crate::process::wait_for_resources_released(&resources, CORE_RESOURCE_RELEASE_WAIT)
                 .await;
-                return Json(ApiResponse {
-                    code: "core_stop_failed".to_string(),
-                    message: msg,
-                    data: None,
-                });
-            }
+        let held_names: Vec<String> = held.iter().map(ToString::to_string).collect();
+        // 进程已经退出，资源未及时释放只作为警告随成功响应返回，不影响后续的启动 / 重启
+        let warning = (!held.is_empty()).then(|| {
+            format!(
+                "core stopped via {}, but resources are still in use: {}",
+                method.as_str(),
+                held_names.join(", ")
+            )
+        });
+        if let Some(msg) = &warning {
+            tracing::warn!("{msg}");
         }
+        let data = serde_json::json!({
+            "via": method.as_str(),
+            "unreleased": held_names,
+            "warning": warning.as_ref().map(|_| "core_resources_not_released"),
+        });
+
+        update_core_operation_state(
+            CoreOperationKind::Stop,
+            CoreOperationStatus::Success,
+            Some(warning.unwrap_or_else(|| format!("core stopped via {}", method.as_str()))),
+            None,
+            true,
+        )
+        .await;
+        Json(ApiResponse {
+            code: "ok".to_string(),
+            message: "stopped".to_string(),
+            data: Some(data),
+        })
     }
 
     #[cfg(not(target_family = "unix"))]
     {
-        let _ = pid;
+        if pid.is_none() {
+            return stop_core_failed("core_not_running", "core is not running".to_string()).await;
+        }
         Json(ApiResponse {
             code: "core_stop_unsupported".to_string(),
             message: "core stop is only supported on unix targets".to_string(),
@@ -1308,10 +1544,123 @@ pub async fn stop_core() -> Json<ApiResponse<serde_json::Value>> {
     }
 }
 
-/// 在 camofy 启动时，根据上次记忆的状态自动拉起内核。
+/// 内核进程退出后的清理：
+/// - 仅当 PID 文件仍指向该进程时才清理 PID 文件与 DNS 重定向规则，避免与后续再次启动的内核产生竞争；
+/// - 非用户主动停止的退出交由 supervisor 按重启策略处理。
+fn handle_core_exit(data_root: &PathBuf, pid: u32, exit_status: Option<std::process::ExitStatus>) {
+    let requested_stop = crate::supervisor::is_requested_stop(pid);
+
+    match read_core_pid(data_root) {
+        Ok(current_pid) if current_pid == pid => {
+            remove_core_pid(data_root);
+            remove_dns_redirect_rule();
+
+            let state = app_state();
+            let event = AppEvent::CoreStatusChanged {
+                running: false,
+                pid: None,
+                timestamp: current_timestamp(),
+            };
+            if let Err(err) = state.events_tx.send(event) {
+                tracing::debug!(
+                    "failed to broadcast CoreStatusChanged after core exit: {err}"
+                );
+            }
+
+            if !requested_stop {
+                // 后台轮询可能尚未读到内核退出前最后的 stderr 输出，上报崩溃前先同步读取
+                crate::logs::drain_spool_now(&core_output_spool_path("stderr"));
+                crate::supervisor::handle_core_crash(
+                    crate::supervisor::CoreExitInfo::from_status(pid, exit_status),
+                );
+            }
+        }
+        Ok(_) => {
+            tracing::info!(
+                "core pid file changed after process exit; skip dns redirect cleanup for pid {pid}"
+            );
+        }
+        Err(reason) => {
+            if reason != "pid_file_not_found" {
+                tracing::warn!(
+                    "failed to read core pid in watcher after process exit: {reason}"
+                );
+            }
+        }
+    }
+}
+
+/// 接管 camofy 重启前已在运行的内核，返回是否接管成功。
+///
+/// 依据 PID 文件中的进程身份确认内核仍在运行，并通过控制接口确认其可用；
+/// 随后重新跟踪其输出、监控其生命周期，并广播 `CoreStatusChanged`。
+/// 接管的内核不是 camofy 的子进程，无法获得退出码，只能轮询发现其退出。
+async fn adopt_running_core() -> bool {
+    let state = app_state();
+    let root = state.data_root.clone();
+
+    let record = match read_core_pid_record(&root) {
+        Ok(record) if is_core_process(&root, &record) => record,
+        _ => {
+            // 顺带清理陈旧或损坏的 PID 文件
+            let _ = core_running_status(&root);
+            return false;
+        }
+    };
+    let pid = record.pid;
+
+    match tokio::time::timeout(
+        CORE_ADOPT_PROBE_TIMEOUT,
+        crate::mihomo::query_core_version(&root),
+    )
+    .await
+    {
+        Ok(Ok(version)) => {
+            tracing::info!("adopting running core (pid {pid}, version {version})");
+        }
+        Ok(Err(err)) => {
+            tracing::warn!("adopting running core (pid {pid}), but controller is not reachable: {err}");
+        }
+        Err(_) => {
+            tracing::warn!("adopting running core (pid {pid}), but controller did not respond in time");
+        }
+    }
+
+    spawn_core_output_tasks(&root, record.clone());
+
+    // 重新登记 DNS 重定向规则（先删后加，避免重复追加同一条规则）
+    remove_dns_redirect_rule();
+    apply_dns_redirect_rule();
+
+    let watcher_root = root.clone();
+    tokio::spawn(async move {
+        while is_core_process(&watcher_root, &record) {
+            tokio::time::sleep(CORE_ADOPT_POLL_INTERVAL).await;
+        }
+        tracing::info!("adopted core process (pid {pid}) exited");
+        handle_core_exit(&watcher_root, pid, None);
+    });
+
+    let event = AppEvent::CoreStatusChanged {
+        running: true,
+        pid: Some(pid),
+        timestamp: current_timestamp(),
+    };
+    if let Err(err) = state.events_tx.send(event) {
+        tracing::debug!("failed to broadcast CoreStatusChanged after adopting core: {err}");
+    }
+    true
+}
+
+/// 在 camofy 启动时接管仍在运行的内核，或根据上次记忆的状态自动拉起内核。
 pub(crate) async fn auto_start_core_if_configured() {
     let state = app_state();
 
+    // camofy 重启而内核仍在运行时直接接管，无需再次启动。
+    if adopt_running_core().await {
+        return;
+    }
+
     let should_auto_start = {
         let guard = state
             .app_config
@@ -1325,25 +1674,6 @@ pub(crate) async fn auto_start_core_if_configured() {
         return;
     }
 
-    // 如果 PID 存在且仍在运行，则无需再次启动。
-    match read_core_pid(&state.data_root) {
-        Ok(pid) => {
-            if is_process_running(pid) {
-                tracing::info!("core is already running on startup with pid {}", pid);
-                return;
-            } else {
-                // 清理陈旧的 pid 文件
-                remove_core_pid(&state.data_root);
-            }
-        }
-        Err(reason) => {
-            if reason != "pid_file_not_found" {
-                tracing::warn!("failed to read core pid on startup: {reason}");
-                remove_core_pid(&state.data_root);
-            }
-        }
-    }
-
     // 检查内核是否已经安装
     let core_path = core_binary_path(&state.data_root);
     if !core_path.is_file() {
@@ -132,6 +132,16 @@ pub async fn stop_core_async() -> Json<ApiResponse<serde_json::Value>> {
 /// - 若当前已有启动/停止任务在执行，则返回错误；
 /// - 否则在后台依次执行 stop_core（若正在运行）和 start_core。
 pub async fn restart_core_async() -> Json<ApiResponse<serde_json::Value>> {
+    request_core_restart(true).await
+}
+
+/// 健康检查触发的重启：沿用当前 `merged.yaml` 启动，且不重置 supervisor 的崩溃循环检测。
+pub(crate) async fn restart_core_for_health() -> Json<ApiResponse<serde_json::Value>> {
+    request_core_restart(false).await
+}
+
+/// `manual` 为 true 时按用户主动启动处理（重新生成配置并重置崩溃循环检测）。
+async fn request_core_restart(manual: bool) -> Json<ApiResponse<serde_json::Value>> {
     let app = app_state();
 
     // 与 start/stop 接口共享同一并发控制：一次仅允许一个核心操作在执行。
@@ -167,7 +177,7 @@ pub async fn restart_core_async() -> Json<ApiResponse<serde_json::Value>> {
     }
 
     // 后台任务：若核心正在运行则先停止，再重新启动。
-    tokio::spawn(async {
+    tokio::spawn(async move {
         let state = app_state();
         let (running, _) = crate::core::core_running_status(&state.data_root);
 
@@ -185,7 +195,11 @@ pub async fn restart_core_async() -> Json<ApiResponse<serde_json::Value>> {
             tracing::info!("core restart requested but core not running; skip stop step");
         }
 
-        let Json(resp) = crate::core::start_core().await;
+        let Json(resp) = if manual {
+            crate::core::start_core().await
+        } else {
+            crate::core::start_core_with_current_config().await
+        };
         if resp.code != "ok" {
             tracing::error!(
                 "core restart: start_core failed: code={}, message={}",
@@ -0,0 +1,604 @@
+//! 用户 profile 中 `script` 指令的内置解释器。
+//!
+//! `script` 是一个有序的声明式变换步骤列表，在用户 profile 其余字段合并之前，
+//! 作用于“当前已合并的基础配置”（defaults + 订阅），用于修正订阅中的问题节点、
+//! 批量重命名、注入代理组等，而无需维护一份完整的订阅副本。
+//!
+//! 示例：
+//!
+//! ```yaml
+//! script:
+//!   - op: filter-proxies
+//!     exclude: "剩余流量|官网|到期"
+//!   - op: rename-proxies
+//!     pattern: "^\\[(.+?)\\]\\s*"
+//!     replace: "$1 "
+//!     flag: true
+//!   - op: inject-group
+//!     position: prepend
+//!     select: "香港|HK"
+//!     group:
+//!       name: 香港自动
+//!       type: url-test
+//!       url: https://www.gstatic.com/generate_204
+//!       interval: 300
+//!   - op: delete
+//!     path: dns.fallback
+//!   - op: set
+//!     path: dns.ipv6
+//!     value: false
+//! ```
+
+use regex::Regex;
+use serde::Deserialize;
+use serde_yaml::{Mapping, Sequence, Value};
+
+/// 节点过滤条件，各条件之间为“且”的关系。
+///
+/// 同时用于 `script` 的 `filter-proxies` 步骤与用户 profile 顶层的 `filter-proxies` 指令。
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ProxyFilter {
+    /// 仅保留名称匹配该正则的节点
+    #[serde(default)]
+    pub include: Option<String>,
+    /// 移除名称匹配该正则的节点
+    #[serde(default)]
+    pub exclude: Option<String>,
+    /// 仅保留这些协议类型（`type` 字段，不区分大小写）
+    #[serde(default)]
+    pub types: Vec<String>,
+    /// 移除这些协议类型
+    #[serde(default, rename = "exclude-types")]
+    pub exclude_types: Vec<String>,
+    /// 仅保留服务器地址（`server` 字段）匹配该正则的节点
+    #[serde(default)]
+    pub server: Option<String>,
+    /// 移除服务器地址匹配该正则的节点
+    #[serde(default, rename = "exclude-server")]
+    pub exclude_server: Option<String>,
+}
+
+/// 节点重命名规则：先按正则替换，再按需在名称前插入国家 / 地区旗帜 emoji。
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RenameRule {
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub replace: String,
+    /// 根据名称中的地区关键字插入旗帜，已带旗帜的节点保持不变
+    #[serde(default)]
+    pub flag: bool,
+}
+
+/// `script` 中的单个变换步骤，通过 `op` 字段区分类型。
+#[derive(Deserialize, Debug)]
+#[serde(tag = "op")]
+enum ScriptStep {
+    /// 过滤 `proxies`，同时从各代理组中移除被过滤的节点。
+    #[serde(rename = "filter-proxies")]
+    FilterProxies(ProxyFilter),
+    /// 重命名节点，并同步更新各代理组中的引用。
+    #[serde(rename = "rename-proxies")]
+    RenameProxies(RenameRule),
+    /// 注入（或替换同名的）代理组；`select` 用于按正则挑选节点填充该组。
+    #[serde(rename = "inject-group")]
+    InjectGroup {
+        group: Value,
+        #[serde(default)]
+        position: Option<String>,
+        #[serde(default)]
+        select: Option<String>,
+    },
+    /// 删除以 `.` 分隔的路径上的键，例如 `dns.fallback`。
+    #[serde(rename = "delete")]
+    Delete { path: String },
+    /// 在以 `.` 分隔的路径上写入值，缺失的中间层级会自动创建为映射。
+    #[serde(rename = "set")]
+    Set { path: String, value: Value },
+}
+
+fn compile_regex(pattern: &str, label: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|err| format!("invalid {label} regex '{pattern}': {err}"))
+}
+
+fn proxy_name(proxy: &Value) -> Option<&str> {
+    proxy.get("name").and_then(|v| v.as_str())
+}
+
+fn sequence_mut<'a>(config: &'a mut Mapping, key: &str) -> Option<&'a mut Sequence> {
+    match config.get_mut(Value::String(key.to_string())) {
+        Some(Value::Sequence(seq)) => Some(seq),
+        _ => None,
+    }
+}
+
+/// 对所有代理组的 `proxies` 列表逐项应用 `f`：返回 `None` 表示移除该引用，
+/// 返回 `Some(name)` 表示替换为新名称。
+fn rewrite_group_members<F>(config: &mut Mapping, mut f: F)
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let Some(groups) = sequence_mut(config, "proxy-groups") else {
+        return;
+    };
+
+    for group in groups.iter_mut() {
+        let Some(Value::Sequence(members)) = group.get_mut("proxies") else {
+            continue;
+        };
+
+        let mut rewritten = Sequence::with_capacity(members.len());
+        for member in members.drain(..) {
+            match member.as_str() {
+                Some(name) => {
+                    if let Some(new_name) = f(name) {
+                        rewritten.push(Value::String(new_name));
+                    }
+                }
+                None => rewritten.push(member),
+            }
+        }
+        *members = rewritten;
+    }
+}
+
+/// 按过滤条件移除节点，并从各代理组中移除对它们的引用。
+pub(crate) fn filter_proxies(config: &mut Mapping, filter: &ProxyFilter) -> Result<(), String> {
+    let include = filter.include.as_deref().map(|p| compile_regex(p, "include")).transpose()?;
+    let exclude = filter.exclude.as_deref().map(|p| compile_regex(p, "exclude")).transpose()?;
+    let server = filter.server.as_deref().map(|p| compile_regex(p, "server")).transpose()?;
+    let exclude_server = filter
+        .exclude_server
+        .as_deref()
+        .map(|p| compile_regex(p, "exclude-server"))
+        .transpose()?;
+    let type_listed =
+        |list: &[String], ty: &str| list.iter().any(|t| t.eq_ignore_ascii_case(ty));
+
+    let mut removed: Vec<String> = Vec::new();
+
+    if let Some(proxies) = sequence_mut(config, "proxies") {
+        proxies.retain(|proxy| {
+            let Some(name) = proxy_name(proxy) else {
+                return true;
+            };
+            let ty = proxy.get("type").and_then(|v| v.as_str()).unwrap_or_default();
+            let addr = proxy.get("server").and_then(|v| v.as_str()).unwrap_or_default();
+
+            let keep = include.as_ref().is_none_or(|re| re.is_match(name))
+                && !exclude.as_ref().is_some_and(|re| re.is_match(name))
+                && (filter.types.is_empty() || type_listed(&filter.types, ty))
+                && !type_listed(&filter.exclude_types, ty)
+                && server.as_ref().is_none_or(|re| re.is_match(addr))
+                && !exclude_server.as_ref().is_some_and(|re| re.is_match(addr));
+            if !keep {
+                removed.push(name.to_string());
+            }
+            keep
+        });
+    }
+
+    if !removed.is_empty() {
+        rewrite_group_members(config, |name| {
+            if removed.iter().any(|r| r == name) {
+                None
+            } else {
+                Some(name.to_string())
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// 地区关键字与对应的 ISO 3166 代码，按顺序匹配，先匹配到的优先。
+///
+/// 每项依次为：代码、中文关键字、两字母缩写、英文单词。两字母缩写（如 `IN`、`DE`、`US`）
+/// 与常见英文单词同形，只按大写匹配；英文单词不区分大小写。缩写与单词都要求前后不是字母，
+/// 避免匹配到其他单词的一部分。
+const FLAG_KEYWORDS: &[(&str, &str, &str, &str)] = &[
+    ("HK", "香港", "HK", "hong ?kong"),
+    ("MO", "澳门", "MO", "macao|macau"),
+    ("TW", "台湾|臺灣", "TW", "taiwan"),
+    ("JP", "日本|东京|大阪", "JP", "japan|tokyo|osaka"),
+    ("KR", "韩国|首尔", "KR", "korea|seoul"),
+    ("SG", "新加坡|狮城", "SG", "singapore"),
+    ("US", "美国|洛杉矶|硅谷|纽约", "US", "usa|united states|america|los angeles"),
+    ("GB", "英国|伦敦", "UK|GB", "britain|united kingdom|london"),
+    ("DE", "德国|法兰克福", "DE", "germany|frankfurt"),
+    ("FR", "法国|巴黎", "FR", "france|paris"),
+    ("NL", "荷兰", "NL", "netherlands|amsterdam"),
+    ("RU", "俄罗斯", "RU", "russia|moscow"),
+    ("CA", "加拿大", "CA", "canada"),
+    ("AU", "澳大利亚|澳洲", "AU", "australia|sydney"),
+    ("IN", "印度", "IN", "india"),
+    ("TR", "土耳其", "TR", "turkey"),
+];
+
+/// 区域指示符（Regional Indicator）所在的 Unicode 区间，两个组合即为一面旗帜。
+const REGIONAL_INDICATOR_A: u32 = 0x1F1E6;
+
+fn flag_emoji(code: &str) -> String {
+    code.chars()
+        .filter_map(|c| char::from_u32(REGIONAL_INDICATOR_A + (c as u32 - 'A' as u32)))
+        .collect()
+}
+
+fn has_flag(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| (REGIONAL_INDICATOR_A..REGIONAL_INDICATOR_A + 26).contains(&(c as u32)))
+}
+
+fn flag_matchers() -> Vec<(String, Regex)> {
+    FLAG_KEYWORDS
+        .iter()
+        .map(|(code, cjk, abbreviations, words)| {
+            let pattern = format!(
+                "{cjk}|(?:^|[^A-Za-z])(?:{abbreviations})(?:[^A-Za-z]|$)|(?i:(?:^|[^a-z])(?:{words})(?:[^a-z]|$))"
+            );
+            let re = Regex::new(&pattern).expect("built-in flag keyword regex is valid");
+            (flag_emoji(code), re)
+        })
+        .collect()
+}
+
+/// 按规则重命名节点并同步更新各代理组中的引用，返回 (旧名称, 新名称) 列表。
+pub(crate) fn rename_proxies(
+    config: &mut Mapping,
+    rule: &RenameRule,
+) -> Result<Vec<(String, String)>, String> {
+    if rule.pattern.is_none() && !rule.flag {
+        return Err("rename-proxies requires 'pattern' or 'flag'".to_string());
+    }
+    let re = rule.pattern.as_deref().map(|p| compile_regex(p, "rename")).transpose()?;
+    let flags = if rule.flag { flag_matchers() } else { Vec::new() };
+
+    let mut renamed: Vec<(String, String)> = Vec::new();
+
+    if let Some(proxies) = sequence_mut(config, "proxies") {
+        for proxy in proxies.iter_mut() {
+            let Some(old_name) = proxy_name(proxy).map(str::to_string) else {
+                continue;
+            };
+            let mut new_name = match &re {
+                Some(re) => re.replace_all(&old_name, rule.replace.as_str()).trim().to_string(),
+                None => old_name.clone(),
+            };
+            if !has_flag(&new_name)
+                && let Some((flag, _)) = flags.iter().find(|(_, re)| re.is_match(&new_name))
+            {
+                new_name = format!("{flag} {new_name}");
+            }
+            if new_name.is_empty() || new_name == old_name {
+                continue;
+            }
+            if let Some(map) = proxy.as_mapping_mut() {
+                map.insert(Value::String("name".into()), Value::String(new_name.clone()));
+            }
+            renamed.push((old_name, new_name));
+        }
+    }
+
+    if !renamed.is_empty() {
+        rewrite_group_members(config, |name| {
+            let new_name = renamed
+                .iter()
+                .find(|(old, _)| old == name)
+                .map(|(_, new)| new.clone());
+            Some(new_name.unwrap_or_else(|| name.to_string()))
+        });
+    }
+
+    Ok(renamed)
+}
+
+fn inject_group(
+    config: &mut Mapping,
+    group: &Value,
+    position: Option<&str>,
+    select: Option<&str>,
+) -> Result<(), String> {
+    let Value::Mapping(group_map) = group else {
+        return Err("inject-group: 'group' must be a mapping".to_string());
+    };
+    let Some(group_name) = group_map.get("name").and_then(|v| v.as_str()) else {
+        return Err("inject-group: 'group.name' is required".to_string());
+    };
+    let group_name = group_name.to_string();
+
+    let mut group_map = group_map.clone();
+
+    if let Some(pattern) = select {
+        let re = compile_regex(pattern, "select")?;
+        let selected: Vec<Value> = match config.get("proxies") {
+            Some(Value::Sequence(proxies)) => proxies
+                .iter()
+                .filter_map(proxy_name)
+                .filter(|name| re.is_match(name))
+                .map(|name| Value::String(name.to_string()))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let key = Value::String("proxies".into());
+        let mut members = match group_map.get(&key) {
+            Some(Value::Sequence(seq)) => seq.clone(),
+            _ => Sequence::new(),
+        };
+        for name in selected {
+            if !members.contains(&name) {
+                members.push(name);
+            }
+        }
+        group_map.insert(key, Value::Sequence(members));
+    }
+
+    let key = Value::String("proxy-groups".into());
+    if !matches!(config.get(&key), Some(Value::Sequence(_))) {
+        config.insert(key.clone(), Value::Sequence(Sequence::new()));
+    }
+    let Some(Value::Sequence(groups)) = config.get_mut(&key) else {
+        unreachable!("proxy-groups was just ensured to be a sequence");
+    };
+
+    let new_group = Value::Mapping(group_map);
+    if let Some(existing) = groups
+        .iter_mut()
+        .find(|g| g.get("name").and_then(|v| v.as_str()) == Some(group_name.as_str()))
+    {
+        *existing = new_group;
+        return Ok(());
+    }
+
+    match position.unwrap_or("append") {
+        "prepend" => groups.insert(0, new_group),
+        "append" => groups.push(new_group),
+        other => {
+            return Err(format!(
+                "inject-group: unknown position '{other}', expected 'prepend' or 'append'"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn split_path(path: &str) -> Result<Vec<&str>, String> {
+    let segments: Vec<&str> = path.split('.').collect();
+    if segments.iter().any(|s| s.trim().is_empty()) {
+        return Err(format!("invalid path '{path}'"));
+    }
+    Ok(segments)
+}
+
+fn child_mut<'a>(value: &'a mut Value, segment: &str) -> Option<&'a mut Value> {
+    match value {
+        Value::Mapping(map) => map.get_mut(segment),
+        Value::Sequence(seq) => segment.parse::<usize>().ok().and_then(|idx| seq.get_mut(idx)),
+        _ => None,
+    }
+}
+
+fn delete_path(config: &mut Mapping, path: &str) -> Result<(), String> {
+    let segments = split_path(path)?;
+    let (last, parents) = segments
+        .split_last()
+        .expect("split_path returns at least one segment");
+
+    let mut root = Value::Mapping(std::mem::take(config));
+    {
+        let mut current = Some(&mut root);
+        for segment in parents {
+            current = current.and_then(|v| child_mut(v, segment));
+        }
+
+        match current {
+            Some(Value::Mapping(map)) => {
+                map.remove(*last);
+            }
+            Some(Value::Sequence(seq)) => {
+                if let Ok(idx) = last.parse::<usize>()
+                    && idx < seq.len()
+                {
+                    seq.remove(idx);
+                }
+            }
+            // 路径不存在时视为无操作。
+            _ => {}
+        }
+    }
+
+    if let Value::Mapping(map) = root {
+        *config = map;
+    }
+    Ok(())
+}
+
+fn set_path(config: &mut Mapping, path: &str, value: &Value) -> Result<(), String> {
+    let segments = split_path(path)?;
+    let (last, parents) = segments
+        .split_last()
+        .expect("split_path returns at least one segment");
+
+    let mut current = config;
+    for segment in parents {
+        let key = Value::String(segment.to_string());
+        if !matches!(current.get(&key), Some(Value::Mapping(_))) {
+            current.insert(key.clone(), Value::Mapping(Mapping::new()));
+        }
+        let Some(Value::Mapping(next)) = current.get_mut(&key) else {
+            unreachable!("intermediate path segment was just ensured to be a mapping");
+        };
+        current = next;
+    }
+
+    current.insert(Value::String(last.to_string()), value.clone());
+    Ok(())
+}
+
+/// 解析并依次执行 `script` 步骤，直接修改传入的配置，返回 `rename-proxies` 产生的 (旧名称, 新名称) 列表。
+///
+/// 任一步骤失败时返回带步骤序号的错误信息，此时 `config` 可能已被前面的步骤部分修改，
+/// 调用方应当丢弃本次结果。
+pub(crate) fn run_script(
+    config: &mut Mapping,
+    script: &Value,
+) -> Result<Vec<(String, String)>, String> {
+    let mut renamed = Vec::new();
+    let steps = match script {
+        Value::Null => return Ok(renamed),
+        Value::Sequence(seq) => seq,
+        other => return Err(format!("'script' must be a sequence, got {other:?}")),
+    };
+
+    for (idx, raw) in steps.iter().enumerate() {
+        let step: ScriptStep = serde_yaml::from_value(raw.clone())
+            .map_err(|err| format!("invalid script step #{}: {err}", idx + 1))?;
+
+        let result = match &step {
+            ScriptStep::FilterProxies(filter) => filter_proxies(config, filter),
+            ScriptStep::RenameProxies(rule) => {
+                rename_proxies(config, rule).map(|pairs| renamed.extend(pairs))
+            }
+            ScriptStep::InjectGroup {
+                group,
+                position,
+                select,
+            } => inject_group(config, group, position.as_deref(), select.as_deref()),
+            ScriptStep::Delete { path } => delete_path(config, path),
+            ScriptStep::Set { path, value } => set_path(config, path, value),
+        };
+
+        result.map_err(|err| format!("script step #{} failed: {err}", idx + 1))?;
+    }
+
+    Ok(renamed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run_script;
+    use serde_yaml::{Mapping, Value};
+
+    fn mapping(yaml: &str) -> Mapping {
+        serde_yaml::from_str(yaml).expect("parse test yaml")
+    }
+
+    fn names(config: &Mapping, key: &str) -> Vec<String> {
+        config
+            .get(key)
+            .and_then(|v| v.as_sequence())
+            .map(|seq| {
+                seq.iter()
+                    .filter_map(|p| p.get("name").and_then(|n| n.as_str()))
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn filter_and_rename_update_group_members() {
+        let mut config = mapping(
+            r#"
+proxies:
+  - { name: "剩余流量：10G", type: ss }
+  - { name: "[HK] 01", type: ss }
+  - { name: "[JP] 01", type: ss }
+proxy-groups:
+  - { name: 节点选择, type: select, proxies: ["剩余流量：10G", "[HK] 01", "[JP] 01", DIRECT] }
+"#,
+        );
+        let script: Value = serde_yaml::from_str(
+            r#"
+- op: filter-proxies
+  exclude: "剩余流量"
+- op: rename-proxies
+  pattern: "^\\[(\\w+)\\]\\s*"
+  replace: "$1-"
+"#,
+        )
+        .unwrap();
+
+        run_script(&mut config, &script).expect("run script");
+
+        assert_eq!(names(&config, "proxies"), vec!["HK-01", "JP-01"]);
+        let members = config["proxy-groups"][0]["proxies"].as_sequence().unwrap();
+        let members: Vec<&str> = members.iter().filter_map(|v| v.as_str()).collect();
+        assert_eq!(members, vec!["HK-01", "JP-01", "DIRECT"]);
+    }
+
+    #[test]
+    fn inject_group_delete_and_set_paths() {
+        let mut config = mapping(
+            r#"
+proxies:
+  - { name: HK-01, type: ss }
+  - { name: JP-01, type: ss }
+proxy-groups:
+  - { name: 节点选择, type: select, proxies: [HK-01, JP-01] }
+dns:
+  fallback: [8.8.8.8]
+  ipv6: true
+"#,
+        );
+        let script: Value = serde_yaml::from_str(
+            r#"
+- op: inject-group
+  position: prepend
+  select: "^HK"
+  group: { name: 香港自动, type: url-test }
+- op: delete
+  path: dns.fallback
+- op: set
+  path: sniffer.enable
+  value: false
+"#,
+        )
+        .unwrap();
+
+        run_script(&mut config, &script).expect("run script");
+
+        assert_eq!(names(&config, "proxy-groups"), vec!["香港自动", "节点选择"]);
+        assert_eq!(
+            config["proxy-groups"][0]["proxies"],
+            serde_yaml::from_str::<Value>("[HK-01]").unwrap()
+        );
+        assert!(config["dns"].get("fallback").is_none());
+        assert_eq!(config["sniffer"]["enable"].as_bool(), Some(false));
+    }
+
+    #[test]
+    fn flag_keywords_do_not_match_english_words() {
+        let flag_of = |name: &str| {
+            super::flag_matchers()
+                .into_iter()
+                .find(|(_, re)| re.is_match(name))
+                .map(|(flag, _)| flag)
+        };
+        let flag = super::flag_emoji;
+
+        assert_eq!(flag_of("HK 01"), Some(flag("HK")));
+        assert_eq!(flag_of("[IN] Mumbai"), Some(flag("IN")));
+        assert_eq!(flag_of("US-West|01"), Some(flag("US")));
+        assert_eq!(flag_of("Hong Kong 02"), Some(flag("HK")));
+        assert_eq!(flag_of("印度 01"), Some(flag("IN")));
+
+        // 与英文单词同形的小写缩写不视为地区代码
+        assert_eq!(flag_of("Expire in 30 days"), None);
+        assert_eq!(flag_of("Relay via US-de"), Some(flag("US")));
+        assert_eq!(flag_of("Mo speed ca tr node"), None);
+        assert_eq!(flag_of("IPLC to SG in Shanghai"), Some(flag("SG")));
+    }
+
+    #[test]
+    fn unknown_op_reports_step_index() {
+        let mut config = Mapping::new();
+        let script: Value = serde_yaml::from_str("- op: explode").unwrap();
+        let err = run_script(&mut config, &script).unwrap_err();
+        assert!(err.contains("#1"), "unexpected error: {err}");
+    }
+}
@@ -0,0 +1,641 @@
+//! 内核健康检查：定期探测控制接口、DNS 监听端口以及经 mixed-port 的出站连通性，
+//! 汇总为整体健康状态并保留最近的检查历史；连续失败时可选自动重启内核。
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::app::{app_state, current_timestamp};
+use crate::{ApiResponse, AppEvent, CoreOperationStatus};
+use crate::supervisor::RestartDecision;
+
+/// 保留的检查历史条数。
+const HEALTH_HISTORY_LIMIT: usize = 60;
+/// 健康检查关闭或内核未运行时重新读取配置的间隔。
+const HEALTH_IDLE_INTERVAL: Duration = Duration::from_secs(30);
+
+const MIN_INTERVAL_SECS: u64 = 10;
+const MAX_INTERVAL_SECS: u64 = 3600;
+const MAX_TIMEOUT_SECS: u64 = 30;
+const MAX_FAILURE_THRESHOLD: u32 = 20;
+
+/// 健康检查配置（`app.json` 中的 `core_health`）。
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct CoreHealthConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// 两次检查之间的间隔（秒）
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+    /// 单项探测的超时（秒）
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// 连续多少次检查不健康视为持续故障
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// 持续故障时是否自动重启内核
+    #[serde(default)]
+    pub auto_restart: bool,
+    /// DNS 探测解析的域名
+    #[serde(default = "default_test_domain")]
+    pub test_domain: String,
+    /// 出站探测经 mixed-port 请求的地址
+    #[serde(default = "default_test_url")]
+    pub test_url: String,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_interval_secs() -> u64 {
+    60
+}
+
+fn default_timeout_secs() -> u64 {
+    5
+}
+
+fn default_failure_threshold() -> u32 {
+    3
+}
+
+fn default_test_domain() -> String {
+    "www.gstatic.com".to_string()
+}
+
+fn default_test_url() -> String {
+    "https://www.gstatic.com/generate_204".to_string()
+}
+
+impl Default for CoreHealthConfig {
+    fn default() -> Self {
+        CoreHealthConfig {
+            enabled: default_enabled(),
+            interval_secs: default_interval_secs(),
+            timeout_secs: default_timeout_secs(),
+            failure_threshold: default_failure_threshold(),
+            auto_restart: false,
+            test_domain: default_test_domain(),
+            test_url: default_test_url(),
+        }
+    }
+}
+
+impl CoreHealthConfig {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if !(MIN_INTERVAL_SECS..=MAX_INTERVAL_SECS).contains(&self.interval_secs) {
+            return Err(format!(
+                "interval_secs must be between {MIN_INTERVAL_SECS} and {MAX_INTERVAL_SECS}"
+            ));
+        }
+        if !(1..=MAX_TIMEOUT_SECS).contains(&self.timeout_secs) {
+            return Err(format!("timeout_secs must be between 1 and {MAX_TIMEOUT_SECS}"));
+        }
+        if self.timeout_secs >= self.interval_secs {
+            return Err("timeout_secs must be less than interval_secs".to_string());
+        }
+        if !(1..=MAX_FAILURE_THRESHOLD).contains(&self.failure_threshold) {
+            return Err(format!(
+                "failure_threshold must be between 1 and {MAX_FAILURE_THRESHOLD}"
+            ));
+        }
+        if encode_dns_name(&self.test_domain).is_none() {
+            return Err(format!("invalid test_domain: {}", self.test_domain));
+        }
+        match reqwest::Url::parse(&self.test_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => return Err(format!("test_url must be an http(s) url: {}", self.test_url)),
+        }
+        Ok(())
+    }
+}
+
+/// 内核的整体健康状态。
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthState {
+    /// 尚未进行过检查
+    Unknown,
+    /// 健康检查已关闭
+    Disabled,
+    /// 内核未运行
+    Stopped,
+    /// 所有探测均通过
+    Healthy,
+    /// 控制接口可用，但 DNS 或出站探测失败
+    Degraded,
+    /// 控制接口不可用，或 DNS 与出站探测全部失败
+    Unhealthy,
+}
+
+/// 单项探测结果。
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProbeResult {
+    /// `controller` / `dns` / `outbound`
+    pub name: String,
+    pub ok: bool,
+    pub latency_ms: u64,
+    /// 成功时的补充信息：内核版本、解析结果或 HTTP 状态码
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// 一次完整检查的记录。
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct HealthCheckRecord {
+    state: HealthState,
+    checks: Vec<ProbeResult>,
+    timestamp: String,
+}
+
+/// 根据各项探测结果得出整体状态。
+fn composite_state(checks: &[ProbeResult]) -> HealthState {
+    let controller_ok = checks.iter().any(|c| c.name == "controller" && c.ok);
+    let data_plane: Vec<&ProbeResult> = checks.iter().filter(|c| c.name != "controller").collect();
+    if !controller_ok || (!data_plane.is_empty() && data_plane.iter().all(|c| !c.ok)) {
+        HealthState::Unhealthy
+    } else if data_plane.iter().all(|c| c.ok) {
+        HealthState::Healthy
+    } else {
+        HealthState::Degraded
+    }
+}
+
+struct HealthMonitorState {
+    state: HealthState,
+    /// 连续 unhealthy 的检查次数
+    consecutive_failures: u32,
+    history: VecDeque<HealthCheckRecord>,
+    /// 因持续故障自动重启内核的次数
+    restart_count: u32,
+}
+
+impl Default for HealthMonitorState {
+    fn default() -> Self {
+        HealthMonitorState {
+            state: HealthState::Unknown,
+            consecutive_failures: 0,
+            history: VecDeque::new(),
+            restart_count: 0,
+        }
+    }
+}
+
+static HEALTH: Mutex<Option<HealthMonitorState>> = Mutex::new(None);
+
+fn with_state<T>(f: impl FnOnce(&mut HealthMonitorState) -> T) -> T {
+    let mut guard = HEALTH.lock().expect("health mutex poisoned");
+    f(guard.get_or_insert_with(HealthMonitorState::default))
+}
+
+/// 更新整体状态，状态变化时广播 `CoreHealthChanged`。
+fn set_state(state: HealthState, checks: Vec<ProbeResult>) {
+    let (previous, consecutive_failures) = with_state(|s| {
+        let previous = s.state;
+        s.state = state;
+        // 只有 unhealthy 计入连续失败；degraded 时控制接口仍可用，不作为重启依据
+        if state == HealthState::Unhealthy {
+            s.consecutive_failures = s.consecutive_failures.saturating_add(1);
+        } else {
+            s.consecutive_failures = 0;
+        }
+        (previous, s.consecutive_failures)
+    });
+
+    if previous == state {
+        return;
+    }
+    tracing::info!("core health changed: {previous:?} -> {state:?}");
+    let event = AppEvent::CoreHealthChanged {
+        state,
+        previous_state: previous,
+        consecutive_failures,
+        checks,
+        timestamp: current_timestamp(),
+    };
+    if let Err(err) = app_state().events_tx.send(event) {
+        tracing::debug!("failed to broadcast AppEvent::CoreHealthChanged: {err}");
+    }
+}
+
+/// 编码 DNS 报文中的域名；域名不合法时返回 None。
+fn encode_dns_name(domain: &str) -> Option<Vec<u8>> {
+    let domain = domain.trim_end_matches('.');
+    if domain.is_empty() || domain.len() > 253 {
+        return None;
+    }
+    let mut out = Vec::with_capacity(domain.len() + 2);
+    for label in domain.split('.') {
+        if label.is_empty()
+            || label.len() > 63
+            || !label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        {
+            return None;
+        }
+        out.push(label.len() as u8);
+        out.extend_from_slice(label.as_bytes());
+    }
+    out.push(0);
+    Some(out)
+}
+
+/// 构造一个查询 A 记录的 DNS 请求报文（设置 RD 位）。
+fn build_dns_query(id: u16, domain: &str) -> Option<Vec<u8>> {
+    let name = encode_dns_name(domain)?;
+    let mut packet = Vec::with_capacity(12 + name.len() + 4);
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    packet.extend_from_slice(&name);
+    packet.extend_from_slice(&[0, 1, 0, 1]);
+    Some(packet)
+}
+
+/// 跳过报文中 `pos` 处的域名（支持压缩指针），返回其后的位置。
+fn skip_dns_name(packet: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(pos)?;
+        if len & 0xC0 == 0xC0 {
+            return Some(pos + 2);
+        }
+        if len == 0 {
+            return Some(pos + 1);
+        }
+        pos += 1 + len as usize;
+    }
+}
+
+/// 解析 DNS 响应，返回第一条 A 记录的地址。
+fn parse_dns_response(packet: &[u8], id: u16) -> Result<std::net::Ipv4Addr, String> {
+    let read_u16 = |pos: usize| -> Option<u16> {
+        Some(u16::from_be_bytes([*packet.get(pos)?, *packet.get(pos + 1)?]))
+    };
+    let truncated = || "truncated dns response".to_string();
+
+    if read_u16(0).ok_or_else(truncated)? != id {
+        return Err("dns response id mismatch".to_string());
+    }
+    let flags = read_u16(2).ok_or_else(truncated)?;
+    if flags & 0x8000 == 0 {
+        return Err("not a dns response".to_string());
+    }
+    let rcode = flags & 0x000F;
+    if rcode != 0 {
+        return Err(format!("dns server returned rcode {rcode}"));
+    }
+    let questions = read_u16(4).ok_or_else(truncated)?;
+    let answers = read_u16(6).ok_or_else(truncated)?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_dns_name(packet, pos).ok_or_else(truncated)? + 4;
+    }
+    for _ in 0..answers {
+        pos = skip_dns_name(packet, pos).ok_or_else(truncated)?;
+        let rtype = read_u16(pos).ok_or_else(truncated)?;
+        let rdlen = read_u16(pos + 8).ok_or_else(truncated)? as usize;
+        let rdata = packet.get(pos + 10..pos + 10 + rdlen).ok_or_else(truncated)?;
+        if rtype == 1 && rdlen == 4 {
+            return Ok(std::net::Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]));
+        }
+        pos += 10 + rdlen;
+    }
+    Err("dns response has no A record".to_string())
+}
+
+/// 执行一项探测并记录耗时。
+async fn probe<F>(name: &str, timeout: Duration, fut: F) -> ProbeResult
+where
+    F: std::future::Future<Output = Result<String, String>>,
+{
+    let started = Instant::now();
+    let result = match tokio::time::timeout(timeout, fut).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {}s", timeout.as_secs())),
+    };
+    let latency_ms = started.elapsed().as_millis() as u64;
+    match result {
+        Ok(detail) => ProbeResult {
+            name: name.to_string(),
+            ok: true,
+            latency_ms,
+            detail: Some(detail),
+            error: None,
+        },
+        Err(err) => ProbeResult {
+            name: name.to_string(),
+            ok: false,
+            latency_ms,
+            detail: None,
+            error: Some(err),
+        },
+    }
+}
+
+async fn probe_dns(port: u16, domain: &str) -> Result<String, String> {
+    let socket = tokio::net::UdpSocket::bind("127.0.0.1:0")
+        .await
+        .map_err(|err| format!("failed to bind udp socket: {err}"))?;
+    socket
+        .connect(("127.0.0.1", port))
+        .await
+        .map_err(|err| format!("failed to connect to dns listener on port {port}: {err}"))?;
+
+    let id = rand_core::RngCore::next_u32(&mut rand_core::OsRng) as u16;
+    let query = build_dns_query(id, domain).ok_or_else(|| format!("invalid domain: {domain}"))?;
+    socket
+        .send(&query)
+        .await
+        .map_err(|err| format!("failed to send dns query: {err}"))?;
+
+    let mut buf = [0u8; 1500];
+    let n = socket
+        .recv(&mut buf)
+        .await
+        .map_err(|err| format!("failed to receive dns response: {err}"))?;
+    parse_dns_response(&buf[..n], id).map(|addr| format!("{domain} -> {addr}"))
+}
+
+async fn probe_outbound(port: u16, url: &str, timeout: Duration) -> Result<String, String> {
+    let proxy = reqwest::Proxy::all(format!("http://127.0.0.1:{port}"))
+        .map_err(|err| format!("invalid core proxy address: {err}"))?;
+    let client = reqwest::ClientBuilder::new()
+        .user_agent(crate::app::DEFAULT_USER_AGENT)
+        .timeout(timeout)
+        .proxy(proxy)
+        .build()
+        .map_err(|err| format!("failed to build http client: {err}"))?;
+    let resp = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|err| format!("request via mixed-port {port} failed: {err}"))?;
+    let status = resp.status();
+    if status.is_client_error() || status.is_server_error() {
+        return Err(format!("request via mixed-port {port} returned HTTP {}", status.as_u16()));
+    }
+    Ok(format!("HTTP {}", status.as_u16()))
+}
+
+/// 立即执行一次完整检查并记录结果；内核未运行时返回 None。
+async fn run_check(config: &CoreHealthConfig) -> Option<HealthCheckRecord> {
+    let root = app_state().data_root.clone();
+    if !crate::core::core_running_status(&root).0 {
+        set_state(HealthState::Stopped, Vec::new());
+        return None;
+    }
+
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let dns_port = crate::system_config::dns_listen_port(&root);
+    let mixed_port = crate::system_config::mixed_port(&root);
+    let (controller, dns, outbound) = tokio::join!(
+        probe("controller", timeout, crate::mihomo::query_core_version(&root)),
+        probe("dns", timeout, probe_dns(dns_port, &config.test_domain)),
+        probe("outbound", timeout, probe_outbound(mixed_port, &config.test_url, timeout)),
+    );
+    let checks = vec![controller, dns, outbound];
+    let state = composite_state(&checks);
+
+    let record = HealthCheckRecord {
+        state,
+        checks: checks.clone(),
+        timestamp: current_timestamp(),
+    };
+    with_state(|s| {
+        if s.history.len() == HEALTH_HISTORY_LIMIT {
+            s.history.pop_front();
+        }
+        s.history.push_back(record.clone());
+    });
+    set_state(state, checks);
+    Some(record)
+}
+
+/// 连续失败达到阈值且开启了 `auto_restart` 时重启内核。
+async fn restart_if_sustained_failure(config: &CoreHealthConfig) {
+    let failures = with_state(|s| s.consecutive_failures);
+    if failures < config.failure_threshold {
+        return;
+    }
+    if !config.auto_restart {
+        if failures == config.failure_threshold {
+            tracing::warn!("core has been unhealthy for {failures} consecutive checks");
+        }
+        return;
+    }
+
+    // 与崩溃重启共用 supervisor 的退避与崩溃循环检测，避免反复重启一个无法恢复的内核
+    let restarted = match crate::supervisor::register_health_failure() {
+        RestartDecision::Restart(delay) => {
+            tracing::warn!(
+                "core has been unhealthy for {failures} consecutive checks; restarting core in {delay:?}"
+            );
+            tokio::time::sleep(delay).await;
+            let restarted = if crate::core::core_running_status(&app_state().data_root).0 {
+                let Json(resp) = crate::core_async::restart_core_for_health().await;
+                if resp.code != "ok" {
+                    tracing::warn!(
+                        "health check failed to request core restart: code={}, message={}",
+                        resp.code,
+                        resp.message
+                    );
+                }
+                resp.code == "ok"
+            } else {
+                // 等待期间内核已被停止（或已退出交由崩溃守护处理），不再重启
+                tracing::info!("core is no longer running; skip health restart");
+                false
+            };
+            crate::supervisor::clear_next_restart();
+            restarted
+        }
+        RestartDecision::CrashLoop | RestartDecision::NotRestarting => {
+            tracing::error!(
+                "core has been unhealthy for {failures} consecutive checks, but it failed too often recently; automatic restart stopped"
+            );
+            false
+        }
+    };
+    let restart_count = with_state(|s| {
+        if restarted {
+            s.restart_count = s.restart_count.saturating_add(1);
+        }
+        // 重新开始计数，避免每次检查都触发重启或重复计入崩溃窗口
+        s.consecutive_failures = 0;
+        s.restart_count
+    });
+
+    let event = AppEvent::CoreHealthRestart {
+        consecutive_failures: failures,
+        restart_requested: restarted,
+        restart_count,
+        timestamp: current_timestamp(),
+    };
+    if let Err(err) = app_state().events_tx.send(event) {
+        tracing::debug!("failed to broadcast AppEvent::CoreHealthRestart: {err}");
+    }
+}
+
+/// 是否有启动 / 停止等内核操作正在执行（此时跳过检查，避免误判）。
+async fn core_operation_running() -> bool {
+    let guard = app_state().core_operation.lock().await;
+    guard
+        .as_ref()
+        .is_some_and(|op| matches!(op.status, CoreOperationStatus::Running))
+}
+
+/// 后台健康检查循环。
+pub(crate) async fn run_health_check_loop() {
+    loop {
+        let config = crate::get_app_config_snapshot().core_health;
+        if !config.enabled {
+            set_state(HealthState::Disabled, Vec::new());
+            tokio::time::sleep(HEALTH_IDLE_INTERVAL).await;
+            continue;
+        }
+
+        if !core_operation_running().await && run_check(&config).await.is_some() {
+            restart_if_sustained_failure(&config).await;
+        }
+
+        let wait = match with_state(|s| s.state) {
+            HealthState::Stopped => HEALTH_IDLE_INTERVAL.min(Duration::from_secs(config.interval_secs)),
+            _ => Duration::from_secs(config.interval_secs),
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// `GET /api/core/status` 中返回的健康摘要。
+#[derive(Serialize)]
+pub struct HealthSummaryDto {
+    state: HealthState,
+    consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_checked_at: Option<String>,
+}
+
+pub(crate) fn summary_snapshot() -> HealthSummaryDto {
+    with_state(|s| HealthSummaryDto {
+        state: s.state,
+        consecutive_failures: s.consecutive_failures,
+        last_checked_at: s.history.back().map(|r| r.timestamp.clone()),
+    })
+}
+
+#[derive(Serialize)]
+pub struct HealthStatusDto {
+    state: HealthState,
+    consecutive_failures: u32,
+    restart_count: u32,
+    config: CoreHealthConfig,
+    /// 按时间先后排列的检查历史
+    history: Vec<HealthCheckRecord>,
+}
+
+fn status_dto() -> HealthStatusDto {
+    let config = crate::get_app_config_snapshot().core_health;
+    with_state(|s| HealthStatusDto {
+        state: s.state,
+        consecutive_failures: s.consecutive_failures,
+        restart_count: s.restart_count,
+        config,
+        history: s.history.iter().cloned().collect(),
+    })
+}
+
+pub async fn get_core_health() -> Json<ApiResponse<HealthStatusDto>> {
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "success".to_string(),
+        data: Some(status_dto()),
+    })
+}
+
+/// 立即执行一次健康检查（不触发自动重启）。
+pub async fn check_core_health() -> Json<ApiResponse<HealthStatusDto>> {
+    let config = crate::get_app_config_snapshot().core_health;
+    if run_check(&config).await.is_none() {
+        return Json(ApiResponse {
+            code: "core_not_running".to_string(),
+            message: "core is not running".to_string(),
+            data: Some(status_dto()),
+        });
+    }
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "success".to_string(),
+        data: Some(status_dto()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CoreHealthConfig, HealthState, ProbeResult, build_dns_query, composite_state,
+        parse_dns_response,
+    };
+
+    fn check(name: &str, ok: bool) -> ProbeResult {
+        ProbeResult {
+            name: name.to_string(),
+            ok,
+            latency_ms: 1,
+            detail: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn dns_query_and_response_roundtrip() {
+        let query = build_dns_query(0x1234, "www.gstatic.com").unwrap();
+        assert_eq!(&query[..4], &[0x12, 0x34, 0x01, 0x00]);
+        assert!(build_dns_query(1, "bad domain").is_none());
+        assert!(build_dns_query(1, "a..b").is_none());
+
+        // 响应 = 查询头（置 QR 位、ancount=2）+ 问题 + CNAME 与 A 两条压缩名记录
+        let mut resp = query.clone();
+        resp[2] = 0x81;
+        resp[3] = 0x80;
+        resp[7] = 2;
+        resp.extend_from_slice(&[0xC0, 0x0C, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xC0, 0x0C]);
+        resp.extend_from_slice(&[0xC0, 0x0C, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 198, 18, 0, 7]);
+        assert_eq!(
+            parse_dns_response(&resp, 0x1234).unwrap(),
+            std::net::Ipv4Addr::new(198, 18, 0, 7)
+        );
+        assert!(parse_dns_response(&resp, 0x4321).is_err());
+        assert!(parse_dns_response(&resp[..resp.len() - 2], 0x1234).is_err());
+
+        // NXDOMAIN
+        let mut nx = query.clone();
+        nx[2] = 0x81;
+        nx[3] = 0x83;
+        assert!(parse_dns_response(&nx, 0x1234).unwrap_err().contains("rcode 3"));
+    }
+
+    #[test]
+    fn composite_state_and_config_validation() {
+        let all = |c, d, o| vec![check("controller", c), check("dns", d), check("outbound", o)];
+        assert_eq!(composite_state(&all(true, true, true)), HealthState::Healthy);
+        assert_eq!(composite_state(&all(true, false, true)), HealthState::Degraded);
+        assert_eq!(composite_state(&all(true, false, false)), HealthState::Unhealthy);
+        assert_eq!(composite_state(&all(false, true, true)), HealthState::Unhealthy);
+
+        assert!(CoreHealthConfig::default().validate().is_ok());
+        for invalid in [
+            CoreHealthConfig { interval_secs: 5, ..Default::default() },
+            CoreHealthConfig { timeout_secs: 60, interval_secs: 60, ..Default::default() },
+            CoreHealthConfig { failure_threshold: 0, ..Default::default() },
+            CoreHealthConfig { test_domain: "not a domain".to_string(), ..Default::default() },
+            CoreHealthConfig { test_url: "ftp://example.com".to_string(), ..Default::default() },
+        ] {
+            assert!(invalid.validate().is_err());
+        }
+    }
+}
@@ -0,0 +1,678 @@
+//! 配置历史。
+//!
+//! 包含两部分：
+//! - 已成功应用的 merged.yaml（last-known-good）：每当内核成功加载一份 merged.yaml
+//!   并稳定运行后，将其复制到 `<DATA_ROOT>/config/history/merged-<毫秒时间戳>.yaml`；
+//!   重载失败或内核在重载后很快退出时，由 `config_manager` 从这里恢复上一份可用配置。
+//! - 修订记录（revision store）：用户 profile、订阅 subscription.yaml 与 merged.yaml
+//!   每次写入后都会保存一份修订到 `<DATA_ROOT>/config/history/revisions/<rev>.yaml`，
+//!   索引位于 `history/revisions.json`。修订数量与总大小均有上限，并在磁盘剩余空间
+//!   不足时停止记录（与日志模块一致，照顾 /jffs 等小容量分区）。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use axum::extract::{Path as AxumPath, Query};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::app::{app_state, current_timestamp, write_file_atomically};
+use crate::{ApiResponse, AppConfig, ConfigChangeReason, ProfileType};
+
+/// 最多保留的可用 merged 配置份数。
+const MERGED_HISTORY_LIMIT: usize = 5;
+const MERGED_HISTORY_PREFIX: &str = "merged-";
+
+pub(crate) fn history_dir(root: &Path) -> PathBuf {
+    root.join("config").join("history")
+}
+
+/// 列出已记录的可用 merged 配置，按时间从新到旧排序，返回 (revision, 文件路径)。
+pub(crate) fn list_good_merged_configs(root: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = fs::read_dir(history_dir(root)) else {
+        return Vec::new();
+    };
+
+    let mut items: Vec<(String, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let rev = name
+                .strip_prefix(MERGED_HISTORY_PREFIX)?
+                .strip_suffix(".yaml")?
+                .to_string();
+            rev.parse::<u64>().ok()?;
+            Some((rev, entry.path()))
+        })
+        .collect();
+
+    // revision 为定长数字，按字符串倒序即为时间倒序
+    items.sort_by(|a, b| b.0.cmp(&a.0));
+    items
+}
+
+/// 将当前 merged.yaml 记录为一份可用配置。
+///
+/// 与最近一份记录内容相同时不会重复保存，返回 `Ok(None)`；
+/// 超出保留上限的旧记录会被清理。
+pub(crate) fn record_good_merged_config(root: &PathBuf) -> Result<Option<String>, String> {
+    let merged_path = crate::user_profiles::merged_config_path(root);
+    let content = fs::read_to_string(&merged_path)
+        .map_err(|err| format!("failed to read {}: {err}", merged_path.display()))?;
+
+    let existing = list_good_merged_configs(root);
+    if let Some((_, latest)) = existing.first()
+        && fs::read_to_string(latest).is_ok_and(|c| c == content)
+    {
+        return Ok(None);
+    }
+
+    let dir = history_dir(root);
+    fs::create_dir_all(&dir)
+        .map_err(|err| format!("failed to create history dir {}: {err}", dir.display()))?;
+
+    let mut millis = chrono::Utc::now().timestamp_millis().max(0) as u64;
+    // 同一毫秒内多次记录时顺延，保证 revision 单调递增
+    if let Some(latest) = existing.first().and_then(|(rev, _)| rev.parse::<u64>().ok()) {
+        millis = millis.max(latest + 1);
+    }
+    let rev = format!("{millis:016}");
+    let path = dir.join(format!("{MERGED_HISTORY_PREFIX}{rev}.yaml"));
+    write_file_atomically(&path, &content)?;
+
+    for (old_rev, old_path) in existing.iter().skip(MERGED_HISTORY_LIMIT - 1) {
+        if let Err(err) = fs::remove_file(old_path) {
+            tracing::warn!("failed to prune merged config history {old_rev}: {err}");
+        }
+    }
+
+    Ok(Some(rev))
+}
+
+/// 用最近一份与当前内容不同的可用配置覆盖 merged.yaml。
+///
+/// 返回被恢复的 revision；没有可回滚的记录时返回 `Ok(None)`。
+pub(crate) fn rollback_merged_config(root: &PathBuf) -> Result<Option<String>, String> {
+    let merged_path = crate::user_profiles::merged_config_path(root);
+    let current = fs::read_to_string(&merged_path).unwrap_or_default();
+
+    for (rev, path) in list_good_merged_configs(root) {
+        let content = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(err) => {
+                tracing::warn!("failed to read merged config history {rev}: {err}");
+                continue;
+            }
+        };
+        if content == current {
+            continue;
+        }
+
+        write_file_atomically(&merged_path, &content)?;
+        crate::system_config::invalidate_cached_values();
+        record_revision_or_warn(root, RevisionKind::Merged, None, &content);
+        return Ok(Some(rev));
+    }
+
+    Ok(None)
+}
+
+/// 每个目标（某个用户 profile / 订阅 / merged.yaml）最多保留的修订数。
+const REVISIONS_PER_TARGET_LIMIT: usize = 10;
+/// 修订记录的总大小上限。
+const REVISIONS_MAX_TOTAL_BYTES: u64 = 4 * 1_024 * 1_024;
+
+/// 修订索引的读写锁，避免并发写入时索引被覆盖。
+static REVISION_INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionKind {
+    UserProfile,
+    Subscription,
+    Merged,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RevisionMeta {
+    /// 定长的毫秒时间戳，单调递增，同时作为修订 id
+    pub rev: String,
+    pub kind: RevisionKind,
+    /// 用户 profile / 订阅的 id；merged.yaml 为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+    pub created_at: String,
+    pub size: u64,
+}
+
+fn revisions_dir(root: &Path) -> PathBuf {
+    history_dir(root).join("revisions")
+}
+
+fn revisions_index_path(root: &Path) -> PathBuf {
+    history_dir(root).join("revisions.json")
+}
+
+fn revision_file_path(root: &Path, rev: &str) -> PathBuf {
+    revisions_dir(root).join(format!("{rev}.yaml"))
+}
+
+/// 读取修订索引，按时间从旧到新排列。
+fn load_revision_index(root: &Path) -> Vec<RevisionMeta> {
+    let path = revisions_index_path(root);
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+            tracing::warn!("failed to parse revision index {}: {err}", path.display());
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn save_revision_index(root: &Path, index: &[RevisionMeta]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(index)
+        .map_err(|err| format!("failed to serialize revision index: {err}"))?;
+    write_file_atomically(&revisions_index_path(root), content)
+}
+
+fn remove_revision_file(root: &Path, meta: &RevisionMeta) {
+    if let Err(err) = fs::remove_file(revision_file_path(root, &meta.rev))
+        && err.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::warn!("failed to remove revision {}: {err}", meta.rev);
+    }
+}
+
+/// 按上限裁剪修订：先限制每个目标的数量，再按总大小从最旧的开始删除。
+fn prune_revisions(root: &Path, index: &mut Vec<RevisionMeta>, max_total_bytes: u64) {
+    let mut keep = vec![true; index.len()];
+
+    let mut seen: Vec<((RevisionKind, Option<&str>), usize)> = Vec::new();
+    for (idx, meta) in index.iter().enumerate().rev() {
+        let key = (meta.kind, meta.target_id.as_deref());
+        let count = match seen.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, count)) => {
+                *count += 1;
+                *count
+            }
+            None => {
+                seen.push((key, 1));
+                1
+            }
+        };
+        if count > REVISIONS_PER_TARGET_LIMIT {
+            keep[idx] = false;
+        }
+    }
+
+    let mut total: u64 = index
+        .iter()
+        .zip(&keep)
+        .filter(|(_, keep)| **keep)
+        .map(|(meta, _)| meta.size)
+        .sum();
+    for (idx, meta) in index.iter().enumerate() {
+        if total <= max_total_bytes {
+            break;
+        }
+        if keep[idx] {
+            keep[idx] = false;
+            total = total.saturating_sub(meta.size);
+        }
+    }
+
+    let mut idx = 0;
+    index.retain(|meta| {
+        let retained = keep[idx];
+        idx += 1;
+        if !retained {
+            remove_revision_file(root, meta);
+        }
+        retained
+    });
+}
+
+/// 修订可用的总空间：不超过上限，也不超过磁盘剩余空间扣除日志保留空间后的一半。
+fn effective_revisions_max_bytes(root: &Path, used: u64) -> u64 {
+    let dir = history_dir(root);
+    match fs2::available_space(&dir) {
+        Ok(free) => {
+            let spare = free.saturating_sub(crate::logs::LOG_MIN_FREE_SPACE_BYTES) / 2;
+            REVISIONS_MAX_TOTAL_BYTES.min(used.saturating_add(spare))
+        }
+        Err(_) => REVISIONS_MAX_TOTAL_BYTES,
+    }
+}
+
+/// 保存一次写入后的内容为新的修订。
+///
+/// 与该目标最近一份修订内容相同时不重复记录；磁盘空间不足时跳过记录并返回 `Ok(None)`。
+pub(crate) fn record_revision(
+    root: &Path,
+    kind: RevisionKind,
+    target_id: Option<&str>,
+    content: &str,
+) -> Result<Option<String>, String> {
+    let _guard = REVISION_INDEX_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let mut index = load_revision_index(root);
+
+    if let Some(latest) = index
+        .iter()
+        .rev()
+        .find(|m| m.kind == kind && m.target_id.as_deref() == target_id)
+        && fs::read_to_string(revision_file_path(root, &latest.rev)).is_ok_and(|c| c == content)
+    {
+        return Ok(None);
+    }
+
+    let dir = revisions_dir(root);
+    fs::create_dir_all(&dir)
+        .map_err(|err| format!("failed to create revision dir {}: {err}", dir.display()))?;
+
+    let size = content.len() as u64;
+    let used: u64 = index.iter().map(|m| m.size).sum();
+    let max_total = effective_revisions_max_bytes(root, used);
+    if size > max_total {
+        tracing::warn!(
+            "skip recording {kind:?} revision ({size} bytes): history space limit is {max_total} bytes"
+        );
+        return Ok(None);
+    }
+
+    let mut millis = chrono::Utc::now().timestamp_millis().max(0) as u64;
+    if let Some(latest) = index.last().and_then(|m| m.rev.parse::<u64>().ok()) {
+        millis = millis.max(latest + 1);
+    }
+    let rev = format!("{millis:016}");
+
+    write_file_atomically(&revision_file_path(root, &rev), content)?;
+
+    index.push(RevisionMeta {
+        rev: rev.clone(),
+        kind,
+        target_id: target_id.map(str::to_string),
+        created_at: current_timestamp(),
+        size,
+    });
+    prune_revisions(root, &mut index, max_total);
+    save_revision_index(root, &index)?;
+
+    Ok(Some(rev))
+}
+
+/// 记录修订失败不应影响正常的写入流程，仅记录日志。
+pub(crate) fn record_revision_or_warn(
+    root: &Path,
+    kind: RevisionKind,
+    target_id: Option<&str>,
+    content: &str,
+) {
+    if let Err(err) = record_revision(root, kind, target_id, content) {
+        tracing::warn!("failed to record {kind:?} revision: {err}");
+    }
+}
+
+fn find_revision(root: &Path, rev: &str) -> Option<(RevisionMeta, Option<RevisionMeta>)> {
+    let index = load_revision_index(root);
+    let pos = index.iter().position(|m| m.rev == rev)?;
+    let meta = index[pos].clone();
+    let previous = index[..pos]
+        .iter()
+        .rev()
+        .find(|m| m.kind == meta.kind && m.target_id == meta.target_id)
+        .cloned();
+    Some((meta, previous))
+}
+
+fn read_revision(root: &Path, rev: &str) -> Result<String, String> {
+    let path = revision_file_path(root, rev);
+    fs::read_to_string(&path).map_err(|err| format!("failed to read revision {rev}: {err}"))
+}
+
+/// 修订对应的当前文件路径；目标已被删除时返回 None。
+fn revision_target_path(root: &PathBuf, config: &AppConfig, meta: &RevisionMeta) -> Option<PathBuf> {
+    match meta.kind {
+        RevisionKind::Merged => Some(crate::user_profiles::merged_config_path(root)),
+        RevisionKind::UserProfile | RevisionKind::Subscription => {
+            let id = meta.target_id.as_deref()?;
+            let profile = config.profiles.iter().find(|p| {
+                p.id == id
+                    && match meta.kind {
+                        RevisionKind::UserProfile => matches!(p.profile_type, ProfileType::User),
+                        _ => matches!(p.profile_type, ProfileType::Remote),
+                    }
+            })?;
+            let mut path = root.clone();
+            path.push("config");
+            path.push(&profile.path);
+            Some(path)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HistoryListQuery {
+    #[serde(default)]
+    pub kind: Option<RevisionKind>,
+    #[serde(default)]
+    pub target_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct HistoryListResponse {
+    /// 按时间从新到旧排列
+    pub revisions: Vec<RevisionMeta>,
+    pub total_bytes: u64,
+}
+
+pub async fn list_history(
+    Query(query): Query<HistoryListQuery>,
+) -> Json<ApiResponse<HistoryListResponse>> {
+    let state = app_state();
+    let index = load_revision_index(&state.data_root);
+    let total_bytes = index.iter().map(|m| m.size).sum();
+
+    let revisions = index
+        .into_iter()
+        .rev()
+        .filter(|m| query.kind.is_none_or(|kind| m.kind == kind))
+        .filter(|m| {
+            query
+                .target_id
+                .as_deref()
+                .is_none_or(|id| m.target_id.as_deref() == Some(id))
+        })
+        .collect();
+
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "success".to_string(),
+        data: Some(HistoryListResponse {
+            revisions,
+            total_bytes,
+        }),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct HistoryDiffQuery {
+    /// `previous`（默认）：与同一目标的上一份修订比较；`current`：与目标当前文件比较
+    #[serde(default)]
+    pub against: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct HistoryDiffResponse {
+    pub rev: String,
+    pub against: String,
+    /// 参与比较的上一份修订；与当前文件比较或没有更早修订时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_rev: Option<String>,
+    pub diff: String,
+}
+
+pub async fn get_history_diff(
+    AxumPath(rev): AxumPath<String>,
+    Query(query): Query<HistoryDiffQuery>,
+) -> Json<ApiResponse<HistoryDiffResponse>> {
+    let state = app_state();
+    let root = &state.data_root;
+
+    let Some((meta, previous)) = find_revision(root, &rev) else {
+        return Json(ApiResponse {
+            code: "history_revision_not_found".to_string(),
+            message: format!("revision not found: {rev}"),
+            data: None,
+        });
+    };
+
+    let content = match read_revision(root, &meta.rev) {
+        Ok(c) => c,
+        Err(err) => {
+            return Json(ApiResponse {
+                code: "history_read_failed".to_string(),
+                message: err,
+                data: None,
+            });
+        }
+    };
+
+    let against = query.against.unwrap_or_else(|| "previous".to_string());
+    let (base, base_label, previous_rev) = match against.as_str() {
+        "previous" => match previous {
+            Some(prev) => match read_revision(root, &prev.rev) {
+                Ok(c) => (c, prev.rev.clone(), Some(prev.rev)),
+                Err(err) => {
+                    return Json(ApiResponse {
+                        code: "history_read_failed".to_string(),
+                        message: err,
+                        data: None,
+                    });
+                }
+            },
+            None => (String::new(), "(none)".to_string(), None),
+        },
+        "current" => {
+            let config = crate::get_app_config_snapshot();
+            let current = revision_target_path(root, &config, &meta)
+                .and_then(|path| fs::read_to_string(path).ok())
+                .unwrap_or_default();
+            (current, "current".to_string(), None)
+        }
+        other => {
+            return Json(ApiResponse {
+                code: "history_invalid_query".to_string(),
+                message: format!("unsupported diff base '{other}', expected 'previous' or 'current'"),
+                data: None,
+            });
+        }
+    };
+
+    let diff = crate::user_profiles::unified_diff(&base, &content, &base_label, &meta.rev);
+
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "success".to_string(),
+        data: Some(HistoryDiffResponse {
+            rev: meta.rev,
+            against,
+            previous_rev,
+            diff,
+        }),
+    })
+}
+
+/// 将目标文件恢复为指定修订的内容，并按需重新生成 / 应用配置。
+///
+/// - 用户 profile / 订阅：写回对应文件，若其参与当前合并则重新生成 merged.yaml 并重载，
+///   重新生成失败时写回原内容；
+/// - merged.yaml：经语义校验与内核测试后替换并重载，不重新生成（否则会被当前配置覆盖）。
+pub async fn restore_history_revision(
+    AxumPath(rev): AxumPath<String>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let state = app_state();
+    let root = &state.data_root;
+
+    let Some((meta, _)) = find_revision(root, &rev) else {
+        return Json(ApiResponse {
+            code: "history_revision_not_found".to_string(),
+            message: format!("revision not found: {rev}"),
+            data: None,
+        });
+    };
+
+    let content = match read_revision(root, &meta.rev) {
+        Ok(c) => c,
+        Err(err) => {
+            return Json(ApiResponse {
+                code: "history_read_failed".to_string(),
+                message: err,
+                data: None,
+            });
+        }
+    };
+
+    let config = crate::get_app_config_snapshot();
+    let Some(target) = revision_target_path(root, &config, &meta) else {
+        return Json(ApiResponse {
+            code: "history_target_not_found".to_string(),
+            message: format!("target of revision {rev} no longer exists"),
+            data: None,
+        });
+    };
+
+    let target_id = meta.target_id.as_deref();
+    let (affects_merged, reason) = match meta.kind {
+        RevisionKind::Merged => (false, ConfigChangeReason::Other),
+        RevisionKind::UserProfile => (
+            target_id.is_some_and(|id| config.active_user_profile_ids.iter().any(|a| a == id)),
+            ConfigChangeReason::UserProfileUpdated,
+        ),
+        RevisionKind::Subscription => (
+            target_id.is_some_and(|id| {
+                config.active_subscription_id.as_deref() == Some(id)
+                    || (config.combined_subscription.enabled
+                        && config.combined_subscription.subscription_ids.iter().any(|s| s == id))
+            }),
+            ConfigChangeReason::SubscriptionFetched,
+        ),
+    };
+
+    // merged.yaml 修订不经过重新生成，但同样需要通过语义校验与内核测试后才替换
+    if meta.kind == RevisionKind::Merged {
+        if let Err(err) = crate::user_profiles::apply_merged_config_content(root, &content).await
+        {
+            tracing::error!("failed to restore merged config revision {rev}: {err}");
+            return Json(ApiResponse {
+                code: err.code().to_string(),
+                message: err.to_string(),
+                data: err.issues_data(),
+            });
+        }
+    } else {
+        let previous = fs::read_to_string(&target).ok();
+        if let Err(err) = write_file_atomically(&target, &content) {
+            tracing::error!("failed to restore revision {rev}: {err}");
+            return Json(ApiResponse {
+                code: "history_restore_failed".to_string(),
+                message: err,
+                data: None,
+            });
+        }
+
+        if affects_merged
+            && let Err(err) = crate::user_profiles::generate_merged_config(root).await
+        {
+            tracing::error!("failed to generate merged config after history restore: {err}");
+            // 合并被拒绝时写回原内容，避免下一次合并悄悄用上这份被拒绝的修订
+            let restored = match &previous {
+                Some(previous) => write_file_atomically(&target, previous),
+                None => fs::remove_file(&target).map_err(|err| err.to_string()),
+            };
+            if let Err(restore_err) = restored {
+                tracing::error!(
+                    "failed to restore {} after rejected history restore: {restore_err}",
+                    target.display()
+                );
+            }
+            return Json(ApiResponse {
+                code: err.code().to_string(),
+                message: err.to_string(),
+                data: err.issues_data(),
+            });
+        }
+        record_revision_or_warn(root, meta.kind, meta.target_id.as_deref(), &content);
+    }
+
+    let core_reload = if affects_merged || meta.kind == RevisionKind::Merged {
+        Some(crate::config_manager::reload_core_if_running(reason).await)
+    } else {
+        None
+    };
+
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "restored".to_string(),
+        data: Some(serde_json::json!({
+            "rev": meta.rev,
+            "kind": meta.kind,
+            "target_id": meta.target_id,
+            "core_reload": core_reload,
+        })),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        RevisionKind, list_good_merged_configs, load_revision_index, record_good_merged_config,
+        record_revision, rollback_merged_config,
+    };
+    use crate::test_support::TempRoot;
+    use crate::user_profiles::merged_config_path;
+    use std::fs;
+
+    fn temp_root() -> TempRoot {
+        let root = TempRoot::new("history");
+        fs::create_dir_all(root.join("config")).unwrap();
+        root
+    }
+
+    #[test]
+    fn records_and_rolls_back_to_previous_good_config() {
+        let root = temp_root();
+        let merged = merged_config_path(&root);
+
+        fs::write(&merged, "mode: rule\n").unwrap();
+        let first = record_good_merged_config(&root).unwrap().expect("first record");
+        // 内容未变化时不重复记录
+        assert_eq!(record_good_merged_config(&root).unwrap(), None);
+
+        fs::write(&merged, "mode: global\n").unwrap();
+        let second = record_good_merged_config(&root).unwrap().expect("second record");
+        assert_eq!(list_good_merged_configs(&root).len(), 2);
+
+        // 新写入的配置加载失败：回滚到与当前内容不同的最近一份
+        fs::write(&merged, "mode: broken\n").unwrap();
+        assert_eq!(rollback_merged_config(&root).unwrap(), Some(second));
+        assert_eq!(fs::read_to_string(&merged).unwrap(), "mode: global\n");
+
+        // 已是最新可用配置时，继续回滚到更早的一份
+        assert_eq!(rollback_merged_config(&root).unwrap(), Some(first));
+        assert_eq!(fs::read_to_string(&merged).unwrap(), "mode: rule\n");
+    }
+
+    #[test]
+    fn revisions_are_deduplicated_and_bounded_per_target() {
+        let root = temp_root();
+
+        for i in 0..15 {
+            let content = format!("rules: [{i}]\n");
+            record_revision(&root, RevisionKind::UserProfile, Some("u1"), &content).unwrap();
+            // 重复写入相同内容不产生新修订
+            assert_eq!(
+                record_revision(&root, RevisionKind::UserProfile, Some("u1"), &content).unwrap(),
+                None
+            );
+        }
+        record_revision(&root, RevisionKind::Merged, None, "mode: rule\n").unwrap();
+
+        let index = load_revision_index(&root);
+        let profile_revs: Vec<_> = index
+            .iter()
+            .filter(|m| m.kind == RevisionKind::UserProfile)
+            .collect();
+        assert_eq!(profile_revs.len(), super::REVISIONS_PER_TARGET_LIMIT);
+        // 保留的是最新的修订
+        let newest = super::read_revision(&root, &profile_revs.last().unwrap().rev).unwrap();
+        assert_eq!(newest, "rules: [14]\n");
+        assert_eq!(index.iter().filter(|m| m.kind == RevisionKind::Merged).count(), 1);
+
+        let files = std::fs::read_dir(super::revisions_dir(&root)).unwrap().count();
+        assert_eq!(files, index.len());
+    }
+}
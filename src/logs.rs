@@ -1,4 +1,5 @@
 use axum::Json;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
@@ -167,71 +168,282 @@ pub fn write_log_with_rotation_and_space_guard(
     file.write(buf)
 }
 
-/// 从异步读取端持续读取数据并写入日志文件。
-/// 用于将子进程的 stdout/stderr 管道内容转发到指定日志文件。
-pub fn spawn_log_pipe_task<R>(
-    mut reader: R,
+/// 跟踪 spool 文件时的轮询间隔。
+const LOG_TAIL_POLL_INTERVAL_MS: u64 = 200;
+/// spool 中允许积压的未读字节数上限，超出时丢弃较早的输出（例如 camofy 长时间未运行）。
+const SPOOL_MAX_BACKLOG_BYTES: u64 = 1_024 * 1_024;
+
+/// 读取 spool 文件中 `offset` 之后新增的内容并交给 `on_chunk` 处理。
+///
+/// 写入方（内核）以追加模式持有同一文件，截断无法与它的写入互斥，会丢掉检查与截断之间
+/// 追加的输出。因此读完后不截断，而是释放已读部分占用的空间（punch hole）：文件逻辑长度
+/// 继续增长，实际占用只有尚未读取的部分。`offset` 丢失（camofy 重启）时从第一段数据开始读。
+fn drain_spool_file(
+    spool: &Path,
+    offset: &mut u64,
+    mut on_chunk: impl FnMut(&[u8]),
+) -> std::io::Result<()> {
+    use std::fs::OpenOptions;
+    use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+    let mut file = match OpenOptions::new().read(true).write(true).open(spool) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    let len = file.metadata()?.len();
+    // 新的内核启动时会清空 spool，从头读取
+    if len < *offset {
+        *offset = 0;
+    }
+    // 跳过已释放的空洞；空洞之后的首个数据块中已读部分被填充为 0，一并跳过
+    let data_start = spool_data_start(&file, *offset, len);
+    let mut skip_zeros = data_start > *offset;
+    *offset = data_start;
+    if len - *offset > SPOOL_MAX_BACKLOG_BYTES {
+        let skipped = len - SPOOL_MAX_BACKLOG_BYTES - *offset;
+        tracing::warn!(
+            "{} has too much unread output, dropping the oldest {skipped} bytes",
+            spool.display()
+        );
+        *offset = len - SPOOL_MAX_BACKLOG_BYTES;
+        skip_zeros = false;
+    }
+    file.seek(SeekFrom::Start(*offset))?;
+
+    let read_from = *offset;
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        *offset += n as u64;
+        let mut chunk = &buf[..n];
+        if skip_zeros {
+            let start = chunk.iter().position(|b| *b != 0).unwrap_or(n);
+            chunk = &chunk[start..];
+            skip_zeros = chunk.is_empty();
+        }
+        if !chunk.is_empty() {
+            on_chunk(chunk);
+        }
+    }
+
+    if *offset > read_from
+        && !release_spool_prefix(&file, *offset)?
+        && *offset > SPOOL_MAX_BACKLOG_BYTES
+        && file.metadata()?.len() == *offset
+    {
+        // 文件系统不支持打洞时退而在读完且超过上限后截断；
+        // 检查与截断之间内核追加的少量输出可能丢失，但 spool 不会无限增长
+        file.set_len(0)?;
+        *offset = 0;
+    }
+    Ok(())
+}
+
+/// 记录 spool 读取进度的文件：camofy 重启后从上次的位置继续读取，避免重复写入日志。
+pub(crate) fn spool_offset_path(spool: &Path) -> PathBuf {
+    let mut path = spool.as_os_str().to_owned();
+    path.push(".offset");
+    PathBuf::from(path)
+}
+
+fn load_spool_offset(spool: &Path) -> u64 {
+    std::fs::read_to_string(spool_offset_path(spool))
+        .ok()
+        .and_then(|content| content.trim().parse().ok())
+        .unwrap_or_default()
+}
+
+/// 返回 `offset` 之后第一段数据的位置；之后全是空洞时返回文件末尾。
+#[cfg(target_os = "linux")]
+fn spool_data_start(file: &std::fs::File, offset: u64, len: u64) -> u64 {
+    use std::os::fd::AsRawFd;
+
+    let Ok(raw_offset) = libc::off_t::try_from(offset) else {
+        return offset;
+    };
+    // SAFETY: lseek(2) 只移动该文件描述符的读写位置，读取前会重新 seek。
+    let pos = unsafe { libc::lseek(file.as_raw_fd(), raw_offset, libc::SEEK_DATA) };
+    if pos >= 0 {
+        return u64::try_from(pos).unwrap_or(offset);
+    }
+    if std::io::Error::last_os_error().raw_os_error() == Some(libc::ENXIO) {
+        len
+    } else {
+        offset
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn spool_data_start(_file: &std::fs::File, offset: u64, _len: u64) -> u64 {
+    offset
+}
+
+/// 释放 spool 中 `[0, offset)` 已读部分占用的空间，不影响之后追加的内容；
+/// 返回是否已释放（文件系统不支持时为 false）。
+#[cfg(target_os = "linux")]
+fn release_spool_prefix(file: &std::fs::File, offset: u64) -> std::io::Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    let Ok(len) = libc::off_t::try_from(offset) else {
+        return Ok(false);
+    };
+    // SAFETY: fallocate(2) 以 KEEP_SIZE 方式打洞，只作用于已读的区间，不访问进程内存。
+    let ret = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+            0,
+            len,
+        )
+    };
+    if ret == 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EOPNOTSUPP) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn release_spool_prefix(_file: &std::fs::File, _offset: u64) -> std::io::Result<bool> {
+    Ok(false)
+}
+
+/// 正在跟踪的 spool 文件的读取进度与输出去向。
+struct SpoolTail {
     path: PathBuf,
     state: SharedLogWriteState,
     log_name: &'static str,
     direction: &'static str,
-    broadcast: bool,
-) where
-    R: tokio::io::AsyncRead + Unpin + Send + 'static,
-{
-    tokio::spawn(async move {
-        use tokio::io::AsyncReadExt;
+    tap: Option<fn(&[u8])>,
+    offset: u64,
+    write_failed: bool,
+}
 
-        let tx = if broadcast {
-            let app = app_state();
-            Some(app.events_tx.clone())
-        } else {
-            None
-        };
+impl SpoolTail {
+    fn drain(&mut self, spool: &Path) -> std::io::Result<()> {
+        let tx = app_state().events_tx.clone();
+        let previous_offset = self.offset;
+        let result = drain_spool_file(spool, &mut self.offset, |chunk| {
+            if let Err(err) =
+                write_log_with_rotation_and_space_guard(&self.path, &self.state, chunk, self.log_name)
+            {
+                if !self.write_failed {
+                    self.write_failed = true;
+                    tracing::warn!(
+                        "failed to write {} {} log: {err}",
+                        self.log_name,
+                        self.direction
+                    );
+                }
+            } else {
+                self.write_failed = false;
+            }
+
+            if let Some(tap) = self.tap {
+                tap(chunk);
+            }
+
+            let event = AppEvent::MihomoLogChunk {
+                stream: self.direction.to_string(),
+                chunk: String::from_utf8_lossy(chunk).to_string(),
+                timestamp: current_timestamp(),
+            };
+            if let Err(err) = tx.send(event) {
+                tracing::debug!("failed to broadcast mihomo log chunk via websocket: {err}");
+            }
+        });
+        if self.offset != previous_offset {
+            let path = spool_offset_path(spool);
+            if let Err(err) = std::fs::write(&path, self.offset.to_string()) {
+                tracing::debug!("failed to save spool offset to {}: {err}", path.display());
+            }
+        }
+        result
+    }
+}
 
-        let mut buf = [0u8; 4096];
+/// 各 spool 文件当前的跟踪状态；后台轮询与 [`drain_spool_now`] 共用，避免重复读取。
+static SPOOL_TAILS: Mutex<BTreeMap<PathBuf, Arc<Mutex<SpoolTail>>>> =
+    Mutex::new(BTreeMap::new());
+
+/// 立即读取 spool 中尚未处理的输出（例如内核退出后、上报崩溃事件之前）。
+pub(crate) fn drain_spool_now(spool: &Path) {
+    let tail = SPOOL_TAILS
+        .lock()
+        .expect("spool tails mutex poisoned")
+        .get(spool)
+        .cloned();
+    let Some(tail) = tail else {
+        return;
+    };
+    let mut tail = tail.lock().expect("spool tail mutex poisoned");
+    if let Err(err) = tail.drain(spool) {
+        tracing::warn!("failed to read {}: {err}", spool.display());
+    }
+}
+
+/// 持续跟踪子进程输出的 spool 文件，将新增内容写入指定日志文件并通过 WebSocket 广播。
+///
+/// Mihomo 的 stdout/stderr 直接写入 spool 文件而不是管道：camofy 重启后可以重新接上读取，
+/// 内核也不会因为管道读端关闭而收到 SIGPIPE。`alive` 返回 false 后再读取一次剩余内容即结束。
+pub fn spawn_log_tail_task<F>(
+    spool: PathBuf,
+    path: PathBuf,
+    state: SharedLogWriteState,
+    log_name: &'static str,
+    direction: &'static str,
+    tap: Option<fn(&[u8])>,
+    alive: F,
+) where
+    F: Fn() -> bool + Send + 'static,
+{
+    let tail = Arc::new(Mutex::new(SpoolTail {
+        path,
+        state,
+        log_name,
+        direction,
+        tap,
+        offset: load_spool_offset(&spool),
+        write_failed: false,
+    }));
+    SPOOL_TAILS
+        .lock()
+        .expect("spool tails mutex poisoned")
+        .insert(spool.clone(), tail.clone());
 
+    tokio::spawn(async move {
         loop {
-            match reader.read(&mut buf).await {
-                Ok(0) => break,
-                Ok(n) => {
-                    if let Err(err) = crate::logs::write_log_with_rotation_and_space_guard(
-                        &path,
-                        &state,
-                        &buf[..n],
-                        log_name,
-                    ) {
-                        tracing::warn!(
-                            "failed to write {} {} log: {err}",
-                            log_name,
-                            direction
-                        );
-                        break;
-                    }
+            let exited = !alive();
+
+            let result = tail.lock().expect("spool tail mutex poisoned").drain(&spool);
+            if let Err(err) = result {
+                tracing::warn!(
+                    "failed to read {} {} from {}: {err}",
+                    log_name,
+                    direction,
+                    spool.display()
+                );
+                break;
+            }
 
-                    if let Some(tx) = &tx {
-                        let chunk = String::from_utf8_lossy(&buf[..n]).to_string();
-                        let event = AppEvent::MihomoLogChunk {
-                            stream: direction.to_string(),
-                            chunk,
-                            timestamp: current_timestamp(),
-                        };
-                        if let Err(err) = tx.send(event) {
-                            tracing::debug!(
-                                "failed to broadcast mihomo log chunk via websocket: {err}"
-                            );
-                        }
-                    }
-                }
-                Err(err) => {
-                    tracing::warn!(
-                        "failed to read {} {}: {err}",
-                        log_name,
-                        direction
-                    );
-                    break;
-                }
+            if exited {
+                break;
             }
+            tokio::time::sleep(std::time::Duration::from_millis(LOG_TAIL_POLL_INTERVAL_MS)).await;
+        }
+
+        let mut tails = SPOOL_TAILS.lock().expect("spool tails mutex poisoned");
+        if tails.get(&spool).is_some_and(|current| Arc::ptr_eq(current, &tail)) {
+            tails.remove(&spool);
         }
     });
 }
@@ -325,3 +537,51 @@ pub async fn get_mihomo_log() -> Json<ApiResponse<serde_json::Value>> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{SPOOL_MAX_BACKLOG_BYTES, drain_spool_file};
+    use std::io::Write;
+    use std::os::unix::fs::MetadataExt;
+
+    #[test]
+    fn spool_is_drained_without_losing_appended_output() {
+        let root = crate::test_support::TempRoot::new("spool");
+        let spool = root.join("mihomo.stdout");
+        let mut writer = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&spool)
+            .unwrap();
+
+        let mut offset = 0;
+        let mut out = Vec::new();
+        let first = format!("{}\n", "a".repeat(20_000));
+        writer.write_all(first.as_bytes()).unwrap();
+        drain_spool_file(&spool, &mut offset, |chunk| out.extend_from_slice(chunk)).unwrap();
+        assert_eq!(out, first.as_bytes());
+        // 已读部分的空间被释放，文件长度不变，写入方继续追加的内容不会丢失
+        let meta = std::fs::metadata(&spool).unwrap();
+        assert_eq!(meta.len(), first.len() as u64);
+        assert!(meta.blocks() * 512 < first.len() as u64);
+
+        writer.write_all(b"line 2\n").unwrap();
+        drain_spool_file(&spool, &mut offset, |chunk| out.extend_from_slice(chunk)).unwrap();
+        assert!(out.ends_with(b"a\nline 2\n"));
+
+        // camofy 重启后从头跟踪：跳过已释放的部分，只读取新的输出
+        writer.write_all(b"line 3\n").unwrap();
+        let mut offset = 0;
+        let mut out = Vec::new();
+        drain_spool_file(&spool, &mut offset, |chunk| out.extend_from_slice(chunk)).unwrap();
+        assert_eq!(out, b"line 3\n");
+
+        // 积压超过上限时丢弃较早的输出
+        writer
+            .write_all(&vec![b'x'; SPOOL_MAX_BACKLOG_BYTES as usize + 10])
+            .unwrap();
+        let mut out = Vec::new();
+        drain_spool_file(&spool, &mut offset, |chunk| out.extend_from_slice(chunk)).unwrap();
+        assert_eq!(out.len() as u64, SPOOL_MAX_BACKLOG_BYTES);
+    }
+}
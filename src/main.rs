@@ -16,14 +16,26 @@ mod app;
 mod auth;
 mod config_manager;
 mod core;
+mod combine;
+mod convert;
 mod core_async;
+mod enhance;
 mod ws;
+mod history;
 mod logs;
+mod providers;
 mod subscriptions;
+mod system_config;
 mod user_profiles;
+mod validate;
 mod mihomo;
 mod geoip;
 mod scheduler;
+mod process;
+mod supervisor;
+mod health;
+#[cfg(test)]
+mod test_support;
 
 use crate::app::AppState;
 
@@ -70,6 +82,67 @@ struct ProfileMeta {
     last_fetch_status: Option<String>,
     #[serde(default)]
     last_modified_time: Option<String>,
+    /// 仅对 `remote` profile 生效，最近一次拉取时识别出的订阅格式
+    #[serde(default)]
+    subscription_format: Option<crate::convert::SubscriptionFormat>,
+    /// 仅对 `remote` profile 生效，订阅响应头 `subscription-userinfo` 中的流量与到期信息
+    #[serde(default)]
+    userinfo: Option<crate::subscriptions::SubscriptionUserinfo>,
+    /// 仅对 `remote` profile 生效，订阅响应头 `profile-update-interval` 建议的刷新间隔（小时）
+    #[serde(default)]
+    update_interval_hours: Option<u32>,
+    /// 仅对 `remote` profile 生效，订阅响应头 `content-disposition` 中的文件名
+    #[serde(default)]
+    remote_filename: Option<String>,
+    /// 仅对 `remote` profile 生效，主 URL 拉取失败时依次尝试的备用 URL
+    #[serde(default)]
+    mirror_urls: Vec<String>,
+    /// 仅对 `remote` profile 生效，上一次成功拉取的来源与 ETag / Last-Modified
+    #[serde(default)]
+    fetch_cache: Option<crate::subscriptions::SubscriptionFetchCache>,
+    /// 仅对 `remote` profile 生效，拉取订阅时使用的请求选项
+    #[serde(default)]
+    fetch_options: crate::subscriptions::SubscriptionFetchOptions,
+    /// 仅对 `remote` profile 生效，自动更新方式（默认跟随全局订阅更新任务）
+    #[serde(default)]
+    schedule: crate::subscriptions::SubscriptionSchedule,
+    /// 仅对 `remote` profile 生效，最近一次拉取时判定的流量 / 到期告警；
+    /// 只有新出现的告警才会再次广播，避免每次拉取都重复提醒
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    usage_warnings: Vec<crate::subscriptions::UsageWarning>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ProviderKind {
+    Rule,
+    Proxy,
+}
+
+/// 由 Camofy 下载并缓存的独立 provider，生成 merged.yaml 时以 `type: file` 注入。
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct ProviderMeta {
+    id: String,
+    /// 写入 `rule-providers` / `proxy-providers` 时使用的名称，规则与代理组通过它引用
+    name: String,
+    kind: ProviderKind,
+    /// rule-provider 的 behavior：domain / ipcidr / classical
+    #[serde(default)]
+    behavior: Option<String>,
+    /// rule-provider 的文件格式：yaml / text / mrs；proxy-provider 固定为 yaml
+    #[serde(default)]
+    format: Option<String>,
+    url: String,
+    #[serde(default = "default_true")]
+    enabled: bool,
+    #[serde(default)]
+    last_fetch_time: Option<String>,
+    #[serde(default)]
+    last_fetch_status: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Serialize, Deserialize, Clone, Default)]
@@ -86,6 +159,18 @@ struct ScheduledTaskConfig {
     last_run_message: Option<String>,
 }
 
+/// 多订阅合并（combined 模式）配置。
+///
+/// 启用后，以 `active_subscription_id` 作为主订阅提供 rules / dns 等字段，
+/// 并将 `subscription_ids` 中其他订阅的节点合并进来。
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub(crate) struct CombinedSubscriptionConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    subscription_ids: Vec<String>,
+}
+
 /// 记录某个代理组当前选择的节点。
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub(crate) struct ProxySelectionRecord {
@@ -93,14 +178,17 @@ pub(crate) struct ProxySelectionRecord {
     pub node: String,
 }
 
-/// 针对一组配置组合（订阅 + 用户配置）的代理选择快照。
+/// 针对一组配置组合（订阅 + 用户配置链）的代理选择快照。
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub(crate) struct ProxySelectionSet {
     /// 对应 AppConfig.active_subscription_id
     #[serde(default)]
     pub subscription_id: Option<String>,
-    /// 对应 AppConfig.active_user_profile_id
+    /// 对应 AppConfig.active_user_profile_ids（按链中顺序）
     #[serde(default)]
+    pub user_profile_ids: Vec<String>,
+    /// 旧版本中的单一用户配置 id，仅用于读取时迁移到 `user_profile_ids`。
+    #[serde(default, skip_serializing)]
     pub user_profile_id: Option<String>,
     #[serde(default)]
     pub selections: Vec<ProxySelectionRecord>,
@@ -112,7 +200,14 @@ pub(crate) struct AppConfig {
     profiles: Vec<ProfileMeta>,
     #[serde(default)]
     active_subscription_id: Option<String>,
+    /// 多订阅合并配置
+    #[serde(default)]
+    combined_subscription: CombinedSubscriptionConfig,
+    /// 按顺序依次叠加到订阅之上的已启用用户 profile 链。
     #[serde(default)]
+    active_user_profile_ids: Vec<String>,
+    /// 旧版本中的单一活跃用户 profile，仅用于读取时迁移到 `active_user_profile_ids`。
+    #[serde(default, skip_serializing)]
     active_user_profile_id: Option<String>,
     #[serde(default)]
     panel_password_hash: Option<String>,
@@ -125,6 +220,18 @@ pub(crate) struct AppConfig {
     /// 自动更新 GeoIP 数据库的定时任务配置
     #[serde(default)]
     geoip_auto_update: Option<ScheduledTaskConfig>,
+    /// 托管的 rule-provider / proxy-provider
+    #[serde(default)]
+    providers: Vec<ProviderMeta>,
+    /// 自动刷新托管 provider 的定时任务配置
+    #[serde(default)]
+    provider_auto_update: Option<ScheduledTaskConfig>,
+    /// 内核守护：异常退出后的重启策略与崩溃循环检测
+    #[serde(default)]
+    core_supervisor: supervisor::CoreSupervisorConfig,
+    /// 内核健康检查：探测间隔、失败阈值与是否自动重启
+    #[serde(default)]
+    core_health: health::CoreHealthConfig,
     /// 针对不同订阅 + 用户配置组合保存的代理选择快照。
     #[serde(default)]
     proxy_selections: Vec<ProxySelectionSet>,
@@ -137,11 +244,16 @@ pub(crate) struct AppConfig {
 pub enum ConfigChangeReason {
     SubscriptionFetched,
     ActiveSubscriptionChanged,
+    CombinedSubscriptionChanged,
     SubscriptionDeleted,
     UserProfileUpdated,
     ActiveUserProfileChanged,
     UserProfileDeleted,
     SettingsUpdated,
+    DefaultsUpdated,
+    SystemOverridesUpdated,
+    ProviderUpdated,
+    ProviderDeleted,
     Other,
 }
 
@@ -165,6 +277,68 @@ pub enum AppEvent {
         core_reload: CoreReloadResult,
         timestamp: String,
     },
+    /// 新配置重载失败或内核在重载后很快退出，已自动回滚到上一份可用的 merged.yaml。
+    ConfigRolledBack {
+        reason: ConfigChangeReason,
+        /// 触发回滚的失败原因
+        error: String,
+        /// 恢复所用的历史版本（见 `history` 模块）
+        restored_revision: String,
+        /// 回滚后重新应用配置的结果
+        core_reload: CoreReloadResult,
+        /// 回滚只恢复 merged.yaml，来源配置未变，下一次重新生成仍会得到被拒绝的配置
+        warning: String,
+        timestamp: String,
+    },
+    /// 拉取订阅时发现流量即将用尽或套餐即将到期。
+    SubscriptionUsageWarning {
+        subscription_id: String,
+        name: String,
+        warning: crate::subscriptions::UsageWarning,
+        /// 已用流量（上传 + 下载，字节）
+        used: u64,
+        /// 总流量（字节），0 表示未提供
+        total: u64,
+        /// 到期时间（Unix 秒）
+        expire: Option<u64>,
+        timestamp: String,
+    },
+    /// 内核非主动退出（崩溃）。
+    CoreCrashed {
+        pid: u32,
+        exit_code: Option<i32>,
+        /// 终止进程的信号编号（仅 unix）
+        signal: Option<i32>,
+        /// 退出前 stderr 的最后若干行
+        stderr_tail: Vec<String>,
+        /// 是否已安排自动重启
+        restart_scheduled: bool,
+        restart_in_secs: Option<u64>,
+        /// 是否因窗口内崩溃次数过多而停止自动重启
+        crash_loop: bool,
+        /// 自 camofy 启动以来自动重启的次数
+        restart_count: u32,
+        timestamp: String,
+    },
+    /// 内核整体健康状态发生变化。
+    CoreHealthChanged {
+        state: health::HealthState,
+        previous_state: health::HealthState,
+        /// 连续不健康的检查次数
+        consecutive_failures: u32,
+        /// 本次检查的各项探测结果
+        checks: Vec<health::ProbeResult>,
+        timestamp: String,
+    },
+    /// 内核持续不健康，健康检查请求重启内核。
+    CoreHealthRestart {
+        consecutive_failures: u32,
+        /// 重启请求是否已被受理（已有内核操作在执行时会被拒绝）
+        restart_requested: bool,
+        /// 自 camofy 启动以来因健康检查重启的次数
+        restart_count: u32,
+        timestamp: String,
+    },
     CoreStatusChanged {
         running: bool,
         pid: Option<u32>,
@@ -230,9 +404,9 @@ async fn main() {
     let state = AppState {
         data_root: data_root.clone(),
         http_client: reqwest::ClientBuilder::new()
-            .user_agent("clash-verge/v2.4.3")
+            .user_agent(app::DEFAULT_USER_AGENT)
             // 为所有 HTTP 请求设置一个上限，防止下载或远程请求无限挂起。
-            .timeout(std::time::Duration::from_secs(300))
+            .timeout(std::time::Duration::from_secs(app::DEFAULT_HTTP_TIMEOUT_SECS))
             .build()
             .unwrap(),
         auth_tokens: tokio::sync::Mutex::new(Vec::new()),
@@ -257,6 +431,9 @@ async fn main() {
     // 启动后台定时任务调度器（订阅自动更新、GeoIP 数据库自动更新等）
     scheduler::start_scheduler();
 
+    // 启动内核健康检查循环
+    tokio::spawn(health::run_health_check_loop());
+
     // 根据上次记忆的状态自动启动内核（如果需要）。
     // 放到后台任务中执行，内部会在尝试启动前等待网络连通性恢复，
     // 避免在路由器刚开机、网络尚未就绪时阻塞 Web 服务启动。
@@ -351,6 +528,11 @@ fn build_router() -> Router {
             "/subscriptions",
             get(subscriptions::list_subscriptions).post(subscriptions::create_subscription),
         )
+        .route(
+            "/subscriptions/combined",
+            get(subscriptions::get_combined_subscription)
+                .put(subscriptions::update_combined_subscription),
+        )
         .route(
             "/subscriptions/:id",
             put(subscriptions::update_subscription).delete(subscriptions::delete_subscription),
@@ -367,6 +549,10 @@ fn build_router() -> Router {
             "/user-profiles",
             get(user_profiles::list_user_profiles).post(user_profiles::create_user_profile),
         )
+        .route(
+            "/user-profiles/chain",
+            put(user_profiles::update_user_profile_chain),
+        )
         .route(
             "/user-profiles/:id",
             get(user_profiles::get_user_profile)
@@ -377,13 +563,47 @@ fn build_router() -> Router {
             "/user-profiles/:id/activate",
             post(user_profiles::activate_user_profile),
         )
+        .route(
+            "/user-profiles/:id/deactivate",
+            post(user_profiles::deactivate_user_profile),
+        )
         .route("/core", get(core::get_core_info))
         .route("/core/status", get(core::get_core_status))
         .route("/core/download", post(core::download_core))
         .route("/core/start", post(core_async::start_core_async))
         .route("/core/stop", post(core_async::stop_core_async))
         .route("/core/restart", post(core_async::restart_core_async))
+        .route("/core/health", get(health::get_core_health))
+        .route("/core/health/check", post(health::check_core_health))
         .route("/config/merged", get(user_profiles::get_merged_config))
+        .route("/config/preview", post(user_profiles::preview_config))
+        .route(
+            "/config/merged/explain",
+            get(user_profiles::get_merged_config_explain),
+        )
+        .route(
+            "/config/defaults",
+            get(system_config::get_defaults_config).put(system_config::update_defaults_config),
+        )
+        .route(
+            "/config/system-overrides",
+            get(system_config::get_system_overrides).put(system_config::update_system_overrides),
+        )
+        .route(
+            "/providers",
+            get(providers::list_providers).post(providers::create_provider),
+        )
+        .route(
+            "/providers/:id",
+            put(providers::update_provider).delete(providers::delete_provider),
+        )
+        .route("/providers/:id/fetch", post(providers::fetch_provider))
+        .route("/history", get(history::list_history))
+        .route("/history/:rev/diff", get(history::get_history_diff))
+        .route(
+            "/history/:rev/restore",
+            post(history::restore_history_revision),
+        )
         .route("/logs/app", get(logs::get_app_log))
         .route("/logs/mihomo", get(logs::get_mihomo_log))
         .route("/mihomo/proxies", get(mihomo::get_proxies))
@@ -480,6 +700,34 @@ fn apply_app_config_defaults(config: &mut AppConfig) {
             last_run_message: None,
         });
     }
+
+    // provider 刷新错开到订阅更新之后，避免两次重新生成 merged.yaml 相互覆盖。
+    if config.provider_auto_update.is_none() {
+        config.provider_auto_update = Some(ScheduledTaskConfig {
+            cron: "30 3 * * *".to_string(),
+            enabled: true,
+            last_run_time: None,
+            last_run_status: None,
+            last_run_message: None,
+        });
+    }
+}
+
+/// 将旧版本 app.json 中的单一活跃用户 profile 字段迁移为用户 profile 链。
+fn migrate_legacy_app_config(config: &mut AppConfig) {
+    if let Some(id) = config.active_user_profile_id.take()
+        && !config.active_user_profile_ids.contains(&id)
+    {
+        config.active_user_profile_ids.insert(0, id);
+    }
+
+    for set in &mut config.proxy_selections {
+        if let Some(id) = set.user_profile_id.take()
+            && set.user_profile_ids.is_empty()
+        {
+            set.user_profile_ids.push(id);
+        }
+    }
 }
 
 pub(crate) fn load_app_config(root: &PathBuf) -> Result<AppConfig, String> {
@@ -491,6 +739,7 @@ pub(crate) fn load_app_config(root: &PathBuf) -> Result<AppConfig, String> {
         Ok(content) => {
             let mut config: AppConfig = serde_json::from_str(&content)
                 .map_err(|err| format!("failed to parse app.json at {}: {err}", path.display()))?;
+            migrate_legacy_app_config(&mut config);
             apply_app_config_defaults(&mut config);
             Ok(config)
         }
@@ -565,19 +814,20 @@ pub(crate) fn update_proxy_selection_for_current_profile(
 ) -> Result<(), String> {
     with_app_config_mut(|config: &mut AppConfig| {
         let sub_id = config.active_subscription_id.clone();
-        let user_id = config.active_user_profile_id.clone();
+        let user_ids = config.active_user_profile_ids.clone();
 
         // 先尝试找到当前组合对应的快照，没有则新建。
         let set = if let Some(existing) = config
             .proxy_selections
             .iter_mut()
-            .find(|s| s.subscription_id == sub_id && s.user_profile_id == user_id)
+            .find(|s| s.subscription_id == sub_id && s.user_profile_ids == user_ids)
         {
             existing
         } else {
             config.proxy_selections.push(ProxySelectionSet {
                 subscription_id: sub_id,
-                user_profile_id: user_id,
+                user_profile_ids: user_ids,
+                user_profile_id: None,
                 selections: Vec::new(),
             });
             config
@@ -604,15 +854,50 @@ pub(crate) fn update_proxy_selection_for_current_profile(
     Ok(())
 }
 
+/// 将代理选择中的节点名按 (旧名称, 新名称) 映射改写，返回是否有记录被修改。
+fn rename_selection_nodes(selections: &mut [ProxySelectionRecord], renames: &[(String, String)]) -> bool {
+    let mut changed = false;
+    for rec in selections.iter_mut() {
+        if let Some((_, new)) = renames.iter().find(|(old, _)| *old == rec.node) {
+            rec.node = new.clone();
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// 节点被 `rename-proxies` 改名后，同步更新当前配置组合下已保存的代理选择，
+/// 避免重载后按旧名称恢复选择失败。没有记录需要修改时不写盘。
+pub(crate) fn rename_saved_proxy_selections(renames: &[(String, String)]) -> Result<(), String> {
+    let needs_update = get_proxy_selections_for_active_profile()
+        .is_some_and(|selections| selections.iter().any(|r| renames.iter().any(|(old, _)| *old == r.node)));
+    if !needs_update {
+        return Ok(());
+    }
+
+    with_app_config_mut(|config: &mut AppConfig| {
+        let sub_id = config.active_subscription_id.clone();
+        let user_ids = config.active_user_profile_ids.clone();
+        if let Some(set) = config
+            .proxy_selections
+            .iter_mut()
+            .find(|s| s.subscription_id == sub_id && s.user_profile_ids == user_ids)
+            && rename_selection_nodes(&mut set.selections, renames)
+        {
+            tracing::info!("updated saved proxy selections for renamed proxies");
+        }
+    })
+}
+
 /// 读取当前活跃配置组合下保存的代理选择快照。
 pub(crate) fn get_proxy_selections_for_active_profile(
 ) -> Option<Vec<ProxySelectionRecord>> {
     let cfg = get_app_config_snapshot();
     let sub_id = cfg.active_subscription_id.clone();
-    let user_id = cfg.active_user_profile_id.clone();
+    let user_ids = cfg.active_user_profile_ids.clone();
 
     cfg.proxy_selections
         .into_iter()
-        .find(|s| s.subscription_id == sub_id && s.user_profile_id == user_id)
+        .find(|s| s.subscription_id == sub_id && s.user_profile_ids == user_ids)
         .map(|s| s.selections)
 }
@@ -10,7 +10,6 @@ use crate::app::app_state;
 use crate::core::ensure_controller_secret;
 use crate::{ApiResponse, ProxySelectionRecord};
 
-const MIHOMO_SOCKET_PATH: &str = "/tmp/verge/clash-verge-service.sock";
 const DEFAULT_TEST_URL: &str = "https://www.gstatic.com/generate_204";
 const DEFAULT_TEST_TIMEOUT_MS: u32 = 5000;
 
@@ -188,9 +187,10 @@ async fn send_mihomo_request(
     body: Option<&str>,
     secret: &str,
 ) -> Result<(u16, String), String> {
-    let mut stream = UnixStream::connect(MIHOMO_SOCKET_PATH)
+    let socket_path = crate::system_config::controller_socket_path(&app_state().data_root);
+    let mut stream = UnixStream::connect(&socket_path)
         .await
-        .map_err(|err| format!("failed to connect to mihomo unix socket at {MIHOMO_SOCKET_PATH}: {err}"))?;
+        .map_err(|err| format!("failed to connect to mihomo unix socket at {socket_path}: {err}"))?;
 
     let request = build_http_request(method, path, body, secret);
     stream
@@ -327,6 +327,24 @@ async fn delay_proxy(
     }
 }
 
+/// 通过控制接口查询内核版本，也用于确认控制通道可用。
+pub(crate) async fn query_core_version(root: &PathBuf) -> Result<String, String> {
+    #[derive(Deserialize)]
+    struct VersionBody {
+        version: String,
+    }
+
+    let secret = ensure_controller_secret(root)
+        .map_err(|err| format!("failed to ensure controller secret: {err}"))?;
+    let (status, body) = send_mihomo_request("GET", "/version", None, &secret).await?;
+    if !(200..300).contains(&status) {
+        return Err(map_error_from_body(status, &body));
+    }
+    serde_json::from_str::<VersionBody>(&body)
+        .map(|v| v.version)
+        .map_err(|err| format!("failed to parse version response: {err}"))
+}
+
 /// 使用当前的 `merged.yaml` 向 Mihomo 发送“重新加载配置”请求。
 ///
 /// 约定：调用方应保证 `merged.yaml` 已经根据最新配置生成。
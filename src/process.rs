@@ -0,0 +1,387 @@
+//! 内核进程相关的底层操作：发送信号、读取 `/proc` 中的进程状态与身份，
+//! 以及检查内核退出后 TUN 网卡与监听端口是否已释放。
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// 轮询进程 / 资源状态的间隔。
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 系统 procfs 挂载点；测试中以伪造的目录代替。
+pub(crate) const PROC_ROOT: &str = "/proc";
+
+/// 向进程发送信号；进程已不存在时返回 `Ok(false)`。
+#[cfg(target_family = "unix")]
+pub(crate) fn send_signal(pid: u32, signal: libc::c_int) -> Result<bool, String> {
+    let raw_pid = libc::pid_t::try_from(pid).map_err(|_| format!("invalid pid {pid}"))?;
+    if raw_pid <= 0 {
+        return Err(format!("invalid pid {pid}"));
+    }
+    // SAFETY: kill(2) 只向指定进程发送信号，不涉及任何内存访问。
+    if unsafe { libc::kill(raw_pid, signal) } == 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::ESRCH) {
+        Ok(false)
+    } else {
+        Err(format!("failed to send signal {signal} to pid {pid}: {err}"))
+    }
+}
+
+/// 从 `/proc/<pid>/stat` 中取出进程状态字符（`R` / `S` / `Z` 等）。
+///
+/// 进程名（第二列）可能包含空格或括号，因此以最后一个 `)` 作为分隔。
+fn proc_stat_state(stat: &str) -> Option<char> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    rest.trim_start().chars().next()
+}
+
+/// 从 `/proc/<pid>/stat` 中取出进程启动时间（第 22 列 `starttime`，开机后经过的时钟滴答数）。
+fn proc_stat_start_time(stat: &str) -> Option<u64> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    // `)` 之后从第 3 列（进程状态）开始
+    rest.split_whitespace().nth(19)?.parse().ok()
+}
+
+fn read_proc_stat(proc_root: &Path, pid: u32) -> Option<String> {
+    std::fs::read_to_string(proc_root.join(pid.to_string()).join("stat")).ok()
+}
+
+/// 读取 `/proc/<pid>/exe` 指向的可执行文件。
+///
+/// 运行中的二进制被替换（例如更新内核）后，链接目标会带上 ` (deleted)` 后缀，这里将其去除。
+fn proc_exe(proc_root: &Path, pid: u32) -> Option<PathBuf> {
+    let target = std::fs::read_link(proc_root.join(pid.to_string()).join("exe")).ok()?;
+    if let Some(path) = target.to_str().and_then(|s| s.strip_suffix(" (deleted)")) {
+        return Some(PathBuf::from(path));
+    }
+    Some(target)
+}
+
+/// `/proc/<pid>/comm` 是否与可执行文件名一致（内核将 comm 截断为 15 字节）。
+fn proc_comm_matches(proc_root: &Path, pid: u32, exe: &Path) -> bool {
+    let Ok(comm) = std::fs::read_to_string(proc_root.join(pid.to_string()).join("comm")) else {
+        return false;
+    };
+    let Some(name) = exe.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    let truncated: String = name.chars().take(15).collect();
+    comm.trim_end_matches('\n') == truncated
+}
+
+/// 进程是否已经退出：`/proc` 中不存在，或已成为等待父进程回收的僵尸进程。
+pub(crate) fn process_exited(pid: u32) -> bool {
+    match read_proc_stat(Path::new(PROC_ROOT), pid) {
+        Some(stat) => proc_stat_state(&stat) == Some('Z'),
+        None => true,
+    }
+}
+
+/// PID 文件中记录的进程身份。
+///
+/// 除 PID 外还记录进程启动时间与可执行文件路径：路由器重启后 PID 可能被其他进程复用，
+/// 仅凭 `/proc/<pid>` 存在无法判断内核是否仍在运行。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ProcessIdentity {
+    pub(crate) pid: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) start_time: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) exe: Option<PathBuf>,
+}
+
+impl ProcessIdentity {
+    /// 读取进程当前的身份信息，无法读取的字段留空。
+    pub(crate) fn capture(proc_root: &Path, pid: u32) -> Self {
+        Self {
+            pid,
+            start_time: read_proc_stat(proc_root, pid).and_then(|stat| proc_stat_start_time(&stat)),
+            exe: proc_exe(proc_root, pid),
+        }
+    }
+
+    /// 解析 PID 文件内容：当前格式为 JSON，同时兼容旧版本只写入 PID 数字的格式。
+    pub(crate) fn parse(content: &str) -> Result<Self, String> {
+        let content = content.trim();
+        if let Ok(pid) = content.parse::<u32>() {
+            return Ok(Self {
+                pid,
+                start_time: None,
+                exe: None,
+            });
+        }
+        serde_json::from_str(content).map_err(|err| format!("invalid pid file: {err}"))
+    }
+
+    /// 记录的身份是否仍对应一个存活的进程。
+    ///
+    /// - 进程不存在或已成为僵尸进程时不匹配
+    /// - 启动时间与记录不一致，说明 PID 已被其他进程复用
+    /// - 可执行文件与记录不一致（旧格式 PID 文件未记录时与 `expected_exe` 比较）同样不匹配；
+    ///   无法读取 `exe` 链接时退而比较 `/proc/<pid>/comm` 中的进程名
+    pub(crate) fn is_alive(&self, proc_root: &Path, expected_exe: &Path) -> bool {
+        let Some(stat) = read_proc_stat(proc_root, self.pid) else {
+            return false;
+        };
+        if proc_stat_state(&stat) == Some('Z') {
+            return false;
+        }
+        if let Some(recorded) = self.start_time
+            && proc_stat_start_time(&stat) != Some(recorded)
+        {
+            return false;
+        }
+        let Some(exe) = proc_exe(proc_root, self.pid) else {
+            let expected = self.exe.as_deref().unwrap_or(expected_exe);
+            return proc_comm_matches(proc_root, self.pid, expected);
+        };
+        match &self.exe {
+            Some(recorded) => exe == *recorded,
+            None => std::fs::canonicalize(expected_exe)
+                .map(|expected| expected == exe)
+                .unwrap_or_else(|_| exe == expected_exe),
+        }
+    }
+}
+
+/// 在 `timeout` 内轮询等待进程退出，返回是否已退出。
+pub(crate) async fn wait_for_exit(pid: u32, timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if process_exited(pid) {
+            return true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// 内核运行时占用、退出后应被释放的系统资源。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum CoreResource {
+    TcpPort(u16),
+    UdpPort(u16),
+    TunDevice(String),
+}
+
+impl fmt::Display for CoreResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoreResource::TcpPort(port) => write!(f, "tcp port {port}"),
+            CoreResource::UdpPort(port) => write!(f, "udp port {port}"),
+            CoreResource::TunDevice(name) => write!(f, "tun device {name}"),
+        }
+    }
+}
+
+/// Mihomo 在 Linux 上未指定 `tun.device` 时使用的网卡名。
+const DEFAULT_TUN_DEVICE: &str = "Meta";
+
+/// 根据内核实际使用的配置（merged.yaml）列出其占用的端口与 TUN 网卡。
+pub(crate) fn core_resources(config: &serde_yaml::Value) -> Vec<CoreResource> {
+    let mut resources = Vec::new();
+
+    for key in ["mixed-port", "port", "socks-port", "redir-port", "tproxy-port"] {
+        if let Some(port) = config
+            .get(key)
+            .and_then(|v| v.as_u64())
+            .and_then(|p| u16::try_from(p).ok())
+            .filter(|p| *p > 0)
+        {
+            resources.push(CoreResource::TcpPort(port));
+        }
+    }
+
+    if let Some(dns) = config.get("dns")
+        && dns.get("enable").and_then(|v| v.as_bool()).unwrap_or(false)
+        && let Some(port) = dns
+            .get("listen")
+            .and_then(|v| v.as_str())
+            .and_then(|s| s.rsplit_once(':'))
+            .and_then(|(_, port)| port.parse::<u16>().ok())
+    {
+        resources.push(CoreResource::UdpPort(port));
+    }
+
+    if let Some(tun) = config.get("tun")
+        && tun.get("enable").and_then(|v| v.as_bool()).unwrap_or(false)
+    {
+        let device = tun
+            .get("device")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .unwrap_or(DEFAULT_TUN_DEVICE);
+        resources.push(CoreResource::TunDevice(device.to_string()));
+    }
+
+    resources
+}
+
+/// 检查 `/proc/net/{tcp,udp}` 格式的内容中是否有进程占用指定本地端口。
+/// TCP 仅统计处于 LISTEN（`0A`）状态的套接字。
+fn proc_net_has_port(content: &str, port: u16, tcp: bool) -> bool {
+    content.lines().skip(1).any(|line| {
+        let mut cols = line.split_whitespace();
+        let (Some(local), Some(_remote), Some(state)) = (cols.nth(1), cols.next(), cols.next())
+        else {
+            return false;
+        };
+        let local_port = local
+            .rsplit_once(':')
+            .and_then(|(_, p)| u16::from_str_radix(p, 16).ok());
+        local_port == Some(port) && (!tcp || state == "0A")
+    })
+}
+
+fn resource_held(resource: &CoreResource) -> bool {
+    let read = |name: &str| std::fs::read_to_string(Path::new("/proc/net").join(name)).unwrap_or_default();
+    match resource {
+        CoreResource::TcpPort(port) => ["tcp", "tcp6"]
+            .iter()
+            .any(|name| proc_net_has_port(&read(name), *port, true)),
+        CoreResource::UdpPort(port) => ["udp", "udp6"]
+            .iter()
+            .any(|name| proc_net_has_port(&read(name), *port, false)),
+        CoreResource::TunDevice(name) => Path::new("/sys/class/net").join(name).exists(),
+    }
+}
+
+/// 在 `timeout` 内等待资源全部释放，返回仍被占用的资源。
+pub(crate) async fn wait_for_resources_released(
+    resources: &[CoreResource],
+    timeout: Duration,
+) -> Vec<CoreResource> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let held: Vec<CoreResource> = resources.iter().filter(|r| resource_held(r)).cloned().collect();
+        if held.is_empty() || tokio::time::Instant::now() >= deadline {
+            return held;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use std::time::Duration;
+
+    use super::{
+        CoreResource, ProcessIdentity, core_resources, proc_net_has_port, proc_stat_state,
+        send_signal, wait_for_exit,
+    };
+    use crate::test_support::TempRoot;
+
+    fn fake_process(root: &Path, pid: u32, state: char, start_time: u64, exe: &str) {
+        let dir = root.join(pid.to_string());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("stat"),
+            format!("{pid} (mihomo) {state} 1 {pid} {pid} 0 -1 4194560 100 0 0 0 5 3 0 0 20 0 8 0 {start_time} 7340032 1024"),
+        )
+        .unwrap();
+        let link = dir.join("exe");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(exe, link).unwrap();
+    }
+
+    #[test]
+    fn proc_files_are_parsed() {
+        assert_eq!(proc_stat_state("123 (mihomo) S 1 123 123 0"), Some('S'));
+        assert_eq!(proc_stat_state("123 (a) b (c)) Z 1 123"), Some('Z'));
+        assert_eq!(proc_stat_state("garbage"), None);
+
+        let tcp = "  sl  local_address rem_address   st tx_queue rx_queue\n\
+                   0: 00000000:1ED9 00000000:0000 0A 00000000:00000000\n\
+                   1: 0100007F:1F90 0100007F:D2F0 01 00000000:00000000\n";
+        assert!(proc_net_has_port(tcp, 7897, true));
+        // 已建立的连接不算作监听
+        assert!(!proc_net_has_port(tcp, 8080, true));
+        assert!(proc_net_has_port(tcp, 8080, false));
+
+        let config: serde_yaml::Value = serde_yaml::from_str(
+            "mixed-port: 7897\ndns:\n  enable: true\n  listen: 0.0.0.0:1053\ntun:\n  enable: true\n",
+        )
+        .unwrap();
+        assert_eq!(
+            core_resources(&config),
+            vec![
+                CoreResource::TcpPort(7897),
+                CoreResource::UdpPort(1053),
+                CoreResource::TunDevice("Meta".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn pid_identity_detects_reused_pid() {
+        let root = TempRoot::new("proc");
+        let core = Path::new("/jffs/camofy/core/mihomo");
+        fake_process(&root, 4242, 'S', 12345, "/jffs/camofy/core/mihomo");
+
+        let identity = ProcessIdentity::capture(&root, 4242);
+        assert_eq!(identity.start_time, Some(12345));
+        assert_eq!(identity.exe.as_deref(), Some(core));
+        let content = serde_json::to_string(&identity).unwrap();
+        assert_eq!(ProcessIdentity::parse(&content).unwrap(), identity);
+        assert!(identity.is_alive(&root, core));
+
+        // 内核更新后原二进制被替换，仍是同一个进程
+        fake_process(&root, 4242, 'S', 12345, "/jffs/camofy/core/mihomo (deleted)");
+        assert!(identity.is_alive(&root, core));
+
+        // 重启后 PID 被其他进程复用：启动时间与可执行文件都不同
+        fake_process(&root, 4242, 'S', 99, "/usr/sbin/dnsmasq");
+        assert!(!identity.is_alive(&root, core));
+        // 旧格式 PID 文件只有数字，依据可执行文件判断
+        let legacy = ProcessIdentity::parse("4242\n").unwrap();
+        assert_eq!(legacy.start_time, None);
+        assert!(!legacy.is_alive(&root, core));
+
+        // 无法读取 exe 链接时比较进程名
+        fs::remove_file(root.join("4242/exe")).unwrap();
+        assert!(!legacy.is_alive(&root, core));
+        fs::write(root.join("4242/comm"), "mihomo\n").unwrap();
+        assert!(legacy.is_alive(&root, core));
+
+        fake_process(&root, 4242, 'Z', 12345, "/jffs/camofy/core/mihomo");
+        assert!(!identity.is_alive(&root, core));
+        assert!(!ProcessIdentity { pid: 7, start_time: None, exe: None }.is_alive(&root, core));
+        assert!(ProcessIdentity::parse("not a pid").is_err());
+    }
+
+    #[tokio::test]
+    async fn sigterm_is_escalated_to_sigkill() {
+        // 忽略 SIGTERM 的进程：exec 后仍保持忽略，模拟不响应 SIGTERM 的内核
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "trap '' TERM; exec sleep 30"])
+            .spawn()
+            .unwrap();
+        let pid = child.id();
+        for _ in 0..100 {
+            let comm = fs::read_to_string(format!("/proc/{pid}/comm")).unwrap_or_default();
+            if comm.trim() == "sleep" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        assert!(send_signal(pid, libc::SIGTERM).unwrap());
+        assert!(!wait_for_exit(pid, Duration::from_millis(500)).await);
+
+        assert!(send_signal(pid, libc::SIGKILL).unwrap());
+        // 未被回收的子进程处于僵尸状态，同样视为已退出
+        assert!(wait_for_exit(pid, Duration::from_secs(5)).await);
+        let _ = child.wait();
+        assert!(!send_signal(pid, libc::SIGTERM).unwrap());
+    }
+}
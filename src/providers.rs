@@ -0,0 +1,797 @@
+//! 托管的 rule-provider / proxy-provider。
+//!
+//! provider 登记在 `AppConfig.providers` 中，由 Camofy 下载并缓存到
+//! `<DATA_ROOT>/config/providers/<id>.<ext>`，生成 merged.yaml 时以 `type: file`
+//! 注入 `rule-providers` / `proxy-providers`，Mihomo 本身无需访问外网拉取 provider。
+//! 缓存由 `provider_auto_update` 定时任务统一刷新。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use axum::{extract::Path as UrlPath, Json};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use uuid::Uuid;
+
+use crate::app::{app_state, current_timestamp, write_file_atomically};
+use crate::{
+    ApiResponse, AppConfig, ConfigChangeReason, ProviderKind, ProviderMeta, config_manager,
+    with_app_config_mut,
+};
+
+const RULE_BEHAVIORS: &[&str] = &["domain", "ipcidr", "classical"];
+const RULE_FORMATS: &[&str] = &["yaml", "text", "mrs"];
+
+/// mrs 为 zstd 压缩的二进制规则集
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// 注入时保留的 proxy-provider 字段：订阅或用户配置对节点的筛选与健康检查设置。
+const PRESERVED_PROXY_PROVIDER_KEYS: &[&str] = &[
+    "health-check",
+    "override",
+    "filter",
+    "exclude-filter",
+    "exclude-type",
+];
+
+#[derive(Serialize)]
+pub struct ProviderDto {
+    pub id: String,
+    pub name: String,
+    pub kind: ProviderKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub behavior: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    pub url: String,
+    pub enabled: bool,
+    /// 本地是否已有缓存文件（只有已缓存的 provider 才会注入 merged.yaml）
+    pub cached: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_fetch_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_fetch_status: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ProviderListResponse {
+    pub providers: Vec<ProviderDto>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateProviderRequest {
+    pub name: String,
+    pub kind: ProviderKind,
+    #[serde(default)]
+    pub behavior: Option<String>,
+    #[serde(default)]
+    pub format: Option<String>,
+    pub url: String,
+}
+
+/// provider 类型创建后不可修改。
+#[derive(Deserialize)]
+pub struct UpdateProviderRequest {
+    pub name: String,
+    #[serde(default)]
+    pub behavior: Option<String>,
+    #[serde(default)]
+    pub format: Option<String>,
+    pub url: String,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+fn providers_dir(root: &Path) -> PathBuf {
+    root.join("config").join("providers")
+}
+
+fn provider_file_name(provider: &ProviderMeta) -> String {
+    let ext = match (provider.kind, provider.format.as_deref()) {
+        (ProviderKind::Rule, Some("text")) => "txt",
+        (ProviderKind::Rule, Some("mrs")) => "mrs",
+        _ => "yaml",
+    };
+    format!("{}.{ext}", provider.id)
+}
+
+fn provider_file_path(root: &Path, provider: &ProviderMeta) -> PathBuf {
+    providers_dir(root).join(provider_file_name(provider))
+}
+
+fn to_provider_dto(root: &Path, provider: &ProviderMeta) -> ProviderDto {
+    ProviderDto {
+        id: provider.id.clone(),
+        name: provider.name.clone(),
+        kind: provider.kind,
+        behavior: provider.behavior.clone(),
+        format: provider.format.clone(),
+        url: provider.url.clone(),
+        enabled: provider.enabled,
+        cached: provider_file_path(root, provider).is_file(),
+        last_fetch_time: provider.last_fetch_time.clone(),
+        last_fetch_status: provider.last_fetch_status.clone(),
+    }
+}
+
+fn provider_error<T>(code: &str, message: String) -> Json<ApiResponse<T>>
+where
+    T: Serialize,
+{
+    Json(ApiResponse {
+        code: code.to_string(),
+        message,
+        data: None,
+    })
+}
+
+/// 校验 provider 的名称、URL 与类型相关字段，返回规范化后的 (behavior, format)。
+fn normalize_provider_fields(
+    kind: ProviderKind,
+    name: &str,
+    url: &str,
+    behavior: Option<&str>,
+    format: Option<&str>,
+) -> Result<(Option<String>, Option<String>), String> {
+    if name.trim().is_empty() {
+        return Err("provider name cannot be empty".to_string());
+    }
+    let url = url.trim();
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err(format!("provider url must be http(s): {url}"));
+    }
+
+    match kind {
+        ProviderKind::Rule => {
+            let Some(behavior) = behavior.map(str::trim) else {
+                return Err("rule provider requires behavior".to_string());
+            };
+            if !RULE_BEHAVIORS.contains(&behavior) {
+                return Err(format!(
+                    "invalid behavior '{behavior}', expected one of {}",
+                    RULE_BEHAVIORS.join(" / ")
+                ));
+            }
+            let format = format.map(str::trim).unwrap_or("yaml");
+            if !RULE_FORMATS.contains(&format) {
+                return Err(format!(
+                    "invalid format '{format}', expected one of {}",
+                    RULE_FORMATS.join(" / ")
+                ));
+            }
+            if format == "mrs" && behavior == "classical" {
+                return Err("mrs format does not support classical behavior".to_string());
+            }
+            Ok((Some(behavior.to_string()), Some(format.to_string())))
+        }
+        ProviderKind::Proxy => {
+            if behavior.is_some() {
+                return Err("proxy provider does not accept behavior".to_string());
+            }
+            if format.is_some_and(|f| f.trim() != "yaml") {
+                return Err("proxy provider only supports yaml format".to_string());
+            }
+            Ok((None, None))
+        }
+    }
+}
+
+/// 同类型 provider 在 merged.yaml 中共享同一命名空间，名称不可重复。
+fn name_taken(config: &AppConfig, kind: ProviderKind, name: &str, except_id: Option<&str>) -> bool {
+    config
+        .providers
+        .iter()
+        .any(|p| p.kind == kind && p.name == name && Some(p.id.as_str()) != except_id)
+}
+
+/// 检查下载内容与 provider 类型 / 格式是否相符，避免把错误页面等写入缓存。
+fn validate_provider_content(provider: &ProviderMeta, content: &[u8]) -> Result<(), String> {
+    if content.is_empty() {
+        return Err("provider content is empty".to_string());
+    }
+
+    if provider.kind == ProviderKind::Rule && provider.format.as_deref() == Some("mrs") {
+        if !content.starts_with(ZSTD_MAGIC) {
+            return Err("content is not a mrs rule set".to_string());
+        }
+        return Ok(());
+    }
+
+    let text = std::str::from_utf8(content)
+        .map_err(|err| format!("provider content is not valid utf-8: {err}"))?;
+
+    if provider.kind == ProviderKind::Rule && provider.format.as_deref() == Some("text") {
+        let has_entry = text.lines().map(str::trim).any(|line| {
+            !line.is_empty() && !line.starts_with('#') && !line.starts_with("//")
+        });
+        if !has_entry {
+            return Err("rule list contains no entries".to_string());
+        }
+        if text.trim_start().starts_with('<') {
+            return Err("content looks like an html page".to_string());
+        }
+        return Ok(());
+    }
+
+    let value: Value =
+        serde_yaml::from_str(text).map_err(|err| format!("invalid provider yaml: {err}"))?;
+    match provider.kind {
+        ProviderKind::Rule => match value.get("payload") {
+            Some(Value::Sequence(_)) => Ok(()),
+            _ => Err("rule provider yaml must contain a 'payload' list".to_string()),
+        },
+        ProviderKind::Proxy => match value.get("proxies") {
+            Some(Value::Sequence(proxies)) if !proxies.is_empty() => Ok(()),
+            _ => Err("proxy provider yaml must contain a non-empty 'proxies' list".to_string()),
+        },
+    }
+}
+
+/// 下载失败的原因，`status` 记录到 `last_fetch_status`。
+struct ProviderFetchError {
+    status: &'static str,
+    message: String,
+}
+
+impl ProviderFetchError {
+    fn new(status: &'static str, message: String) -> Self {
+        ProviderFetchError { status, message }
+    }
+
+    fn code(&self) -> &'static str {
+        match self.status {
+            "invalid_content" => "provider_invalid_content",
+            "write_failed" => "provider_save_failed",
+            _ => "provider_fetch_failed",
+        }
+    }
+}
+
+/// 下载并缓存单个 provider，返回缓存内容是否发生变化。
+async fn download_provider(root: &Path, provider: &ProviderMeta) -> Result<bool, ProviderFetchError> {
+    let state = app_state();
+
+    let resp = state
+        .http_client
+        .get(&provider.url)
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|err| ProviderFetchError::new("request_failed", format!("request failed: {err}")))?;
+    let content = resp.bytes().await.map_err(|err| {
+        ProviderFetchError::new(
+            "body_read_failed",
+            format!("failed to read response body: {err}"),
+        )
+    })?;
+
+    validate_provider_content(provider, &content)
+        .map_err(|err| ProviderFetchError::new("invalid_content", err))?;
+
+    let path = provider_file_path(root, provider);
+    if fs::read(&path).is_ok_and(|current| current == content.as_ref()) {
+        return Ok(false);
+    }
+    write_file_atomically(&path, &content)
+        .map_err(|err| ProviderFetchError::new("write_failed", err))?;
+    Ok(true)
+}
+
+fn record_fetch_status(id: &str, status: &str) {
+    let _ = with_app_config_mut(|config: &mut AppConfig| {
+        if let Some(provider) = config.providers.iter_mut().find(|p| p.id == id) {
+            provider.last_fetch_status = Some(status.to_string());
+            if status == "ok" {
+                provider.last_fetch_time = Some(current_timestamp());
+            }
+        }
+    });
+}
+
+/// 下载 provider 并记录拉取状态。
+async fn refresh_provider(root: &Path, provider: &ProviderMeta) -> Result<bool, ProviderFetchError> {
+    match download_provider(root, provider).await {
+        Ok(changed) => {
+            record_fetch_status(&provider.id, "ok");
+            Ok(changed)
+        }
+        Err(err) => {
+            tracing::error!("failed to fetch provider '{}': {}", provider.name, err.message);
+            record_fetch_status(&provider.id, err.status);
+            Err(err)
+        }
+    }
+}
+
+/// 构造注入 merged.yaml 的 provider 片段：只包含已启用且已有缓存文件的 provider。
+///
+/// 路径相对于 Mihomo 的工作目录（`<DATA_ROOT>/config`）。
+pub(crate) fn providers_layer(root: &Path, config: &AppConfig) -> Mapping {
+    let mut rule_providers = Mapping::new();
+    let mut proxy_providers = Mapping::new();
+
+    for provider in config.providers.iter().filter(|p| p.enabled) {
+        if !provider_file_path(root, provider).is_file() {
+            tracing::warn!(
+                "provider '{}' has not been fetched yet, skip injecting it",
+                provider.name
+            );
+            continue;
+        }
+
+        let mut entry = Mapping::new();
+        entry.insert("type".into(), "file".into());
+        match provider.kind {
+            ProviderKind::Rule => {
+                if let Some(behavior) = &provider.behavior {
+                    entry.insert("behavior".into(), behavior.as_str().into());
+                }
+                if let Some(format) = &provider.format {
+                    entry.insert("format".into(), format.as_str().into());
+                }
+            }
+            ProviderKind::Proxy => {}
+        }
+        entry.insert(
+            "path".into(),
+            format!("./providers/{}", provider_file_name(provider)).into(),
+        );
+
+        let target = match provider.kind {
+            ProviderKind::Rule => &mut rule_providers,
+            ProviderKind::Proxy => &mut proxy_providers,
+        };
+        target.insert(provider.name.as_str().into(), Value::Mapping(entry));
+    }
+
+    let mut layer = Mapping::new();
+    if !rule_providers.is_empty() {
+        layer.insert("rule-providers".into(), Value::Mapping(rule_providers));
+    }
+    if !proxy_providers.is_empty() {
+        layer.insert("proxy-providers".into(), Value::Mapping(proxy_providers));
+    }
+    layer
+}
+
+/// 将托管 provider 写入合并结果：同名 provider 整体替换为本地文件，
+/// proxy-provider 上已有的筛选与健康检查设置予以保留。
+pub(crate) fn inject_providers(config: &mut Value, layer: &Mapping) -> Result<(), String> {
+    let Some(root) = config.as_mapping_mut() else {
+        return Err("merged config is not a mapping".to_string());
+    };
+
+    for (section, entries) in layer {
+        let Some(entries) = entries.as_mapping() else {
+            continue;
+        };
+        let is_proxy = section.as_str() == Some("proxy-providers");
+
+        let target = root
+            .entry(section.clone())
+            .or_insert_with(|| Value::Mapping(Mapping::new()));
+        if !target.is_mapping() {
+            *target = Value::Mapping(Mapping::new());
+        }
+        let Some(target) = target.as_mapping_mut() else {
+            continue;
+        };
+
+        for (name, entry) in entries {
+            let mut entry = entry.clone();
+            if is_proxy
+                && let Some(existing) = target.get(name).and_then(|v| v.as_mapping())
+                && let Some(entry) = entry.as_mapping_mut()
+            {
+                for key in PRESERVED_PROXY_PROVIDER_KEYS {
+                    if let Some(value) = existing.get(*key) {
+                        entry.insert((*key).into(), value.clone());
+                    }
+                }
+            }
+            target.insert(name.clone(), entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// 重新生成 merged.yaml 并通知内核重载；生成失败时返回对应的错误响应（携带校验问题）。
+async fn apply_provider_change(
+    reason: ConfigChangeReason,
+) -> Result<(), Json<ApiResponse<serde_json::Value>>> {
+    let state = app_state();
+    if let Err(err) = crate::user_profiles::generate_merged_config(&state.data_root).await {
+        tracing::error!("failed to generate merged config after provider change: {err}");
+        return Err(Json(ApiResponse {
+            code: err.code().to_string(),
+            message: err.to_string(),
+            data: err.issues_data(),
+        }));
+    }
+    let _ = config_manager::reload_core_if_running(reason).await;
+    Ok(())
+}
+
+pub async fn list_providers() -> Json<ApiResponse<ProviderListResponse>> {
+    let state = app_state();
+    let config = crate::get_app_config_snapshot();
+
+    let providers = config
+        .providers
+        .iter()
+        .map(|p| to_provider_dto(&state.data_root, p))
+        .collect();
+
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "success".to_string(),
+        data: Some(ProviderListResponse { providers }),
+    })
+}
+
+/// 登记新的 provider；创建后需调用 fetch 下载缓存，之后才会注入 merged.yaml。
+pub async fn create_provider(
+    Json(body): Json<CreateProviderRequest>,
+) -> Json<ApiResponse<ProviderDto>> {
+    let state = app_state();
+
+    let (behavior, format) = match normalize_provider_fields(
+        body.kind,
+        &body.name,
+        &body.url,
+        body.behavior.as_deref(),
+        body.format.as_deref(),
+    ) {
+        Ok(fields) => fields,
+        Err(err) => return provider_error("provider_invalid", err),
+    };
+    let name = body.name.trim().to_string();
+
+    let result = with_app_config_mut(|config: &mut AppConfig| {
+        if name_taken(config, body.kind, &name, None) {
+            return None;
+        }
+        let provider = ProviderMeta {
+            id: Uuid::new_v4().to_string(),
+            name: name.clone(),
+            kind: body.kind,
+            behavior,
+            format,
+            url: body.url.trim().to_string(),
+            enabled: true,
+            last_fetch_time: None,
+            last_fetch_status: None,
+        };
+        config.providers.push(provider.clone());
+        Some(provider)
+    });
+
+    match result {
+        Ok(Some(provider)) => Json(ApiResponse {
+            code: "ok".to_string(),
+            message: "created".to_string(),
+            data: Some(to_provider_dto(&state.data_root, &provider)),
+        }),
+        Ok(None) => provider_error(
+            "provider_name_conflict",
+            format!("provider name already exists: {name}"),
+        ),
+        Err(err) => {
+            tracing::error!("{err}");
+            provider_error("config_save_failed", err)
+        }
+    }
+}
+
+pub async fn update_provider(
+    UrlPath(id): UrlPath<String>,
+    Json(body): Json<UpdateProviderRequest>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let state = app_state();
+
+    let Some(existing) = crate::get_app_config_snapshot()
+        .providers
+        .into_iter()
+        .find(|p| p.id == id)
+    else {
+        return provider_error("provider_not_found", "provider not found".to_string());
+    };
+
+    let (behavior, format) = match normalize_provider_fields(
+        existing.kind,
+        &body.name,
+        &body.url,
+        body.behavior.as_deref(),
+        body.format.as_deref(),
+    ) {
+        Ok(fields) => fields,
+        Err(err) => return provider_error("provider_invalid", err),
+    };
+    let name = body.name.trim().to_string();
+
+    // 格式变化后旧缓存已不可用，需重新拉取
+    let format_changed = format != existing.format;
+    if format_changed {
+        let old_path = provider_file_path(&state.data_root, &existing);
+        if let Err(err) = fs::remove_file(&old_path)
+            && err.kind() != std::io::ErrorKind::NotFound
+        {
+            tracing::warn!("failed to remove provider cache {}: {err}", old_path.display());
+        }
+    }
+
+    let result = with_app_config_mut(|config: &mut AppConfig| {
+        if name_taken(config, existing.kind, &name, Some(&id)) {
+            return Err("conflict");
+        }
+        let Some(provider) = config.providers.iter_mut().find(|p| p.id == id) else {
+            return Err("not_found");
+        };
+        provider.name = name.clone();
+        provider.behavior = behavior.clone();
+        provider.format = format.clone();
+        provider.url = body.url.trim().to_string();
+        if let Some(enabled) = body.enabled {
+            provider.enabled = enabled;
+        }
+        if format_changed {
+            provider.last_fetch_time = None;
+            provider.last_fetch_status = None;
+        }
+        Ok(provider.clone())
+    });
+
+    let updated = match result {
+        Ok(Ok(provider)) => provider,
+        Ok(Err("conflict")) => {
+            return provider_error(
+                "provider_name_conflict",
+                format!("provider name already exists: {name}"),
+            );
+        }
+        Ok(Err(_)) => {
+            return provider_error("provider_not_found", "provider not found".to_string());
+        }
+        Err(err) => {
+            tracing::error!("{err}");
+            return provider_error("config_save_failed", err);
+        }
+    };
+
+    // 合并失败时恢复原有元数据，拒绝本次修改
+    if let Err(resp) = apply_provider_change(ConfigChangeReason::ProviderUpdated).await {
+        if let Err(err) = with_app_config_mut(|config: &mut AppConfig| {
+            if let Some(provider) = config.providers.iter_mut().find(|p| p.id == id) {
+                *provider = existing;
+            }
+        }) {
+            tracing::error!("{err}");
+        }
+        return resp;
+    }
+
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "updated".to_string(),
+        data: serde_json::to_value(to_provider_dto(&state.data_root, &updated)).ok(),
+    })
+}
+
+pub async fn delete_provider(UrlPath(id): UrlPath<String>) -> Json<ApiResponse<serde_json::Value>> {
+    let state = app_state();
+
+    let result = with_app_config_mut(|config: &mut AppConfig| {
+        let index = config.providers.iter().position(|p| p.id == id)?;
+        Some(config.providers.remove(index))
+    });
+
+    let removed = match result {
+        Ok(Some(provider)) => provider,
+        Ok(None) => {
+            return provider_error("provider_not_found", "provider not found".to_string());
+        }
+        Err(err) => {
+            tracing::error!("{err}");
+            return provider_error("config_save_failed", err);
+        }
+    };
+
+    let path = provider_file_path(&state.data_root, &removed);
+    if let Err(err) = fs::remove_file(&path)
+        && err.kind() != std::io::ErrorKind::NotFound
+    {
+        tracing::warn!("failed to remove provider cache {}: {err}", path.display());
+    }
+
+    // 与删除订阅一致：重新生成失败（例如规则仍引用该 provider）不影响删除结果，校验问题随响应返回。
+    let issues = crate::user_profiles::generate_merged_config(&state.data_root).await
+        .err()
+        .and_then(|err| {
+            tracing::error!("failed to generate merged config after provider delete: {err}");
+            err.issues_data()
+        });
+    let _ = config_manager::reload_core_if_running(ConfigChangeReason::ProviderDeleted).await;
+
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "deleted".to_string(),
+        data: Some(issues.unwrap_or_else(|| serde_json::json!({}))),
+    })
+}
+
+/// 立即下载指定 provider；内容有变化且 provider 已启用时重新生成 merged.yaml 并重载内核。
+pub async fn fetch_provider(UrlPath(id): UrlPath<String>) -> Json<ApiResponse<serde_json::Value>> {
+    let state = app_state();
+
+    let Some(provider) = crate::get_app_config_snapshot()
+        .providers
+        .into_iter()
+        .find(|p| p.id == id)
+    else {
+        return provider_error("provider_not_found", "provider not found".to_string());
+    };
+
+    let changed = match refresh_provider(&state.data_root, &provider).await {
+        Ok(changed) => changed,
+        Err(err) => return provider_error(err.code(), err.message),
+    };
+
+    if changed
+        && provider.enabled
+        && let Err(resp) = apply_provider_change(ConfigChangeReason::ProviderUpdated).await
+    {
+        return resp;
+    }
+
+    let provider = crate::get_app_config_snapshot()
+        .providers
+        .into_iter()
+        .find(|p| p.id == id)
+        .unwrap_or(provider);
+
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: if changed { "fetched" } else { "unchanged" }.to_string(),
+        data: serde_json::to_value(to_provider_dto(&state.data_root, &provider)).ok(),
+    })
+}
+
+/// 定时任务：刷新全部已启用的 provider，任一内容变化时重新生成 merged.yaml 并重载内核。
+///
+/// - 没有已启用的 provider 时返回 Err("skipped:...")，由调度器记录为跳过状态；
+/// - 部分 provider 下载失败不影响其余 provider，最终以失败状态汇总返回。
+pub async fn auto_update_providers() -> Result<(), String> {
+    let state = app_state();
+
+    let providers: Vec<ProviderMeta> = crate::get_app_config_snapshot()
+        .providers
+        .into_iter()
+        .filter(|p| p.enabled)
+        .collect();
+    if providers.is_empty() {
+        return Err("skipped:no_enabled_providers".to_string());
+    }
+
+    let mut changed = false;
+    let mut failures = Vec::new();
+    for provider in &providers {
+        match refresh_provider(&state.data_root, provider).await {
+            Ok(c) => changed |= c,
+            Err(err) => failures.push(format!("{}: {}", provider.name, err.message)),
+        }
+    }
+
+    if changed
+        && let Err(axum::Json(resp)) =
+            apply_provider_change(ConfigChangeReason::ProviderUpdated).await
+    {
+        failures.push(format!("merged config: {}", resp.message));
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("provider_auto_update_failed: {}", failures.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inject_providers, provider_file_path, providers_layer, validate_provider_content};
+    use crate::{AppConfig, ProviderKind, ProviderMeta};
+    use std::fs;
+
+    fn provider(id: &str, kind: ProviderKind, format: Option<&str>) -> ProviderMeta {
+        ProviderMeta {
+            id: id.to_string(),
+            name: id.to_string(),
+            kind,
+            behavior: (kind == ProviderKind::Rule).then(|| "domain".to_string()),
+            format: format.map(str::to_string),
+            url: "https://example.com/provider".to_string(),
+            enabled: true,
+            last_fetch_time: None,
+            last_fetch_status: None,
+        }
+    }
+
+    #[test]
+    fn provider_content_is_checked_against_kind_and_format() {
+        let rule_yaml = provider("ads", ProviderKind::Rule, Some("yaml"));
+        assert!(validate_provider_content(&rule_yaml, b"payload:\n  - '+.ads.example'\n").is_ok());
+        assert!(validate_provider_content(&rule_yaml, b"proxies: []\n").is_err());
+
+        let rule_text = provider("direct", ProviderKind::Rule, Some("text"));
+        assert!(validate_provider_content(&rule_text, b"# list\n+.example.com\n").is_ok());
+        assert!(validate_provider_content(&rule_text, b"# only comments\n").is_err());
+        assert!(validate_provider_content(&rule_text, b"<html>blocked</html>").is_err());
+
+        let rule_mrs = provider("cn", ProviderKind::Rule, Some("mrs"));
+        assert!(validate_provider_content(&rule_mrs, &[0x28, 0xb5, 0x2f, 0xfd, 0x00]).is_ok());
+        assert!(validate_provider_content(&rule_mrs, b"payload: []").is_err());
+
+        let proxies = provider("nodes", ProviderKind::Proxy, None);
+        assert!(validate_provider_content(
+            &proxies,
+            b"proxies:\n  - {name: a, type: ss, server: 1.1.1.1, port: 1}\n"
+        )
+        .is_ok());
+        assert!(validate_provider_content(&proxies, b"proxies: []\n").is_err());
+        assert!(validate_provider_content(&proxies, b"").is_err());
+    }
+
+    #[test]
+    fn cached_providers_are_injected_as_local_files() {
+        let root = crate::test_support::TempRoot::new("providers");
+
+        let ads = provider("ads", ProviderKind::Rule, Some("text"));
+        let nodes = provider("nodes", ProviderKind::Proxy, None);
+        let pending = provider("pending", ProviderKind::Rule, Some("yaml"));
+        let mut disabled = provider("disabled", ProviderKind::Proxy, None);
+        disabled.enabled = false;
+
+        for cached in [&ads, &nodes, &disabled] {
+            let path = provider_file_path(&root, cached);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "cached").unwrap();
+        }
+
+        let config = AppConfig {
+            providers: vec![ads, nodes, pending, disabled],
+            ..AppConfig::default()
+        };
+        let layer = providers_layer(&root, &config);
+
+        let mut merged: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+proxy-providers:
+  nodes:
+    type: http
+    url: https://example.com/nodes
+    interval: 3600
+    health-check: {enable: true, url: https://www.gstatic.com/generate_204, interval: 300}
+  other:
+    type: http
+    url: https://example.com/other
+"#,
+        )
+        .unwrap();
+        inject_providers(&mut merged, &layer).unwrap();
+
+        let ads = &merged["rule-providers"]["ads"];
+        assert_eq!(ads["type"].as_str(), Some("file"));
+        assert_eq!(ads["behavior"].as_str(), Some("domain"));
+        assert_eq!(ads["format"].as_str(), Some("text"));
+        assert_eq!(ads["path"].as_str(), Some("./providers/ads.txt"));
+        assert!(merged["rule-providers"].get("pending").is_none());
+
+        let nodes = &merged["proxy-providers"]["nodes"];
+        assert_eq!(nodes["type"].as_str(), Some("file"));
+        assert_eq!(nodes["path"].as_str(), Some("./providers/nodes.yaml"));
+        assert!(nodes.get("url").is_none());
+        assert_eq!(nodes["health-check"]["interval"].as_u64(), Some(300));
+        assert_eq!(merged["proxy-providers"]["other"]["type"].as_str(), Some("http"));
+        assert!(merged["proxy-providers"].get("disabled").is_none());
+    }
+}
@@ -10,12 +10,14 @@ use crate::{save_app_config, AppConfig, ScheduledTaskConfig};
 enum TaskKind {
     UpdateSubscriptions,
     UpdateGeoip,
+    RefreshProviders,
 }
 
 fn task_name(kind: TaskKind) -> &'static str {
     match kind {
         TaskKind::UpdateSubscriptions => "subscription_auto_update",
         TaskKind::UpdateGeoip => "geoip_auto_update",
+        TaskKind::RefreshProviders => "provider_auto_update",
     }
 }
 
@@ -26,6 +28,7 @@ fn task_config<'a>(
     match kind {
         TaskKind::UpdateSubscriptions => config.subscription_auto_update.as_ref(),
         TaskKind::UpdateGeoip => config.geoip_auto_update.as_ref(),
+        TaskKind::RefreshProviders => config.provider_auto_update.as_ref(),
     }
 }
 
@@ -42,16 +45,24 @@ fn task_config_mut<'a>(
         TaskKind::UpdateGeoip => config
             .geoip_auto_update
             .get_or_insert_with(ScheduledTaskConfig::default),
+        TaskKind::RefreshProviders => config
+            .provider_auto_update
+            .get_or_insert_with(ScheduledTaskConfig::default),
     }
 }
 
 static SUBS_RUNNING: AtomicBool = AtomicBool::new(false);
 static GEOIP_RUNNING: AtomicBool = AtomicBool::new(false);
+static PROVIDERS_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// 全局订阅更新任务等待按计划刷新让出运行标记的最长时间。
+const SUBSCRIPTION_LOCK_WAIT: Duration = Duration::from_secs(30 * 60);
 
 fn task_flag(kind: TaskKind) -> &'static AtomicBool {
     match kind {
         TaskKind::UpdateSubscriptions => &SUBS_RUNNING,
         TaskKind::UpdateGeoip => &GEOIP_RUNNING,
+        TaskKind::RefreshProviders => &PROVIDERS_RUNNING,
     }
 }
 
@@ -68,6 +79,18 @@ impl<'a> TaskLockGuard<'a> {
         TaskLockGuard { flag, acquired }
     }
 
+    /// 在 `timeout` 内每秒重试获取运行标记，用于需要等待其他任务让出的场景。
+    async fn lock_waiting(flag: &'a AtomicBool, timeout: Duration) -> Self {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let guard = TaskLockGuard::lock(flag);
+            if guard.is_acquired() || tokio::time::Instant::now() >= deadline {
+                return guard;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
     fn is_acquired(&self) -> bool {
         self.acquired
     }
@@ -265,6 +288,28 @@ fn parse_dow_field(spec: &str) -> Result<CronField, String> {
     Ok(field)
 }
 
+/// 校验 cron 表达式，规则与全局定时任务一致。
+pub(crate) fn validate_cron(expr: &str) -> Result<(), String> {
+    CronSchedule::parse(expr).map(|_| ())
+}
+
+/// 自 `since`（Unix 秒）之后到 `now` 为止，cron 表达式是否至少触发过一次。
+/// `since` 为 0（从未执行过）时视为已到期。
+pub(crate) fn cron_due_since(expr: &str, since: u64, now: u64) -> Result<bool, String> {
+    use chrono::TimeZone;
+
+    let schedule = CronSchedule::parse(expr)?;
+    if since == 0 {
+        return Ok(true);
+    }
+    let Some(since) = Local.timestamp_opt(since as i64, 0).single() else {
+        return Ok(true);
+    };
+    Ok(schedule
+        .next_after(since)
+        .is_some_and(|next| next.timestamp() <= now as i64))
+}
+
 enum TaskRunState {
     Success,
     Skipped(String),
@@ -274,7 +319,14 @@ enum TaskRunState {
 async fn execute_task(kind: TaskKind) -> TaskRunState {
     let name = task_name(kind);
     let flag = task_flag(kind);
-    let guard = TaskLockGuard::lock(flag);
+    // 订阅的按计划刷新与全局订阅更新共用运行标记；全局任务遇到正在进行的计划刷新时
+    // 等待其结束后再执行，而不是直接跳过本次 cron 触发。
+    let guard = match kind {
+        TaskKind::UpdateSubscriptions => {
+            TaskLockGuard::lock_waiting(flag, SUBSCRIPTION_LOCK_WAIT).await
+        }
+        _ => TaskLockGuard::lock(flag),
+    };
 
     if !guard.is_acquired() {
         return TaskRunState::Skipped("task already running".to_string());
@@ -283,6 +335,7 @@ async fn execute_task(kind: TaskKind) -> TaskRunState {
     let result = match kind {
         TaskKind::UpdateSubscriptions => crate::subscriptions::auto_update_subscriptions().await,
         TaskKind::UpdateGeoip => crate::geoip::update_geoip_db().await,
+        TaskKind::RefreshProviders => crate::providers::auto_update_providers().await,
     };
 
     match result {
@@ -419,8 +472,31 @@ async fn run_task_loop(kind: TaskKind) {
     }
 }
 
+/// 按各订阅自身的自动更新方式（`interval` / `cron`，以及 `global` 方式下服务端
+/// 下发的 `profile-update-interval`）刷新到期的订阅。
+///
+/// 与全局订阅更新任务共用运行标记，避免同时拉取；标记被占用时跳过本轮检查，
+/// 而全局任务会等待本轮刷新结束后再执行。
+async fn run_subscription_schedule_loop() {
+    loop {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+
+        let guard = TaskLockGuard::lock(task_flag(TaskKind::UpdateSubscriptions));
+        if !guard.is_acquired() {
+            continue;
+        }
+        match crate::subscriptions::refresh_due_subscriptions().await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("refreshed {count} subscription(s) per their schedules"),
+            Err(err) => tracing::warn!("failed to refresh scheduled subscriptions: {err}"),
+        }
+    }
+}
+
 /// 启动所有后台定时任务调度循环。
 pub fn start_scheduler() {
     tokio::spawn(run_task_loop(TaskKind::UpdateSubscriptions));
     tokio::spawn(run_task_loop(TaskKind::UpdateGeoip));
+    tokio::spawn(run_task_loop(TaskKind::RefreshProviders));
+    tokio::spawn(run_subscription_schedule_loop());
 }
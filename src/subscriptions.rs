@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 use axum::{extract::Path, Json};
@@ -5,20 +6,424 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::app::app_state;
-use crate::app::current_timestamp;
-use crate::{ApiResponse, AppConfig, ConfigChangeReason, ProfileMeta, ProfileType};
+use crate::app::{current_timestamp, write_file_atomically};
+use crate::{
+    ApiResponse, AppConfig, AppEvent, CombinedSubscriptionConfig, ConfigChangeReason,
+    ProfileMeta, ProfileType,
+};
+use crate::convert::{ConvertedSubscription, SubscriptionFormat};
+use crate::history::RevisionKind;
 use crate::{config_manager, save_app_config, with_app_config_mut};
 
+/// 已用流量达到总流量的该比例时发出流量告警。
+const QUOTA_WARNING_RATIO: f64 = 0.9;
+/// 距到期不足该时长时发出到期告警。
+const EXPIRY_WARNING_SECS: u64 = 3 * 24 * 3600;
+/// 按计划自动刷新失败后，至少间隔该时长再重试。
+const SCHEDULE_RETRY_SECS: u64 = 3600;
+/// `interval` 自动更新方式允许的最长间隔（小时）。
+const MAX_SCHEDULE_INTERVAL_HOURS: u32 = 24 * 30;
+/// 单个订阅可配置的最大请求超时（秒）。
+const MAX_FETCH_TIMEOUT_SECS: u64 = 600;
+/// 每个订阅 URL 的最多尝试次数（含首次请求）。
+const FETCH_ATTEMPTS_PER_URL: u32 = 3;
+/// 重试的初始退避时长（毫秒），之后每次翻倍。
+const FETCH_RETRY_BASE_MS: u64 = 1000;
+
+/// 订阅服务端通过 `subscription-userinfo` 响应头下发的流量与到期信息。
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub(crate) struct SubscriptionUserinfo {
+    #[serde(default)]
+    pub upload: u64,
+    #[serde(default)]
+    pub download: u64,
+    #[serde(default)]
+    pub total: u64,
+    /// 到期时间（Unix 秒），未提供或为 0 时视为不过期
+    #[serde(default)]
+    pub expire: Option<u64>,
+}
+
+impl SubscriptionUserinfo {
+    pub fn used(&self) -> u64 {
+        self.upload.saturating_add(self.download)
+    }
+}
+
+/// 订阅流量 / 到期告警类型。
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageWarning {
+    QuotaLow,
+    QuotaExhausted,
+    Expiring,
+    Expired,
+}
+
+/// 单个订阅的拉取选项；全部为默认值时直接复用全局 HTTP 客户端。
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub(crate) struct SubscriptionFetchOptions {
+    /// 覆盖默认的 User-Agent（部分机场按 UA 下发不同格式）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// 额外的请求头，例如鉴权 token
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// 请求超时（秒），未设置时使用全局超时
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    /// 通过内核的 mixed-port 代理拉取（订阅地址被墙时使用），要求内核正在运行
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub via_core: bool,
+    /// 跳过 TLS 证书校验（自签名证书的订阅服务端）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_insecure: bool,
+}
+
+/// 接口返回拉取选项时用于替换请求头取值的掩码；更新时原样提交表示保留已保存的值。
+const MASKED_HEADER_VALUE: &str = "******";
+
+impl SubscriptionFetchOptions {
+    /// 供接口返回的副本：请求头只保留名称，取值（可能是鉴权 token）替换为掩码。
+    fn masked(&self) -> Self {
+        let mut masked = self.clone();
+        for value in masked.headers.values_mut() {
+            *value = MASKED_HEADER_VALUE.to_string();
+        }
+        masked
+    }
+
+    /// 将取值为掩码的请求头恢复为 `previous` 中已保存的值。
+    fn restore_masked_headers(&mut self, previous: &Self) -> Result<(), String> {
+        for (name, value) in self.headers.iter_mut() {
+            if value != MASKED_HEADER_VALUE {
+                continue;
+            }
+            let Some(saved) = previous.headers.get(name) else {
+                return Err(format!("header '{name}' has no saved value to keep"));
+            };
+            *value = saved.clone();
+        }
+        Ok(())
+    }
+
+    fn needs_dedicated_client(&self) -> bool {
+        self.user_agent.is_some() || self.timeout_secs.is_some() || self.via_core || self.allow_insecure
+    }
+
+    /// 清理首尾空白并检查各字段，返回规范化后的选项。
+    fn normalized(mut self) -> Result<Self, String> {
+        self.user_agent = self
+            .user_agent
+            .map(|ua| ua.trim().to_string())
+            .filter(|ua| !ua.is_empty());
+        if let Some(ua) = self.user_agent.as_deref()
+            && reqwest::header::HeaderValue::from_str(ua).is_err()
+        {
+            return Err("user_agent contains invalid characters".to_string());
+        }
+
+        let mut headers = BTreeMap::new();
+        for (name, value) in self.headers {
+            let name = name.trim().to_ascii_lowercase();
+            if reqwest::header::HeaderName::from_bytes(name.as_bytes()).is_err() {
+                return Err(format!("invalid header name '{name}'"));
+            }
+            if matches!(name.as_str(), "host" | "content-length" | "user-agent") {
+                return Err(format!("header '{name}' cannot be overridden"));
+            }
+            let value = value.trim().to_string();
+            if reqwest::header::HeaderValue::from_str(&value).is_err() {
+                return Err(format!("invalid value for header '{name}'"));
+            }
+            headers.insert(name, value);
+        }
+        self.headers = headers;
+
+        if let Some(timeout) = self.timeout_secs
+            && !(1..=MAX_FETCH_TIMEOUT_SECS).contains(&timeout)
+        {
+            return Err(format!(
+                "timeout_secs must be between 1 and {MAX_FETCH_TIMEOUT_SECS}"
+            ));
+        }
+
+        Ok(self)
+    }
+
+    /// 按选项构造本次拉取使用的 HTTP 客户端与请求。
+    fn build_request(&self, url: &str) -> Result<reqwest::RequestBuilder, String> {
+        let state = app_state();
+        // 额外请求头按请求附加，其余选项都为默认值时无需单独构造客户端
+        let client = if !self.needs_dedicated_client() {
+            state.http_client.clone()
+        } else {
+            let mut builder = reqwest::Client::builder()
+                .user_agent(
+                    self.user_agent
+                        .as_deref()
+                        .unwrap_or(crate::app::DEFAULT_USER_AGENT),
+                )
+                .timeout(std::time::Duration::from_secs(
+                    self.timeout_secs
+                        .unwrap_or(crate::app::DEFAULT_HTTP_TIMEOUT_SECS),
+                ))
+                .danger_accept_invalid_certs(self.allow_insecure);
+            if self.via_core {
+                let port = crate::system_config::mixed_port(&state.data_root);
+                let proxy = reqwest::Proxy::all(format!("http://127.0.0.1:{port}"))
+                    .map_err(|err| format!("invalid core proxy address: {err}"))?;
+                builder = builder.proxy(proxy);
+            }
+            builder
+                .build()
+                .map_err(|err| format!("failed to build http client: {err}"))?
+        };
+
+        let mut request = client.get(url);
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        Ok(request)
+    }
+}
+
+/// 订阅的自动更新方式。
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub(crate) enum SubscriptionSchedule {
+    /// 跟随全局订阅自动更新任务（`subscription_auto_update`）
+    #[default]
+    Global,
+    /// 距上次成功拉取超过指定小时数后刷新
+    Interval { hours: u32 },
+    /// 按独立的 cron 表达式刷新
+    Cron { cron: String },
+    /// 不自动更新，仅手动拉取
+    Disabled,
+}
+
+impl SubscriptionSchedule {
+    fn normalized(self) -> Result<Self, String> {
+        match self {
+            SubscriptionSchedule::Interval { hours }
+                if !(1..=MAX_SCHEDULE_INTERVAL_HOURS).contains(&hours) =>
+            {
+                Err(format!(
+                    "interval hours must be between 1 and {MAX_SCHEDULE_INTERVAL_HOURS}"
+                ))
+            }
+            SubscriptionSchedule::Cron { cron } => {
+                let cron = cron.trim().to_string();
+                crate::scheduler::validate_cron(&cron)
+                    .map_err(|err| format!("invalid cron expression: {err}"))?;
+                Ok(SubscriptionSchedule::Cron { cron })
+            }
+            other => Ok(other),
+        }
+    }
+}
+
+/// 上一次成功拉取的来源及其缓存校验信息，用于发送条件请求。
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub(crate) struct SubscriptionFetchCache {
+    /// 实际提供内容的 URL（主 URL 或某个备用 URL）
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+/// 清理备用 URL 列表：去除空白、空项以及与主 URL 重复的项。
+fn normalize_mirror_urls(primary: &str, mirrors: Vec<String>) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for url in mirrors {
+        let url = url.trim().to_string();
+        if !url.is_empty() && url != primary.trim() && !result.contains(&url) {
+            result.push(url);
+        }
+    }
+    result
+}
+
+/// 拉取订阅时从响应头中提取的附加信息。
+#[derive(Default)]
+struct SubscriptionHeaders {
+    userinfo: Option<SubscriptionUserinfo>,
+    update_interval_hours: Option<u32>,
+    filename: Option<String>,
+}
+
+impl SubscriptionHeaders {
+    fn from_headers(headers: &reqwest::header::HeaderMap) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+        SubscriptionHeaders {
+            userinfo: header("subscription-userinfo").and_then(parse_userinfo),
+            update_interval_hours: header("profile-update-interval")
+                .and_then(|v| v.parse::<u32>().ok())
+                .filter(|hours| *hours > 0),
+            filename: header("content-disposition").and_then(parse_content_disposition_filename),
+        }
+    }
+}
+
+/// 解析 `upload=1234; download=5678; total=10737418240; expire=1700000000`。
+fn parse_userinfo(value: &str) -> Option<SubscriptionUserinfo> {
+    let mut info = SubscriptionUserinfo::default();
+    let mut found = false;
+    for pair in value.split(';') {
+        let Some((key, raw)) = pair.split_once('=') else {
+            continue;
+        };
+        // 个别服务端会下发浮点数或科学计数法
+        let Some(number) = raw
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .or_else(|| raw.trim().parse::<f64>().ok().map(|v| v.max(0.0) as u64))
+        else {
+            continue;
+        };
+        match key.trim().to_ascii_lowercase().as_str() {
+            "upload" => info.upload = number,
+            "download" => info.download = number,
+            "total" => info.total = number,
+            "expire" => info.expire = (number > 0).then_some(number),
+            _ => continue,
+        }
+        found = true;
+    }
+    found.then_some(info)
+}
+
+/// 优先使用 RFC 5987 的 `filename*=UTF-8''...`，其次为 `filename="..."`。
+fn parse_content_disposition_filename(value: &str) -> Option<String> {
+    let mut plain = None;
+    for part in value.split(';') {
+        let Some((key, raw)) = part.trim().split_once('=') else {
+            continue;
+        };
+        let raw = raw.trim().trim_matches('"');
+        match key.trim().to_ascii_lowercase().as_str() {
+            "filename*" => {
+                let encoded = raw.rsplit("''").next().unwrap_or(raw);
+                let decoded = percent_encoding::percent_decode_str(encoded)
+                    .decode_utf8_lossy()
+                    .into_owned();
+                if !decoded.is_empty() {
+                    return Some(decoded);
+                }
+            }
+            "filename" if !raw.is_empty() => plain = Some(raw.to_string()),
+            _ => {}
+        }
+    }
+    plain
+}
+
+/// 根据流量与到期信息判断需要发出的告警。
+fn usage_warnings(info: &SubscriptionUserinfo, now: u64) -> Vec<UsageWarning> {
+    let mut warnings = Vec::new();
+    if info.total > 0 {
+        let used = info.used();
+        if used >= info.total {
+            warnings.push(UsageWarning::QuotaExhausted);
+        } else if used as f64 >= info.total as f64 * QUOTA_WARNING_RATIO {
+            warnings.push(UsageWarning::QuotaLow);
+        }
+    }
+    if let Some(expire) = info.expire {
+        if expire <= now {
+            warnings.push(UsageWarning::Expired);
+        } else if expire - now <= EXPIRY_WARNING_SECS {
+            warnings.push(UsageWarning::Expiring);
+        }
+    }
+    warnings
+}
+
+/// 相对上一次拉取新出现的告警；已告警过且仍然存在的不再重复提醒。
+fn newly_raised_warnings(previous: &[UsageWarning], current: &[UsageWarning]) -> Vec<UsageWarning> {
+    current
+        .iter()
+        .filter(|w| !previous.contains(w))
+        .copied()
+        .collect()
+}
+
+fn emit_usage_warnings(
+    id: &str,
+    name: &str,
+    info: &SubscriptionUserinfo,
+    warnings: Vec<UsageWarning>,
+) {
+    for warning in warnings {
+        tracing::warn!("subscription '{name}' usage warning: {warning:?}");
+        let event = AppEvent::SubscriptionUsageWarning {
+            subscription_id: id.to_string(),
+            name: name.to_string(),
+            warning,
+            used: info.used(),
+            total: info.total,
+            expire: info.expire,
+            timestamp: current_timestamp(),
+        };
+        if let Err(err) = app_state().events_tx.send(event) {
+            tracing::debug!("failed to broadcast AppEvent::SubscriptionUsageWarning: {err}");
+        }
+    }
+}
+
+/// 单个订阅内容（转换前后）的大小上限。
+const MAX_SUBSCRIPTION_BYTES: usize = 16 * 1024 * 1024;
+
 #[derive(Serialize)]
 pub struct SubscriptionDto {
     pub id: String,
     pub name: String,
     pub url: String,
     pub is_active: bool,
+    /// 是否参与多订阅合并
+    pub is_combined: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_fetch_time: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_fetch_status: Option<String>,
+    /// 最近一次拉取时识别出的订阅格式
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<SubscriptionFormat>,
+    /// 已用流量（上传 + 下载，字节）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub used_bytes: Option<u64>,
+    /// 总流量（字节）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_bytes: Option<u64>,
+    /// 到期时间（Unix 秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expire_time: Option<String>,
+    /// 订阅服务端建议的刷新间隔（小时）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_interval_hours: Option<u32>,
+    /// 订阅服务端给出的文件名，可作为订阅名称的参考
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_filename: Option<String>,
+    /// 备用 URL，主 URL 拉取失败时依次尝试
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mirror_urls: Vec<String>,
+    /// 最近一次成功拉取所用的 URL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetched_url: Option<String>,
+    /// 拉取选项（UA、请求头、超时、经内核代理等）；请求头取值以掩码返回
+    pub fetch_options: SubscriptionFetchOptions,
+    /// 自动更新方式
+    pub schedule: SubscriptionSchedule,
 }
 
 #[derive(Serialize)]
@@ -30,12 +435,44 @@ pub struct SubscriptionListResponse {
 pub struct CreateSubscriptionRequest {
     pub name: String,
     pub url: String,
+    #[serde(default)]
+    pub mirror_urls: Vec<String>,
+    #[serde(default)]
+    pub fetch_options: Option<SubscriptionFetchOptions>,
+    #[serde(default)]
+    pub schedule: Option<SubscriptionSchedule>,
 }
 
 #[derive(Deserialize)]
 pub struct UpdateSubscriptionRequest {
     pub name: String,
     pub url: String,
+    /// 未提供时保留原有备用 URL
+    #[serde(default)]
+    pub mirror_urls: Option<Vec<String>>,
+    /// 未提供时保留原有拉取选项
+    #[serde(default)]
+    pub fetch_options: Option<SubscriptionFetchOptions>,
+    /// 未提供时保留原有自动更新方式
+    #[serde(default)]
+    pub schedule: Option<SubscriptionSchedule>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CombinedSubscriptionDto {
+    pub enabled: bool,
+    /// 参与合并的订阅 id；主订阅始终为当前活跃订阅
+    pub subscription_ids: Vec<String>,
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub primary_id: Option<String>,
+}
+
+fn to_combined_dto(config: &AppConfig) -> CombinedSubscriptionDto {
+    CombinedSubscriptionDto {
+        enabled: config.combined_subscription.enabled,
+        subscription_ids: config.combined_subscription.subscription_ids.clone(),
+        primary_id: config.active_subscription_id.clone(),
+    }
 }
 
 fn subscription_dir(root: &PathBuf, id: &str) -> PathBuf {
@@ -61,8 +498,32 @@ fn to_subscription_dto(config: &AppConfig, profile: &ProfileMeta) -> Option<Subs
         name,
         url,
         is_active: active_id == Some(profile.id.as_str()),
+        is_combined: config.combined_subscription.enabled
+            && config
+                .combined_subscription
+                .subscription_ids
+                .iter()
+                .any(|sid| sid == &profile.id),
         last_fetch_time: profile.last_fetch_time.clone(),
         last_fetch_status: profile.last_fetch_status.clone(),
+        format: profile.subscription_format,
+        used_bytes: profile.userinfo.as_ref().map(SubscriptionUserinfo::used),
+        total_bytes: profile
+            .userinfo
+            .as_ref()
+            .map(|info| info.total)
+            .filter(|total| *total > 0),
+        expire_time: profile
+            .userinfo
+            .as_ref()
+            .and_then(|info| info.expire)
+            .map(|expire| expire.to_string()),
+        update_interval_hours: profile.update_interval_hours,
+        remote_filename: profile.remote_filename.clone(),
+        mirror_urls: profile.mirror_urls.clone(),
+        fetched_url: profile.fetch_cache.as_ref().map(|cache| cache.url.clone()),
+        fetch_options: profile.fetch_options.masked(),
+        schedule: profile.schedule.clone(),
     })
 }
 
@@ -88,11 +549,36 @@ pub async fn list_subscriptions() -> Json<ApiResponse<SubscriptionListResponse>>
     })
 }
 
+fn invalid_fetch_options(err: String) -> Json<ApiResponse<SubscriptionDto>> {
+    Json(ApiResponse {
+        code: "subscription_invalid_fetch_options".to_string(),
+        message: err,
+        data: None,
+    })
+}
+
+fn invalid_schedule(err: String) -> Json<ApiResponse<SubscriptionDto>> {
+    Json(ApiResponse {
+        code: "subscription_invalid_schedule".to_string(),
+        message: err,
+        data: None,
+    })
+}
+
 pub async fn create_subscription(
     Json(body): Json<CreateSubscriptionRequest>,
 ) -> Json<ApiResponse<SubscriptionDto>> {
     let name = body.name.clone();
     let url = body.url.clone();
+    let mirror_urls = normalize_mirror_urls(&url, body.mirror_urls);
+    let fetch_options = match body.fetch_options.unwrap_or_default().normalized() {
+        Ok(options) => options,
+        Err(err) => return invalid_fetch_options(err),
+    };
+    let schedule = match body.schedule.unwrap_or_default().normalized() {
+        Ok(schedule) => schedule,
+        Err(err) => return invalid_schedule(err),
+    };
 
     let result = with_app_config_mut(|config: &mut AppConfig| {
         let id = Uuid::new_v4().to_string();
@@ -106,6 +592,15 @@ pub async fn create_subscription(
             last_fetch_time: None,
             last_fetch_status: None,
             last_modified_time: None,
+            subscription_format: None,
+            userinfo: None,
+            update_interval_hours: None,
+            remote_filename: None,
+            mirror_urls,
+            fetch_cache: None,
+            fetch_options,
+            schedule,
+            usage_warnings: Vec::new(),
         };
 
         if config.active_subscription_id.is_none() {
@@ -141,6 +636,17 @@ pub async fn update_subscription(
 ) -> Json<ApiResponse<SubscriptionDto>> {
     let state = app_state();
 
+    let fetch_options = match body.fetch_options.map(SubscriptionFetchOptions::normalized) {
+        Some(Ok(options)) => Some(options),
+        Some(Err(err)) => return invalid_fetch_options(err),
+        None => None,
+    };
+    let schedule = match body.schedule.map(SubscriptionSchedule::normalized) {
+        Some(Ok(schedule)) => Some(schedule),
+        Some(Err(err)) => return invalid_schedule(err),
+        None => None,
+    };
+
     let mut guard = state
         .app_config
         .write()
@@ -159,13 +665,47 @@ pub async fn update_subscription(
         });
     };
 
+    // 先完成所有校验，再修改配置：校验失败时不能留下未保存的部分修改
+    let fetch_options = match fetch_options {
+        Some(mut options) => {
+            if let Err(err) = options.restore_masked_headers(&profile.fetch_options) {
+                return invalid_fetch_options(err);
+            }
+            Some(options)
+        }
+        None => None,
+    };
+    let original = profile.clone();
+
+    let url_changed = profile.url.as_deref() != Some(body.url.as_str());
+    if let Some(mirrors) = body.mirror_urls {
+        profile.mirror_urls = normalize_mirror_urls(&body.url, mirrors);
+    } else if url_changed {
+        // 未提供备用 URL 时保留原有列表，但需按新的主 URL 重新去重（新主 URL 可能原本是备用 URL）
+        let mirrors = std::mem::take(&mut profile.mirror_urls);
+        profile.mirror_urls = normalize_mirror_urls(&body.url, mirrors);
+    }
+    if url_changed {
+        // 地址变化后旧的缓存校验信息不再可信
+        profile.fetch_cache = None;
+    }
     profile.name = body.name;
     profile.url = Some(body.url);
+    if let Some(options) = fetch_options {
+        profile.fetch_options = options;
+    }
+    if let Some(schedule) = schedule {
+        profile.schedule = schedule;
+    }
 
     let updated = profile.clone();
 
     if let Err(err) = save_app_config(&state.data_root, config) {
         tracing::error!("{err}");
+        // 保存失败时撤销内存中的修改，保持与 app.json 一致
+        if let Some(profile) = config.profiles.iter_mut().find(|p| p.id == original.id) {
+            *profile = original;
+        }
         return Json(ApiResponse {
             code: "config_save_failed".to_string(),
             message: err,
@@ -229,6 +769,11 @@ pub async fn delete_subscription(
                     .map(|p| p.id.clone());
             }
 
+            config
+                .combined_subscription
+                .subscription_ids
+                .retain(|sid| sid != &id);
+
             if let Err(err) = save_app_config(&state.data_root, config) {
                 tracing::error!("{err}");
                 return Json(ApiResponse {
@@ -256,11 +801,13 @@ pub async fn delete_subscription(
     }
 
     // 删除订阅后，尝试基于最新配置重新生成 merged.yaml 并通知内核重载。
-    let _ = crate::user_profiles::generate_merged_config(&state.data_root)
-        .map_err(|err| {
+    let issues = crate::user_profiles::generate_merged_config(&state.data_root).await
+        .err()
+        .and_then(|err| {
             tracing::error!("failed to generate merged config after subscription delete: {err}");
+            err.issues_data()
         });
-    // 生成失败不会影响本次删除操作的返回，仅记录错误；重载结果通过事件总线上报。
+    // 生成失败不会影响本次删除操作的返回，仅记录错误并随响应返回校验问题；重载结果通过事件总线上报。
     let _ = config_manager::reload_core_if_running(
         ConfigChangeReason::SubscriptionDeleted,
     )
@@ -269,7 +816,7 @@ pub async fn delete_subscription(
     Json(ApiResponse {
         code: "ok".to_string(),
         message: "deleted".to_string(),
-        data: Some(serde_json::json!({})),
+        data: Some(issues.unwrap_or_else(|| serde_json::json!({}))),
     })
 }
 
@@ -317,12 +864,12 @@ pub async fn activate_subscription(
     }
 
     // 激活订阅后基于新配置重新生成 merged.yaml，并尝试通知内核重载。
-    if let Err(err) = crate::user_profiles::generate_merged_config(&state.data_root) {
+    if let Err(err) = crate::user_profiles::generate_merged_config(&state.data_root).await {
         tracing::error!("failed to generate merged config after subscription activate: {err}");
         return Json(ApiResponse {
-            code: "config_merge_failed".to_string(),
-            message: err,
-            data: None,
+            code: err.code().to_string(),
+            message: err.to_string(),
+            data: err.issues_data(),
         });
     }
 
@@ -338,15 +885,356 @@ pub async fn activate_subscription(
     })
 }
 
-pub async fn fetch_subscription(
-    Path(id): Path<String>,
+pub async fn get_combined_subscription() -> Json<ApiResponse<CombinedSubscriptionDto>> {
+    let cfg = crate::get_app_config_snapshot();
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "success".to_string(),
+        data: Some(to_combined_dto(&cfg)),
+    })
+}
+
+/// 更新多订阅合并配置，并基于新配置重新生成 merged.yaml。
+pub async fn update_combined_subscription(
+    Json(body): Json<CombinedSubscriptionDto>,
 ) -> Json<ApiResponse<serde_json::Value>> {
-    use std::fs;
+    let state = app_state();
+
+    let mut ids: Vec<String> = Vec::with_capacity(body.subscription_ids.len());
+    for id in body.subscription_ids {
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+
+    let result = with_app_config_mut(|config: &mut AppConfig| {
+        if let Some(missing) = ids.iter().find(|id| {
+            !config
+                .profiles
+                .iter()
+                .any(|p| matches!(p.profile_type, ProfileType::Remote) && &p.id == *id)
+        }) {
+            return Err(missing.clone());
+        }
+
+        let previous = std::mem::replace(
+            &mut config.combined_subscription,
+            CombinedSubscriptionConfig {
+                enabled: body.enabled,
+                subscription_ids: ids.clone(),
+            },
+        );
+        Ok((to_combined_dto(config), previous))
+    });
+
+    let (dto, previous) = match result {
+        Ok(Ok(updated)) => updated,
+        Ok(Err(missing)) => {
+            return Json(ApiResponse {
+                code: "subscription_not_found".to_string(),
+                message: format!("subscription not found: {missing}"),
+                data: None,
+            });
+        }
+        Err(err) => {
+            tracing::error!("{err}");
+            return Json(ApiResponse {
+                code: "config_save_failed".to_string(),
+                message: err,
+                data: None,
+            });
+        }
+    };
+
+    // 合并失败时恢复原有的多订阅合并配置，拒绝本次修改
+    if let Err(err) = crate::user_profiles::generate_merged_config(&state.data_root).await {
+        tracing::error!("failed to generate merged config after combined subscription update: {err}");
+        if let Err(save_err) = with_app_config_mut(|config: &mut AppConfig| {
+            config.combined_subscription = previous;
+        }) {
+            tracing::error!("{save_err}");
+        }
+        return Json(ApiResponse {
+            code: err.code().to_string(),
+            message: err.to_string(),
+            data: err.issues_data(),
+        });
+    }
+
+    let _ = config_manager::reload_core_if_running(
+        ConfigChangeReason::CombinedSubscriptionChanged,
+    )
+    .await;
+
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "updated".to_string(),
+        data: serde_json::to_value(dto).ok(),
+    })
+}
+
+fn record_fetch_failure(id: &str, status: &str) {
+    let _ = with_app_config_mut(|config: &mut AppConfig| {
+        if let Some(profile) = config
+            .profiles
+            .iter_mut()
+            .find(|p| matches!(p.profile_type, ProfileType::Remote) && p.id == id)
+        {
+            profile.last_fetch_status = Some(status.to_string());
+        }
+    });
+}
+
+/// 拉取成功但随后的合并或内核测试被拒绝时调用：丢弃缓存校验信息并记为 `merge_failed`，
+/// 下一次拉取不会因 304 / 内容未变化而跳过重新合并。
+fn record_merge_failure(ids: &[&str]) {
+    let _ = with_app_config_mut(|config: &mut AppConfig| {
+        for profile in config.profiles.iter_mut().filter(|p| {
+            matches!(p.profile_type, ProfileType::Remote) && ids.contains(&p.id.as_str())
+        }) {
+            profile.fetch_cache = None;
+            profile.last_fetch_status = Some(MERGE_FAILED_STATUS.to_string());
+        }
+    });
+}
+
+/// 拉取后合并失败的订阅的 `last_fetch_status`。
+const MERGE_FAILED_STATUS: &str = "merge_failed";
+
+/// 识别并转换拉取到的订阅内容，同时做基本的合法性检查：
+/// 大小上限、根节点为对象、至少包含一个节点或 proxy-provider、节点均有名称与类型。
+fn prepare_subscription_content(body: &str) -> Result<ConvertedSubscription, String> {
+    if body.len() > MAX_SUBSCRIPTION_BYTES {
+        return Err(format!(
+            "subscription is too large ({} bytes, limit {MAX_SUBSCRIPTION_BYTES})",
+            body.len()
+        ));
+    }
+
+    let converted = crate::convert::convert_subscription(body)?;
+    if converted.content.len() > MAX_SUBSCRIPTION_BYTES {
+        return Err(format!(
+            "converted subscription is too large ({} bytes, limit {MAX_SUBSCRIPTION_BYTES})",
+            converted.content.len()
+        ));
+    }
+
+    let value: serde_yaml::Value = serde_yaml::from_str(&converted.content)
+        .map_err(|err| format!("invalid subscription yaml: {err}"))?;
+    let Some(root) = value.as_mapping() else {
+        return Err("subscription root must be a mapping".to_string());
+    };
+
+    let proxies = root
+        .get("proxies")
+        .and_then(|v| v.as_sequence())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let has_providers = root
+        .get("proxy-providers")
+        .and_then(|v| v.as_mapping())
+        .is_some_and(|m| !m.is_empty());
+    if proxies.is_empty() && !has_providers {
+        return Err("subscription contains no proxies or proxy-providers".to_string());
+    }
+
+    if let Some(idx) = proxies.iter().position(|proxy| {
+        proxy.get("name").and_then(|v| v.as_str()).is_none()
+            || proxy.get("type").and_then(|v| v.as_str()).is_none()
+    }) {
+        return Err(format!("proxies[{idx}] has no name or type"));
+    }
+
+    Ok(converted)
+}
+
+/// 拉取失败：`status` 写入 `last_fetch_status`，`code` / `message` 返回给前端。
+struct SubscriptionFetchError {
+    status: &'static str,
+    code: &'static str,
+    message: String,
+    /// 是否值得对同一 URL 重试（网络错误、5xx、429）
+    retryable: bool,
+}
+
+impl SubscriptionFetchError {
+    fn request(message: String, retryable: bool) -> Self {
+        SubscriptionFetchError {
+            status: "request_failed",
+            code: "subscription_fetch_failed",
+            message,
+            retryable,
+        }
+    }
+
+    fn invalid_content(message: String) -> Self {
+        SubscriptionFetchError {
+            status: "invalid_content",
+            code: "subscription_invalid_content",
+            message,
+            retryable: false,
+        }
+    }
+
+    fn into_response(self) -> Json<ApiResponse<serde_json::Value>> {
+        Json(ApiResponse {
+            code: self.code.to_string(),
+            message: self.message,
+            data: None,
+        })
+    }
+}
+
+/// 单个 URL 的一次拉取结果。
+enum FetchedBody {
+    /// 服务端返回 304，本地内容仍是最新
+    NotModified(SubscriptionHeaders),
+    Content {
+        converted: ConvertedSubscription,
+        headers: SubscriptionHeaders,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+/// 第 `attempt`（从 1 开始）次失败后的等待时长：指数退避并叠加最多一半的随机抖动。
+fn retry_delay(attempt: u32) -> std::time::Duration {
+    use rand_core::{OsRng, RngCore};
+
+    let base = FETCH_RETRY_BASE_MS.saturating_mul(1 << (attempt - 1).min(6));
+    let jitter = OsRng.next_u64() % (base / 2 + 1);
+    std::time::Duration::from_millis(base + jitter)
+}
+
+/// 分块读取响应正文，累计超过 `limit` 字节即停止读取：分块传输或压缩的响应没有
+/// `Content-Length`，不能整体读入内存后再检查大小。
+async fn read_limited_body(
+    mut resp: reqwest::Response,
+    limit: usize,
+) -> Result<String, SubscriptionFetchError> {
+    let mut body = Vec::new();
+    loop {
+        let chunk = resp.chunk().await.map_err(|err| SubscriptionFetchError {
+            status: "body_read_failed",
+            code: "subscription_fetch_failed",
+            message: format!("failed to read response body: {err}"),
+            retryable: true,
+        })?;
+        let Some(chunk) = chunk else {
+            break;
+        };
+        if body.len() + chunk.len() > limit {
+            return Err(SubscriptionFetchError::invalid_content(format!(
+                "subscription is too large (more than {limit} bytes)"
+            )));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+async fn fetch_once(
+    options: &SubscriptionFetchOptions,
+    url: &str,
+    cache: Option<&SubscriptionFetchCache>,
+) -> Result<FetchedBody, SubscriptionFetchError> {
+    let mut request = options
+        .build_request(url)
+        .map_err(|err| SubscriptionFetchError::request(err, false))?;
+    if let Some(cache) = cache {
+        if let Some(etag) = cache.etag.as_deref() {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = cache.last_modified.as_deref() {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let resp = request.send().await.map_err(|err| {
+        SubscriptionFetchError::request(format!("failed to send request: {err}"), true)
+    })?;
+    if resp.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(FetchedBody::NotModified(SubscriptionHeaders::from_headers(
+            resp.headers(),
+        )));
+    }
+    let status = resp.status();
+    let resp = resp.error_for_status().map_err(|err| {
+        let retryable = status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+        SubscriptionFetchError::request(format!("request failed: {err}"), retryable)
+    })?;
+    if let Some(len) = resp
+        .content_length()
+        .filter(|len| *len > MAX_SUBSCRIPTION_BYTES as u64)
+    {
+        return Err(SubscriptionFetchError::invalid_content(format!(
+            "subscription is too large ({len} bytes, limit {MAX_SUBSCRIPTION_BYTES})"
+        )));
+    }
 
+    let header = |name: reqwest::header::HeaderName| {
+        resp.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let etag = header(reqwest::header::ETAG);
+    let last_modified = header(reqwest::header::LAST_MODIFIED);
+    let headers = SubscriptionHeaders::from_headers(resp.headers());
+    let text = read_limited_body(resp, MAX_SUBSCRIPTION_BYTES).await?;
+
+    // 识别格式并校验内容后才替换 subscription.yaml：错误页面、验证码页面等
+    // 即使以 HTTP 200 返回，也不会覆盖上一份可用的订阅。
+    let converted = prepare_subscription_content(&text).map_err(|err| {
+        SubscriptionFetchError::invalid_content(format!("invalid subscription content: {err}"))
+    })?;
+    Ok(FetchedBody::Content {
+        converted,
+        headers,
+        etag,
+        last_modified,
+    })
+}
+
+/// 依次尝试主 URL 与各备用 URL，每个 URL 对可重试的错误按指数退避重试。
+/// 返回成功的 URL 与结果；全部失败时返回最后一个错误。
+async fn fetch_from_urls(
+    options: &SubscriptionFetchOptions,
+    urls: &[String],
+    cache: Option<&SubscriptionFetchCache>,
+) -> Result<(String, FetchedBody), SubscriptionFetchError> {
+    let mut last_err = SubscriptionFetchError::request("no subscription url".to_string(), false);
+    for url in urls {
+        // 缓存校验信息只对产生它的 URL 有效
+        let cache = cache.filter(|cache| cache.url == *url);
+        for attempt in 1..=FETCH_ATTEMPTS_PER_URL {
+            match fetch_once(options, url, cache).await {
+                Ok(body) => return Ok((url.clone(), body)),
+                Err(err) => {
+                    tracing::warn!(
+                        "subscription fetch from {url} failed (attempt {attempt}/{FETCH_ATTEMPTS_PER_URL}): {}",
+                        err.message
+                    );
+                    let retry = err.retryable && attempt < FETCH_ATTEMPTS_PER_URL;
+                    last_err = err;
+                    if !retry {
+                        break;
+                    }
+                    tokio::time::sleep(retry_delay(attempt)).await;
+                }
+            }
+        }
+    }
+    Err(last_err)
+}
+
+/// 拉取订阅并在内容有变化时替换本地 `subscription.yaml`，同时更新拉取状态等元信息。
+/// 返回内容是否发生变化；不负责重新生成 merged.yaml 与重载内核。
+async fn download_subscription(id: &str) -> Result<bool, Json<ApiResponse<serde_json::Value>>> {
     let state = app_state();
 
-    // 第一步：从配置中读取订阅 URL
-    let url = {
+    // 第一步：从配置中读取订阅 URL、备用 URL 与拉取选项
+    let (urls, fetch_options, cache, merge_pending) = {
         let guard = state
             .app_config
             .read()
@@ -358,149 +1246,199 @@ pub async fn fetch_subscription(
             .iter()
             .find(|p| matches!(p.profile_type, ProfileType::Remote) && p.id == id)
         else {
-            return Json(ApiResponse {
+            return Err(Json(ApiResponse {
                 code: "subscription_not_found".to_string(),
                 message: "subscription not found".to_string(),
                 data: None,
-            });
+            }));
         };
 
         let Some(url) = profile.url.clone() else {
-            return Json(ApiResponse {
+            return Err(Json(ApiResponse {
                 code: "subscription_url_missing".to_string(),
                 message: "subscription url is missing".to_string(),
                 data: None,
-            });
+            }));
         };
 
-        url
+        let mut urls = vec![url];
+        urls.extend(profile.mirror_urls.iter().cloned());
+        (
+            urls,
+            profile.fetch_options.clone(),
+            profile.fetch_cache.clone(),
+            profile.last_fetch_status.as_deref() == Some(MERGE_FAILED_STATUS),
+        )
     };
 
-    let content = match state.http_client.get(&url).send().await {
-        Ok(resp) => match resp.error_for_status() {
-            Ok(ok) => match ok.text().await {
-                Ok(text) => text,
-                Err(err) => {
-                    let msg = format!("failed to read response body: {err}");
-                    tracing::error!("{msg}");
-                    let _ = with_app_config_mut(|config: &mut AppConfig| {
-                        if let Some(profile) = config
-                            .profiles
-                            .iter_mut()
-                            .find(|p| matches!(p.profile_type, ProfileType::Remote) && p.id == id)
-                        {
-                            profile.last_fetch_status = Some("body_read_failed".to_string());
-                        }
-                    });
-                    return Json(ApiResponse {
-                        code: "subscription_fetch_failed".to_string(),
-                        message: msg,
-                        data: None,
-                    });
-                }
-            },
-            Err(err) => {
-                let msg = format!("request failed: {err}");
-                tracing::error!("{msg}");
-                let _ = with_app_config_mut(|config: &mut AppConfig| {
-                    if let Some(profile) = config
-                        .profiles
-                        .iter_mut()
-                        .find(|p| matches!(p.profile_type, ProfileType::Remote) && p.id == id)
-                    {
-                        profile.last_fetch_status = Some("request_failed".to_string());
-                    }
-                });
-                return Json(ApiResponse {
-                    code: "subscription_fetch_failed".to_string(),
-                    message: msg,
-                    data: None,
-                });
-            }
-        },
+    if fetch_options.via_core && !crate::core::core_running_status(&state.data_root).0 {
+        let msg = "core is not running, cannot fetch via core proxy".to_string();
+        tracing::error!("{msg}");
+        record_fetch_failure(id, "core_not_running");
+        return Err(Json(ApiResponse {
+            code: "subscription_fetch_failed".to_string(),
+            message: msg,
+            data: None,
+        }));
+    }
+
+    let dir = subscription_dir(&state.data_root, id);
+    let subscription_path = dir.join("subscription.yaml");
+    let previous = std::fs::read_to_string(&subscription_path).ok();
+    // 本地文件缺失时不发送条件请求，避免 304 后无内容可用
+    let cache = cache.filter(|_| previous.is_some());
+
+    let (fetched_url, body) = match fetch_from_urls(&fetch_options, &urls, cache.as_ref()).await {
+        Ok(result) => result,
         Err(err) => {
-            let msg = format!("failed to send request: {err}");
-            tracing::error!("{msg}");
-            let _ = with_app_config_mut(|config: &mut AppConfig| {
-                if let Some(profile) = config
-                    .profiles
-                    .iter_mut()
-                    .find(|p| matches!(p.profile_type, ProfileType::Remote) && p.id == id)
-                {
-                    profile.last_fetch_status = Some("request_failed".to_string());
-                }
-            });
-            return Json(ApiResponse {
-                code: "subscription_fetch_failed".to_string(),
-                message: msg,
-                data: None,
-            });
+            tracing::error!("{}", err.message);
+            record_fetch_failure(id, err.status);
+            return Err(err.into_response());
         }
     };
 
-    let dir = subscription_dir(&state.data_root, &id);
-    if let Err(err) = fs::create_dir_all(&dir) {
-        let msg = format!(
-            "failed to create subscription directory {}: {err}",
-            dir.display()
-        );
-        tracing::error!("{msg}");
-        let _ = with_app_config_mut(|config: &mut AppConfig| {
-            if let Some(profile) = config
-                .profiles
-                .iter_mut()
-                .find(|p| matches!(p.profile_type, ProfileType::Remote) && p.id == id)
-            {
-                profile.last_fetch_status = Some("write_failed".to_string());
-            }
-        });
-        return Json(ApiResponse {
-            code: "subscription_save_failed".to_string(),
-            message: msg,
-            data: None,
-        });
+    let (headers, new_cache, converted) = match body {
+        FetchedBody::NotModified(headers) => (headers, cache, None),
+        FetchedBody::Content {
+            converted,
+            headers,
+            etag,
+            last_modified,
+        } => {
+            let new_cache = SubscriptionFetchCache {
+                url: fetched_url.clone(),
+                etag,
+                last_modified,
+            };
+            (headers, Some(new_cache), Some(converted))
+        }
     };
 
-    let subscription_path = dir.join("subscription.yaml");
+    let changed = converted
+        .as_ref()
+        .is_some_and(|converted| previous.as_deref() != Some(converted.content.as_str()));
+    if let Some(converted) = converted.as_ref().filter(|_| changed) {
+        if converted.skipped > 0 {
+            tracing::warn!(
+                "subscription {id}: skipped {} unsupported entries while converting {:?}",
+                converted.skipped,
+                converted.format
+            );
+        }
 
-    if let Err(err) = fs::write(&subscription_path, &content) {
-        let msg = format!("failed to write {}: {err}", subscription_path.display());
-        tracing::error!("{msg}");
-        let _ = with_app_config_mut(|config: &mut AppConfig| {
-            if let Some(profile) = config
-                .profiles
-                .iter_mut()
-                .find(|p| matches!(p.profile_type, ProfileType::Remote) && p.id == id)
-            {
-                profile.last_fetch_status = Some("write_failed".to_string());
-            }
-        });
-        return Json(ApiResponse {
-            code: "subscription_save_failed".to_string(),
-            message: msg,
-            data: None,
-        });
+        if let Err(err) = std::fs::create_dir_all(&dir) {
+            let msg = format!(
+                "failed to create subscription directory {}: {err}",
+                dir.display()
+            );
+            tracing::error!("{msg}");
+            record_fetch_failure(id, "write_failed");
+            return Err(Json(ApiResponse {
+                code: "subscription_save_failed".to_string(),
+                message: msg,
+                data: None,
+            }));
+        };
+
+        if let Err(err) = write_file_atomically(&subscription_path, &converted.content) {
+            let msg = format!("failed to write {}: {err}", subscription_path.display());
+            tracing::error!("{msg}");
+            record_fetch_failure(id, "write_failed");
+            return Err(Json(ApiResponse {
+                code: "subscription_save_failed".to_string(),
+                message: msg,
+                data: None,
+            }));
+        }
+        crate::history::record_revision_or_warn(
+            &state.data_root,
+            RevisionKind::Subscription,
+            Some(id),
+            &converted.content,
+        );
     }
 
-    // 更新订阅拉取状态
-    let _ = with_app_config_mut(|config: &mut AppConfig| {
-        if let Some(profile) = config
+    // 更新订阅拉取状态及响应头中的流量 / 到期等信息；304 响应未携带的信息保留原值
+    let not_modified = converted.is_none();
+    let result = with_app_config_mut(|config: &mut AppConfig| {
+        let profile = config
             .profiles
             .iter_mut()
-            .find(|p| matches!(p.profile_type, ProfileType::Remote) && p.id == id)
-        {
-            profile.last_fetch_status = Some("ok".to_string());
-            profile.last_fetch_time = Some(current_timestamp());
+            .find(|p| matches!(p.profile_type, ProfileType::Remote) && p.id == id)?;
+        profile.last_fetch_status = Some("ok".to_string());
+        profile.last_fetch_time = Some(current_timestamp());
+        profile.fetch_cache = new_cache;
+        if let Some(converted) = converted.as_ref() {
+            profile.subscription_format = Some(converted.format);
+        }
+        if !not_modified || headers.userinfo.is_some() {
+            profile.userinfo = headers.userinfo.clone();
         }
+        if !not_modified || headers.update_interval_hours.is_some() {
+            profile.update_interval_hours = headers.update_interval_hours;
+        }
+        if !not_modified || headers.filename.is_some() {
+            profile.remote_filename = headers.filename.clone();
+        }
+
+        // 流量 / 到期告警只在状态变化（出现新的告警）时广播
+        let now = current_timestamp().parse::<u64>().unwrap_or_default();
+        let current = profile
+            .userinfo
+            .as_ref()
+            .map(|info| usage_warnings(info, now))
+            .unwrap_or_default();
+        let raised = newly_raised_warnings(&profile.usage_warnings, &current);
+        profile.usage_warnings = current;
+        let info = profile.userinfo.clone()?;
+        Some((profile.name.clone(), info, raised))
     });
+    if let Ok(Some((name, info, raised))) = result {
+        emit_usage_warnings(id, &name, &info, raised);
+    }
+
+    if fetched_url != urls[0] {
+        tracing::info!("subscription {id} fetched from mirror {fetched_url}");
+    }
+    // 上一次拉取后的合并失败时，即使内容未变化也需要重新合并
+    Ok(changed || merge_pending)
+}
+
+pub async fn fetch_subscription(
+    Path(id): Path<String>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let state = app_state();
+
+    let changed = match download_subscription(&id).await {
+        Ok(changed) => changed,
+        Err(resp) => return resp,
+    };
+
+    // 内容未变化（304 或与本地一致）时无需重新合并与重载
+    if !changed {
+        return Json(ApiResponse {
+            code: "ok".to_string(),
+            message: "not_modified".to_string(),
+            data: Some(serde_json::json!({ "changed": false })),
+        });
+    }
+    // 未被使用的订阅（非活跃且未参与合并）不影响 merged.yaml
+    if !subscription_in_use(&crate::get_app_config_snapshot(), &id) {
+        return Json(ApiResponse {
+            code: "ok".to_string(),
+            message: "fetched".to_string(),
+            data: Some(serde_json::json!({ "changed": true })),
+        });
+    }
 
     // 拉取订阅成功后尝试生成 merged.yaml
-    if let Err(err) = crate::user_profiles::generate_merged_config(&state.data_root) {
+    if let Err(err) = crate::user_profiles::generate_merged_config(&state.data_root).await {
         tracing::error!("failed to generate merged config after fetch: {err}");
+        record_merge_failure(&[id.as_str()]);
         return Json(ApiResponse {
-            code: "config_merge_failed".to_string(),
-            message: err,
-            data: None,
+            code: err.code().to_string(),
+            message: err.to_string(),
+            data: err.issues_data(),
         });
     }
 
@@ -513,40 +1451,411 @@ pub async fn fetch_subscription(
     Json(ApiResponse {
         code: "ok".to_string(),
         message: "fetched".to_string(),
-        data: Some(serde_json::json!({})),
+        data: Some(serde_json::json!({ "changed": true })),
     })
 }
 
-/// 自动更新当前“活跃订阅”的订阅内容。
-///
-/// - 若未设置活跃订阅，则返回 Err("skipped:...")，由调度器记录为跳过状态。
-/// - 其余错误则用于调度器记录为失败状态。
-pub async fn auto_update_subscriptions() -> Result<(), String> {
-    use axum::extract::Path;
+/// 订阅是否正在被使用：活跃订阅，或启用多订阅合并时参与合并的订阅。
+/// 只有这些订阅的内容变化才需要重新生成 merged.yaml。
+fn subscription_in_use(config: &AppConfig, id: &str) -> bool {
+    config.active_subscription_id.as_deref() == Some(id)
+        || (config.combined_subscription.enabled
+            && config
+                .combined_subscription
+                .subscription_ids
+                .iter()
+                .any(|sid| sid == id))
+}
 
+/// 依次拉取给定订阅；任一正在使用的订阅内容发生变化时，统一重新生成一次
+/// merged.yaml 并重载内核。任一订阅失败时返回汇总的错误信息。
+async fn refresh_subscriptions(ids: &[String]) -> Result<(), String> {
     let state = app_state();
 
-    let active_id = {
-        let guard = state
-            .app_config
-            .read()
-            .expect("app config rwlock poisoned");
-        let config: &AppConfig = &guard;
-
-        let Some(active_id) = config.active_subscription_id.clone() else {
-            return Err("skipped:no_active_subscription".to_string());
-        };
+    let mut failures = Vec::new();
+    let mut in_use_changed = Vec::new();
+    for id in ids {
+        match download_subscription(id).await {
+            Ok(changed) => {
+                if changed && subscription_in_use(&crate::get_app_config_snapshot(), id) {
+                    in_use_changed.push(id.as_str());
+                }
+            }
+            Err(Json(resp)) => failures.push(format!("{id}: {}", resp.message)),
+        }
+    }
 
-        active_id
-    };
+    if !in_use_changed.is_empty() {
+        if let Err(err) = crate::user_profiles::generate_merged_config(&state.data_root).await {
+            record_merge_failure(&in_use_changed);
+            return Err(format!("failed to generate merged config: {err}"));
+        }
+        let _ = config_manager::reload_core_if_running(ConfigChangeReason::SubscriptionFetched)
+            .await;
+    }
 
-    let Json(resp) = fetch_subscription(Path(active_id)).await;
-    if resp.code == "ok" {
+    if failures.is_empty() {
         Ok(())
     } else {
         Err(format!(
-            "subscription_auto_update_failed: {}",
-            resp.message
+            "{}/{} subscriptions failed: {}",
+            failures.len(),
+            ids.len(),
+            failures.join("; ")
         ))
     }
 }
+
+/// 由全局订阅自动更新任务（`subscription_auto_update`）触发：刷新所有
+/// 自动更新方式为 `global` 的远程订阅。
+///
+/// - 若没有需要更新的订阅，则返回 Err("skipped:...")，由调度器记录为跳过状态。
+/// - 其余错误则用于调度器记录为失败状态。
+pub async fn auto_update_subscriptions() -> Result<(), String> {
+    let ids: Vec<String> = crate::get_app_config_snapshot()
+        .profiles
+        .iter()
+        .filter(|p| matches!(p.profile_type, ProfileType::Remote) && p.url.is_some())
+        .filter(|p| p.schedule == SubscriptionSchedule::Global)
+        .map(|p| p.id.clone())
+        .collect();
+    if ids.is_empty() {
+        return Err("skipped:no_subscriptions".to_string());
+    }
+
+    refresh_subscriptions(&ids)
+        .await
+        .map_err(|err| format!("subscription_auto_update_failed: {err}"))
+}
+
+/// 各订阅最近一次按计划自动拉取的时间（Unix 秒），用于限制失败后的重试频率。
+static LAST_SCHEDULED_ATTEMPTS: std::sync::Mutex<BTreeMap<String, u64>> =
+    std::sync::Mutex::new(BTreeMap::new());
+
+/// 判断订阅按自身的自动更新方式是否已到刷新时间。
+///
+/// `global` 方式的订阅由全局定时任务统一刷新，仅当全局任务启用且服务端下发了
+/// `profile-update-interval` 时按该间隔额外刷新。
+fn schedule_due(profile: &ProfileMeta, global_enabled: bool, now: u64) -> bool {
+    let last = profile
+        .last_fetch_time
+        .as_deref()
+        .and_then(|t| t.parse::<u64>().ok())
+        .unwrap_or_default();
+    let interval_elapsed = |hours: u32| last.saturating_add(u64::from(hours) * 3600) <= now;
+    match &profile.schedule {
+        SubscriptionSchedule::Global => {
+            global_enabled && profile.update_interval_hours.is_some_and(interval_elapsed)
+        }
+        SubscriptionSchedule::Interval { hours } => interval_elapsed(*hours),
+        SubscriptionSchedule::Cron { cron } => {
+            crate::scheduler::cron_due_since(cron, last, now).unwrap_or_else(|err| {
+                tracing::warn!("subscription {} has invalid cron '{cron}': {err}", profile.id);
+                false
+            })
+        }
+        SubscriptionSchedule::Disabled => false,
+    }
+}
+
+/// 刷新所有按自身计划已到期的订阅，返回本次尝试拉取的订阅数量。
+pub async fn refresh_due_subscriptions() -> Result<usize, String> {
+    let now = current_timestamp().parse::<u64>().unwrap_or_default();
+    let config = crate::get_app_config_snapshot();
+    let global_enabled = config
+        .subscription_auto_update
+        .as_ref()
+        .is_some_and(|task| task.enabled);
+
+    let ids: Vec<String> = {
+        let mut attempts = LAST_SCHEDULED_ATTEMPTS
+            .lock()
+            .expect("subscription schedule mutex poisoned");
+        let ids: Vec<String> = config
+            .profiles
+            .iter()
+            .filter(|p| matches!(p.profile_type, ProfileType::Remote) && p.url.is_some())
+            .filter(|p| schedule_due(p, global_enabled, now))
+            // 拉取失败时 last_fetch_time 不会更新，限制重试频率
+            .filter(|p| {
+                attempts
+                    .get(&p.id)
+                    .is_none_or(|last| now.saturating_sub(*last) >= SCHEDULE_RETRY_SECS)
+            })
+            .map(|p| p.id.clone())
+            .collect();
+        attempts.retain(|id, _| config.profiles.iter().any(|p| p.id == *id));
+        for id in &ids {
+            attempts.insert(id.clone(), now);
+        }
+        ids
+    };
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    refresh_subscriptions(&ids).await?;
+    Ok(ids.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        FETCH_RETRY_BASE_MS, MASKED_HEADER_VALUE, SubscriptionFetchOptions, SubscriptionSchedule, SubscriptionUserinfo,
+        UsageWarning, newly_raised_warnings, normalize_mirror_urls, parse_content_disposition_filename, parse_userinfo,
+        prepare_subscription_content, retry_delay, schedule_due, usage_warnings,
+    };
+    use crate::{ProfileMeta, ProfileType};
+
+    #[test]
+    fn userinfo_and_filename_headers_are_parsed() {
+        let info = parse_userinfo(
+            "upload=1073741824; download=8589934592; total=10737418240; expire=1800000000",
+        )
+        .unwrap();
+        assert_eq!(info.used(), 9663676416);
+        assert_eq!(info.total, 10737418240);
+        assert_eq!(info.expire, Some(1800000000));
+        assert_eq!(parse_userinfo("upload=1.5E3;expire=0").unwrap().upload, 1500);
+        assert_eq!(parse_userinfo("upload=1.5E3;expire=0").unwrap().expire, None);
+        assert!(parse_userinfo("garbage").is_none());
+
+        assert_eq!(
+            parse_content_disposition_filename(
+                "attachment; filename=\"plain.yaml\"; filename*=UTF-8''%E6%9C%BA%E5%9C%BA"
+            )
+            .as_deref(),
+            Some("机场")
+        );
+        assert_eq!(
+            parse_content_disposition_filename("attachment; filename=\"plain.yaml\"").as_deref(),
+            Some("plain.yaml")
+        );
+        assert!(parse_content_disposition_filename("inline").is_none());
+    }
+
+    #[test]
+    fn usage_warnings_cover_quota_and_expiry() {
+        let now = 1_000_000;
+        let info = |used: u64, total: u64, expire: Option<u64>| SubscriptionUserinfo {
+            upload: 0,
+            download: used,
+            total,
+            expire,
+        };
+        assert!(usage_warnings(&info(10, 100, Some(now + 30 * 86400)), now).is_empty());
+        assert_eq!(usage_warnings(&info(95, 100, None), now), vec![UsageWarning::QuotaLow]);
+        assert_eq!(
+            usage_warnings(&info(100, 100, Some(now + 3600)), now),
+            vec![UsageWarning::QuotaExhausted, UsageWarning::Expiring]
+        );
+        assert_eq!(usage_warnings(&info(5, 0, Some(now)), now), vec![UsageWarning::Expired]);
+
+        // 已告警过的状态不再重复提醒，只有新出现的告警才会广播
+        let low = [UsageWarning::QuotaLow];
+        assert!(newly_raised_warnings(&low, &low).is_empty());
+        assert_eq!(
+            newly_raised_warnings(&low, &[UsageWarning::QuotaExhausted, UsageWarning::Expiring]),
+            vec![UsageWarning::QuotaExhausted, UsageWarning::Expiring]
+        );
+        let resolved = [UsageWarning::QuotaLow, UsageWarning::Expiring];
+        assert!(newly_raised_warnings(&resolved, &low).is_empty());
+        assert_eq!(newly_raised_warnings(&[], &low), low.to_vec());
+    }
+
+    #[test]
+    fn fetch_options_are_normalized_and_validated() {
+        let options = SubscriptionFetchOptions {
+            user_agent: Some("  ".to_string()),
+            headers: [(" Authorization ".to_string(), " Bearer abc ".to_string())].into(),
+            timeout_secs: Some(30),
+            ..Default::default()
+        }
+        .normalized()
+        .unwrap();
+        assert_eq!(options.user_agent, None);
+        assert_eq!(options.headers.get("authorization").map(String::as_str), Some("Bearer abc"));
+        assert!(options.needs_dedicated_client());
+        assert!(!SubscriptionFetchOptions::default().needs_dedicated_client());
+
+        let invalid = |options: SubscriptionFetchOptions| options.normalized().is_err();
+        assert!(invalid(SubscriptionFetchOptions {
+            timeout_secs: Some(0),
+            ..Default::default()
+        }));
+        assert!(invalid(SubscriptionFetchOptions {
+            headers: [("bad header".to_string(), "x".to_string())].into(),
+            ..Default::default()
+        }));
+        assert!(invalid(SubscriptionFetchOptions {
+            headers: [("Host".to_string(), "example.com".to_string())].into(),
+            ..Default::default()
+        }));
+        assert!(invalid(SubscriptionFetchOptions {
+            user_agent: Some("ua\nx".to_string()),
+            ..Default::default()
+        }));
+
+        // 返回给接口的请求头取值被掩码；原样提交回来时恢复已保存的值
+        let masked = options.masked();
+        assert_eq!(
+            masked.headers.get("authorization").map(String::as_str),
+            Some(MASKED_HEADER_VALUE)
+        );
+        let mut resubmitted = SubscriptionFetchOptions {
+            headers: [
+                ("authorization".to_string(), MASKED_HEADER_VALUE.to_string()),
+                ("x-token".to_string(), "new".to_string()),
+            ]
+            .into(),
+            ..masked
+        };
+        resubmitted.restore_masked_headers(&options).unwrap();
+        assert_eq!(
+            resubmitted.headers.get("authorization").map(String::as_str),
+            Some("Bearer abc")
+        );
+        assert_eq!(resubmitted.headers.get("x-token").map(String::as_str), Some("new"));
+
+        let mut unknown = SubscriptionFetchOptions {
+            headers: [("x-other".to_string(), MASKED_HEADER_VALUE.to_string())].into(),
+            ..Default::default()
+        };
+        assert!(unknown.restore_masked_headers(&options).is_err());
+    }
+
+    #[test]
+    fn mirror_urls_are_deduplicated_and_retry_delay_backs_off() {
+        let mirrors = normalize_mirror_urls(
+            "https://a.example/sub",
+            vec![
+                " https://b.example/sub ".to_string(),
+                String::new(),
+                "https://a.example/sub".to_string(),
+                "https://b.example/sub".to_string(),
+            ],
+        );
+        assert_eq!(mirrors, vec!["https://b.example/sub".to_string()]);
+        // 主 URL 改为原来的备用 URL 后，重新去重会将其从备用列表中移除
+        assert!(normalize_mirror_urls("https://b.example/sub", mirrors).is_empty());
+
+        for attempt in 1..=3 {
+            let base = FETCH_RETRY_BASE_MS << (attempt - 1);
+            let delay = retry_delay(attempt).as_millis() as u64;
+            assert!((base..=base + base / 2).contains(&delay), "attempt {attempt}: {delay}ms");
+        }
+    }
+
+    #[test]
+    fn subscriptions_are_due_according_to_their_schedule() {
+        let now = 1_700_000_000;
+        let profile = |schedule: SubscriptionSchedule, last: u64, hint: Option<u32>| ProfileMeta {
+            id: "sub".to_string(),
+            name: "sub".to_string(),
+            profile_type: ProfileType::Remote,
+            path: "subscriptions/sub/subscription.yaml".to_string(),
+            url: Some("https://example.com/sub".to_string()),
+            last_fetch_time: Some(last.to_string()),
+            last_fetch_status: None,
+            last_modified_time: None,
+            subscription_format: None,
+            userinfo: None,
+            update_interval_hours: hint,
+            remote_filename: None,
+            mirror_urls: Vec::new(),
+            fetch_cache: None,
+            fetch_options: Default::default(),
+            schedule,
+            usage_warnings: Vec::new(),
+        };
+
+        let interval = SubscriptionSchedule::Interval { hours: 6 };
+        assert!(schedule_due(&profile(interval.clone(), now - 6 * 3600, None), false, now));
+        assert!(!schedule_due(&profile(interval, now - 3600, None), false, now));
+
+        let cron = SubscriptionSchedule::Cron { cron: "* * * * *".to_string() };
+        assert!(schedule_due(&profile(cron.clone(), now - 120, None), false, now));
+        assert!(!schedule_due(&profile(cron, now, None), false, now));
+
+        // global 方式仅在全局任务启用时参考服务端下发的更新间隔
+        let hinted = profile(SubscriptionSchedule::Global, now - 13 * 3600, Some(12));
+        assert!(schedule_due(&hinted, true, now));
+        assert!(!schedule_due(&hinted, false, now));
+        assert!(!schedule_due(&profile(SubscriptionSchedule::Global, 0, None), true, now));
+        assert!(!schedule_due(&profile(SubscriptionSchedule::Disabled, 0, None), true, now));
+
+        assert!(SubscriptionSchedule::Interval { hours: 0 }.normalized().is_err());
+        assert!(SubscriptionSchedule::Cron { cron: "0 3 *".to_string() }.normalized().is_err());
+        assert_eq!(
+            SubscriptionSchedule::Cron { cron: " 0 */6 * * * ".to_string() }.normalized(),
+            Ok(SubscriptionSchedule::Cron { cron: "0 */6 * * *".to_string() })
+        );
+    }
+
+    #[test]
+    fn fetched_content_is_rejected_unless_it_has_proxies() {
+        assert!(prepare_subscription_content("<html><body>captcha</body></html>").is_err());
+        assert!(prepare_subscription_content("rules:\n  - MATCH,DIRECT\n").is_err());
+        assert!(prepare_subscription_content("proxies: []\n").is_err());
+        assert!(prepare_subscription_content("proxies:\n  - {server: 1.1.1.1}\n").is_err());
+
+        let yaml = "proxies:\n  - {name: a, type: ss, server: 1.1.1.1, port: 1}\n";
+        assert_eq!(prepare_subscription_content(yaml).unwrap().content, yaml);
+        assert!(
+            prepare_subscription_content("proxy-providers:\n  p: {type: http, url: http://x}\n")
+                .is_ok()
+        );
+
+        let huge = format!("{yaml}#{}", "x".repeat(super::MAX_SUBSCRIPTION_BYTES));
+        assert!(
+            matches!(prepare_subscription_content(&huge), Err(err) if err.contains("too large"))
+        );
+    }
+
+    /// 以分块传输（不带 `Content-Length`）返回正文的一次性 HTTP 服务；
+    /// `total` 为 None 时持续发送直到客户端断开。
+    async fn serve_chunked(total: Option<usize>) -> std::net::SocketAddr {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            let head = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+            if socket.write_all(head).await.is_err() {
+                return;
+            }
+            let chunk = vec![b'x'; 16 * 1024];
+            let mut sent = 0;
+            while total.is_none_or(|total| sent < total) {
+                let frame = [format!("{:x}\r\n", chunk.len()).as_bytes(), &chunk, b"\r\n"].concat();
+                if socket.write_all(&frame).await.is_err() {
+                    return;
+                }
+                sent += chunk.len();
+            }
+            let _ = socket.write_all(b"0\r\n\r\n").await;
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn body_without_content_length_is_capped_while_reading() {
+        let addr = serve_chunked(None).await;
+        let resp = reqwest::get(format!("http://{addr}/")).await.unwrap();
+        assert!(resp.content_length().is_none());
+        match super::read_limited_body(resp, 256 * 1024).await {
+            Err(err) => assert_eq!(err.status, "invalid_content"),
+            Ok(body) => panic!("expected size limit error, read {} bytes", body.len()),
+        }
+
+        let addr = serve_chunked(Some(64 * 1024)).await;
+        let resp = reqwest::get(format!("http://{addr}/")).await.unwrap();
+        let Ok(body) = super::read_limited_body(resp, 256 * 1024).await else {
+            panic!("body within the limit should be read");
+        };
+        assert_eq!(body.len(), 64 * 1024);
+    }
+}
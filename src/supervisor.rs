@@ -0,0 +1,590 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::{app_state, current_timestamp};
+use crate::AppEvent;
+
+/// 崩溃事件中附带的 stderr 尾部行数上限。
+const STDERR_TAIL_LINES: usize = 20;
+/// 单行 stderr 保留的字节上限，避免异常输出撑大内存。
+const STDERR_LINE_LIMIT: usize = 1024;
+
+/// 内核退出后的自动重启策略。
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum RestartPolicy {
+    /// 任何非主动停止的退出都重启（包括退出码为 0）
+    Always,
+    /// 仅在异常退出（非 0 退出码或被信号终止）时重启
+    #[default]
+    OnFailure,
+    /// 从不自动重启
+    Never,
+}
+
+/// 内核守护配置（`app.json` 中的 `core_supervisor`）。
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct CoreSupervisorConfig {
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
+    /// 首次重启前的等待时长（秒），之后每次翻倍
+    #[serde(default = "default_backoff_initial_secs")]
+    pub backoff_initial_secs: u64,
+    /// 重启等待时长上限（秒）
+    #[serde(default = "default_backoff_max_secs")]
+    pub backoff_max_secs: u64,
+    /// 在 `crash_loop_window_secs` 内最多允许的崩溃次数，超过后停止自动重启
+    #[serde(default = "default_crash_loop_max_failures")]
+    pub crash_loop_max_failures: u32,
+    #[serde(default = "default_crash_loop_window_secs")]
+    pub crash_loop_window_secs: u64,
+    /// 停止内核时发送 SIGTERM 后等待其退出的时长（秒），超时后发送 SIGKILL
+    #[serde(default = "default_stop_grace_secs")]
+    pub stop_grace_secs: u64,
+}
+
+fn default_backoff_initial_secs() -> u64 {
+    1
+}
+
+fn default_backoff_max_secs() -> u64 {
+    60
+}
+
+fn default_crash_loop_max_failures() -> u32 {
+    5
+}
+
+fn default_crash_loop_window_secs() -> u64 {
+    300
+}
+
+fn default_stop_grace_secs() -> u64 {
+    10
+}
+
+/// `stop_grace_secs` 允许的最大值。
+const MAX_STOP_GRACE_SECS: u64 = 120;
+
+impl Default for CoreSupervisorConfig {
+    fn default() -> Self {
+        CoreSupervisorConfig {
+            restart_policy: RestartPolicy::default(),
+            backoff_initial_secs: default_backoff_initial_secs(),
+            backoff_max_secs: default_backoff_max_secs(),
+            crash_loop_max_failures: default_crash_loop_max_failures(),
+            crash_loop_window_secs: default_crash_loop_window_secs(),
+            stop_grace_secs: default_stop_grace_secs(),
+        }
+    }
+}
+
+impl CoreSupervisorConfig {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.backoff_initial_secs == 0 {
+            return Err("backoff_initial_secs must be at least 1".to_string());
+        }
+        if self.backoff_max_secs < self.backoff_initial_secs {
+            return Err("backoff_max_secs must not be less than backoff_initial_secs".to_string());
+        }
+        if self.crash_loop_max_failures == 0 {
+            return Err("crash_loop_max_failures must be at least 1".to_string());
+        }
+        if self.crash_loop_window_secs == 0 {
+            return Err("crash_loop_window_secs must be at least 1".to_string());
+        }
+        if !(1..=MAX_STOP_GRACE_SECS).contains(&self.stop_grace_secs) {
+            return Err(format!(
+                "stop_grace_secs must be between 1 and {MAX_STOP_GRACE_SECS}"
+            ));
+        }
+        Ok(())
+    }
+
+    /// 窗口内第 `failures` 次崩溃后的重启等待时长。
+    fn backoff(&self, failures: usize) -> Duration {
+        let exp = failures.saturating_sub(1).min(16) as u32;
+        let secs = self
+            .backoff_initial_secs
+            .saturating_mul(1 << exp)
+            .min(self.backoff_max_secs);
+        Duration::from_secs(secs)
+    }
+}
+
+/// 内核进程的一次退出信息。
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub(crate) struct CoreExitInfo {
+    pub pid: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// 终止进程的信号编号（仅 unix）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+    pub timestamp: String,
+}
+
+impl CoreExitInfo {
+    pub(crate) fn from_status(pid: u32, status: Option<std::process::ExitStatus>) -> Self {
+        #[cfg(target_family = "unix")]
+        let signal = {
+            use std::os::unix::process::ExitStatusExt;
+            status.and_then(|s| s.signal())
+        };
+        #[cfg(not(target_family = "unix"))]
+        let signal = None;
+
+        CoreExitInfo {
+            pid,
+            exit_code: status.and_then(|s| s.code()),
+            signal,
+            timestamp: current_timestamp(),
+        }
+    }
+
+    fn is_failure(&self) -> bool {
+        self.exit_code != Some(0)
+    }
+}
+
+/// 崩溃后的处理决定。
+#[derive(Debug, PartialEq)]
+pub(crate) enum RestartDecision {
+    Restart(Duration),
+    /// 策略不允许重启
+    NotRestarting,
+    /// 窗口内崩溃次数过多，停止自动重启
+    CrashLoop,
+}
+
+#[derive(Default)]
+struct SupervisorState {
+    /// 自 camofy 启动以来由守护自动重启内核的次数
+    restart_count: u32,
+    /// 自 camofy 启动以来内核非主动退出的次数
+    crash_count: u32,
+    /// 窗口内的崩溃时间（Unix 秒）
+    recent_failures: VecDeque<u64>,
+    crash_loop_detected: bool,
+    last_exit: Option<CoreExitInfo>,
+    next_restart_at: Option<u64>,
+    /// 用户主动停止的内核 pid，用于区分主动停止与崩溃
+    stop_requested_pid: Option<u32>,
+    stderr_tail: VecDeque<String>,
+    /// 尚未以换行结束的 stderr 片段
+    stderr_partial: String,
+}
+
+impl SupervisorState {
+    /// 记录一次失败并根据策略给出处理决定。
+    fn register_failure(
+        &mut self,
+        config: &CoreSupervisorConfig,
+        exit: Option<&CoreExitInfo>,
+        now: u64,
+    ) -> RestartDecision {
+        self.crash_count = self.crash_count.saturating_add(1);
+        self.record_recent_failure(config, now);
+
+        let wants_restart = match config.restart_policy {
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => exit.is_none_or(CoreExitInfo::is_failure),
+            RestartPolicy::Never => false,
+        };
+        if !wants_restart {
+            return RestartDecision::NotRestarting;
+        }
+        self.restart_decision(config)
+    }
+
+    /// 记录一次健康检查发现的持续故障：与崩溃计入同一窗口，按相同的退避与崩溃循环规则决定。
+    /// 是否重启由健康检查的 `auto_restart` 控制，不受 `restart_policy` 影响。
+    fn register_health_failure(&mut self, config: &CoreSupervisorConfig, now: u64) -> RestartDecision {
+        self.record_recent_failure(config, now);
+        self.restart_decision(config)
+    }
+
+    fn record_recent_failure(&mut self, config: &CoreSupervisorConfig, now: u64) {
+        let window_start = now.saturating_sub(config.crash_loop_window_secs);
+        self.recent_failures.retain(|t| *t > window_start);
+        self.recent_failures.push_back(now);
+    }
+
+    fn restart_decision(&mut self, config: &CoreSupervisorConfig) -> RestartDecision {
+        if self.recent_failures.len() > config.crash_loop_max_failures as usize {
+            self.crash_loop_detected = true;
+            return RestartDecision::CrashLoop;
+        }
+        RestartDecision::Restart(config.backoff(self.recent_failures.len()))
+    }
+
+    fn push_stderr(&mut self, chunk: &str) {
+        self.stderr_partial.push_str(chunk);
+        while let Some(pos) = self.stderr_partial.find('\n') {
+            let line: String = self.stderr_partial.drain(..=pos).collect();
+            let mut line = line.trim_end().to_string();
+            if line.is_empty() {
+                continue;
+            }
+            if line.len() > STDERR_LINE_LIMIT {
+                let mut end = STDERR_LINE_LIMIT;
+                while !line.is_char_boundary(end) {
+                    end -= 1;
+                }
+                line.truncate(end);
+            }
+            if self.stderr_tail.len() == STDERR_TAIL_LINES {
+                self.stderr_tail.pop_front();
+            }
+            self.stderr_tail.push_back(line);
+        }
+        if self.stderr_partial.len() > STDERR_LINE_LIMIT {
+            self.stderr_partial.clear();
+        }
+    }
+
+    fn take_stderr_tail(&mut self) -> Vec<String> {
+        let mut lines: Vec<String> = self.stderr_tail.drain(..).collect();
+        let partial = std::mem::take(&mut self.stderr_partial);
+        if !partial.trim().is_empty() {
+            lines.push(partial.trim_end().to_string());
+        }
+        lines
+    }
+}
+
+static SUPERVISOR: Mutex<Option<SupervisorState>> = Mutex::new(None);
+
+fn with_state<T>(f: impl FnOnce(&mut SupervisorState) -> T) -> T {
+    let mut guard = SUPERVISOR.lock().expect("supervisor mutex poisoned");
+    f(guard.get_or_insert_with(SupervisorState::default))
+}
+
+fn now_secs() -> u64 {
+    current_timestamp().parse::<u64>().unwrap_or_default()
+}
+
+/// `GET /api/core/status` 中返回的守护状态。
+#[derive(Serialize)]
+pub struct SupervisorStatusDto {
+    restart_policy: RestartPolicy,
+    restart_count: u32,
+    crash_count: u32,
+    /// 当前崩溃窗口内的崩溃次数
+    recent_failures: usize,
+    crash_loop_detected: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_exit: Option<CoreExitInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_restart_at: Option<String>,
+}
+
+pub(crate) fn status_snapshot() -> SupervisorStatusDto {
+    let config = crate::get_app_config_snapshot().core_supervisor;
+    let now = now_secs();
+    with_state(|state| SupervisorStatusDto {
+        restart_policy: config.restart_policy,
+        restart_count: state.restart_count,
+        crash_count: state.crash_count,
+        recent_failures: state
+            .recent_failures
+            .iter()
+            .filter(|t| **t > now.saturating_sub(config.crash_loop_window_secs))
+            .count(),
+        crash_loop_detected: state.crash_loop_detected,
+        last_exit: state.last_exit.clone(),
+        next_restart_at: state.next_restart_at.map(|t| t.to_string()),
+    })
+}
+
+/// 是否已安排自动重启（正在退避等待或正在拉起内核）。
+pub(crate) fn restart_pending() -> bool {
+    with_state(|state| state.next_restart_at.is_some())
+}
+
+/// 最近一次内核崩溃的时间（Unix 秒）。
+pub(crate) fn last_failure_time() -> Option<u64> {
+    with_state(|state| state.recent_failures.back().copied())
+}
+
+/// 由内核 stderr 管道调用，保留最近的若干行用于崩溃事件。
+pub(crate) fn record_core_stderr(chunk: &[u8]) {
+    let chunk = String::from_utf8_lossy(chunk);
+    with_state(|state| state.push_stderr(&chunk));
+}
+
+/// 用户主动停止内核前调用，使随后的进程退出不被视为崩溃。
+pub(crate) fn mark_stop_requested(pid: u32) {
+    with_state(|state| {
+        state.stop_requested_pid = Some(pid);
+        state.next_restart_at = None;
+    });
+}
+
+/// 用户主动（重新）启动内核时调用：清除崩溃窗口与崩溃循环标记。
+pub(crate) fn reset_after_manual_start() {
+    with_state(|state| {
+        state.recent_failures.clear();
+        state.crash_loop_detected = false;
+        state.next_restart_at = None;
+        state.stderr_tail.clear();
+        state.stderr_partial.clear();
+    });
+}
+
+/// 健康检查判定内核持续故障、准备重启时调用，返回重启前的退避时长或崩溃循环决定。
+pub(crate) fn register_health_failure() -> RestartDecision {
+    let config = crate::get_app_config_snapshot().core_supervisor;
+    let now = now_secs();
+    with_state(|state| {
+        let decision = state.register_health_failure(&config, now);
+        state.next_restart_at = match &decision {
+            RestartDecision::Restart(delay) => Some(now + delay.as_secs()),
+            _ => None,
+        };
+        decision
+    })
+}
+
+/// 健康检查的重启结束（或被放弃）后清除计划重启时间。
+pub(crate) fn clear_next_restart() {
+    with_state(|state| state.next_restart_at = None);
+}
+
+/// 内核进程退出时由 watcher 调用，返回该退出是否为用户主动停止。
+pub(crate) fn is_requested_stop(pid: u32) -> bool {
+    with_state(|state| {
+        if state.stop_requested_pid == Some(pid) {
+            state.stop_requested_pid = None;
+            true
+        } else {
+            false
+        }
+    })
+}
+
+/// 处理内核的一次非主动退出：广播 `AppEvent::CoreCrashed`，并按策略安排重启。
+pub(crate) fn handle_core_crash(exit: CoreExitInfo) {
+    let config = crate::get_app_config_snapshot().core_supervisor;
+    let now = now_secs();
+
+    let (decision, stderr_tail, restart_count) = with_state(|state| {
+        state.last_exit = Some(exit.clone());
+        let decision = state.register_failure(&config, Some(&exit), now);
+        state.next_restart_at = match &decision {
+            RestartDecision::Restart(delay) => Some(now + delay.as_secs()),
+            _ => None,
+        };
+        (decision, state.take_stderr_tail(), state.restart_count)
+    });
+
+    match &decision {
+        RestartDecision::Restart(delay) => tracing::warn!(
+            "core (pid {}) exited unexpectedly (code {:?}, signal {:?}); restarting in {:?}",
+            exit.pid,
+            exit.exit_code,
+            exit.signal,
+            delay
+        ),
+        RestartDecision::NotRestarting => tracing::warn!(
+            "core (pid {}) exited unexpectedly (code {:?}, signal {:?}); restart policy {:?} does not restart",
+            exit.pid,
+            exit.exit_code,
+            exit.signal,
+            config.restart_policy
+        ),
+        RestartDecision::CrashLoop => tracing::error!(
+            "core crashed more than {} times within {}s; automatic restart stopped",
+            config.crash_loop_max_failures,
+            config.crash_loop_window_secs
+        ),
+    }
+
+    let event = AppEvent::CoreCrashed {
+        pid: exit.pid,
+        exit_code: exit.exit_code,
+        signal: exit.signal,
+        stderr_tail,
+        restart_scheduled: matches!(decision, RestartDecision::Restart(_)),
+        restart_in_secs: match &decision {
+            RestartDecision::Restart(delay) => Some(delay.as_secs()),
+            _ => None,
+        },
+        crash_loop: decision == RestartDecision::CrashLoop,
+        restart_count,
+        timestamp: exit.timestamp.clone(),
+    };
+    if let Err(err) = app_state().events_tx.send(event) {
+        tracing::debug!("failed to broadcast AppEvent::CoreCrashed: {err}");
+    }
+
+    if let RestartDecision::Restart(delay) = decision {
+        tokio::spawn(restart_after(delay));
+    }
+}
+
+/// 等待退避时长后重新拉起内核；启动失败同样计入崩溃窗口并继续退避。
+async fn restart_after(mut delay: Duration) {
+    loop {
+        tokio::time::sleep(delay).await;
+
+        // 等待期间用户已停止内核（清除自动启动标记）或手动启动了内核时放弃本次重启
+        if !crate::get_app_config_snapshot().core_auto_start {
+            tracing::info!("core was stopped during restart backoff; skip automatic restart");
+            with_state(|state| state.next_restart_at = None);
+            return;
+        }
+        if crate::core::core_running_status(&app_state().data_root).0 {
+            with_state(|state| state.next_restart_at = None);
+            return;
+        }
+
+        // 直接使用当前 merged.yaml，与崩溃前保持一致
+        let axum::Json(resp) = crate::core::start_core_with_current_config().await;
+        if resp.code == "ok" {
+            with_state(|state| {
+                state.restart_count = state.restart_count.saturating_add(1);
+                state.next_restart_at = None;
+            });
+            tracing::info!("core restarted by supervisor");
+            return;
+        }
+
+        tracing::error!(
+            "supervisor failed to restart core: code={}, message={}",
+            resp.code,
+            resp.message
+        );
+        let config = crate::get_app_config_snapshot().core_supervisor;
+        let now = now_secs();
+        let decision = with_state(|state| {
+            let decision = state.register_failure(&config, None, now);
+            state.next_restart_at = match &decision {
+                RestartDecision::Restart(delay) => Some(now + delay.as_secs()),
+                _ => None,
+            };
+            decision
+        });
+        match decision {
+            RestartDecision::Restart(next) => delay = next,
+            _ => {
+                tracing::error!("giving up restarting core after repeated failures");
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CoreExitInfo, CoreSupervisorConfig, RestartDecision, RestartPolicy, SupervisorState};
+    use std::time::Duration;
+
+    fn exit(code: Option<i32>) -> CoreExitInfo {
+        CoreExitInfo {
+            pid: 42,
+            exit_code: code,
+            signal: None,
+            timestamp: "0".to_string(),
+        }
+    }
+
+    #[test]
+    fn backoff_grows_until_crash_loop_is_detected() {
+        let config = CoreSupervisorConfig {
+            backoff_initial_secs: 2,
+            backoff_max_secs: 5,
+            crash_loop_max_failures: 3,
+            crash_loop_window_secs: 60,
+            ..Default::default()
+        };
+        let mut state = SupervisorState::default();
+        let crash = exit(Some(2));
+
+        let decisions: Vec<RestartDecision> = (0..4)
+            .map(|i| state.register_failure(&config, Some(&crash), 1000 + i))
+            .collect();
+        assert_eq!(
+            decisions,
+            vec![
+                RestartDecision::Restart(Duration::from_secs(2)),
+                RestartDecision::Restart(Duration::from_secs(4)),
+                RestartDecision::Restart(Duration::from_secs(5)),
+                RestartDecision::CrashLoop,
+            ]
+        );
+        assert!(state.crash_loop_detected);
+
+        // 崩溃窗口过后重新从初始退避开始
+        let mut state = SupervisorState::default();
+        state.register_failure(&config, Some(&crash), 1000);
+        assert_eq!(
+            state.register_failure(&config, Some(&crash), 1100),
+            RestartDecision::Restart(Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn health_failures_share_the_crash_loop_window() {
+        let config = CoreSupervisorConfig {
+            backoff_initial_secs: 2,
+            backoff_max_secs: 5,
+            crash_loop_max_failures: 2,
+            crash_loop_window_secs: 60,
+            restart_policy: RestartPolicy::Never,
+            ..Default::default()
+        };
+        let mut state = SupervisorState::default();
+        // 健康检查触发的重启不受 restart_policy 影响，但同样退避
+        assert_eq!(
+            state.register_health_failure(&config, 1000),
+            RestartDecision::Restart(Duration::from_secs(2))
+        );
+        state.register_failure(&config, Some(&exit(Some(2))), 1010);
+        assert_eq!(state.register_health_failure(&config, 1020), RestartDecision::CrashLoop);
+        assert!(state.crash_loop_detected);
+        assert_eq!(state.crash_count, 1);
+    }
+
+    #[test]
+    fn restart_policy_decides_on_exit_status() {
+        let mut state = SupervisorState::default();
+        let on_failure = CoreSupervisorConfig::default();
+        assert_eq!(
+            state.register_failure(&on_failure, Some(&exit(Some(0))), 1000),
+            RestartDecision::NotRestarting
+        );
+        let always = CoreSupervisorConfig {
+            restart_policy: RestartPolicy::Always,
+            ..Default::default()
+        };
+        assert!(matches!(
+            state.register_failure(&always, Some(&exit(Some(0))), 1000),
+            RestartDecision::Restart(_)
+        ));
+        let never = CoreSupervisorConfig {
+            restart_policy: RestartPolicy::Never,
+            ..Default::default()
+        };
+        assert_eq!(
+            state.register_failure(&never, Some(&exit(None)), 1000),
+            RestartDecision::NotRestarting
+        );
+    }
+
+    #[test]
+    fn stderr_tail_keeps_last_lines() {
+        let mut state = SupervisorState::default();
+        for i in 0..30 {
+            state.push_stderr(&format!("line {i}\n"));
+        }
+        state.push_stderr("panic: boom");
+        let tail = state.take_stderr_tail();
+        assert_eq!(tail.len(), 21);
+        assert_eq!(tail.first().map(String::as_str), Some("line 10"));
+        assert_eq!(tail.last().map(String::as_str), Some("panic: boom"));
+    }
+}
@@ -0,0 +1,534 @@
+//! 内置配置层的编辑：`defaults.yaml` 与 system.yaml 覆盖项。
+//!
+//! - `defaults.yaml` 位于 `<DATA_ROOT>/config/defaults.yaml`，首次合并时由内置模板生成，
+//!   可通过接口整体读取 / 替换；
+//! - system.yaml 内嵌于二进制、在合并时最后叠加以强制关键字段，只允许通过
+//!   `<DATA_ROOT>/config/system-overrides.yaml` 调整白名单内的少量字段（DNS 监听端口、tun 栈、
+//!   控制接口 socket 路径），其余字段保持强制。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::app::{app_state, write_file_atomically};
+use crate::validate::{ValidationIssue, summarize_issues};
+use crate::{ApiResponse, ConfigChangeReason, config_manager};
+
+const SYSTEM_YAML: &str = include_str!("./system.yaml");
+
+/// 允许通过 system-overrides.yaml 调整的 system.yaml 字段（以 `.` 分隔的键路径）。
+pub(crate) const SYSTEM_OVERRIDE_KEYS: &[&str] = &[
+    "tun.stack",
+    "dns.listen",
+    "external-controller-unix",
+];
+
+const TUN_STACKS: &[&str] = &["system", "gvisor", "mixed"];
+
+fn system_overrides_path(root: &Path) -> PathBuf {
+    root.join("config").join("system-overrides.yaml")
+}
+
+/// 将覆盖文件展开为 (键路径, 值) 列表。
+fn flatten(prefix: &str, value: &Value, out: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Mapping(map) if !map.is_empty() => {
+            for (key, child) in map {
+                let key = match key {
+                    Value::String(s) => s.clone(),
+                    other => serde_yaml::to_string(other)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                };
+                let path = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten(&path, child, out);
+            }
+        }
+        _ if prefix.is_empty() => {}
+        other => out.push((prefix.to_string(), other.clone())),
+    }
+}
+
+fn port_in_range(port: Option<u64>) -> bool {
+    port.is_some_and(|p| (1..=65535).contains(&p))
+}
+
+fn check_override_value(path: &str, value: &Value) -> Option<String> {
+    match path {
+        "tun.stack" => match value.as_str() {
+            Some(stack) if TUN_STACKS.contains(&stack) => None,
+            _ => Some(format!("tun.stack must be one of {}", TUN_STACKS.join(", "))),
+        },
+        "dns.listen" => {
+            let port = value
+                .as_str()
+                .and_then(|s| s.rsplit_once(':'))
+                .and_then(|(_, port)| port.parse::<u64>().ok());
+            (!port_in_range(port))
+                .then(|| "dns.listen must look like 0.0.0.0:1053".to_string())
+        }
+        "external-controller-unix" => match value.as_str() {
+            Some(p) if p.starts_with('/') && !p.ends_with('/') => None,
+            _ => Some("external-controller-unix must be an absolute socket path".to_string()),
+        },
+        _ => None,
+    }
+}
+
+/// 解析并校验 system 覆盖内容，返回白名单内的 (键路径, 值) 列表。
+pub(crate) fn parse_system_overrides(
+    content: &str,
+) -> Result<Vec<(String, Value)>, Vec<ValidationIssue>> {
+    let value: Value = if content.trim().is_empty() {
+        Value::Null
+    } else {
+        serde_yaml::from_str(content).map_err(|err| {
+            vec![ValidationIssue {
+                code: "invalid_yaml",
+                path: String::new(),
+                message: format!("invalid system overrides yaml: {err}"),
+            }]
+        })?
+    };
+
+    if !matches!(value, Value::Null | Value::Mapping(_)) {
+        return Err(vec![ValidationIssue {
+            code: "invalid_yaml",
+            path: String::new(),
+            message: "system overrides root must be a mapping".to_string(),
+        }]);
+    }
+
+    let mut entries = Vec::new();
+    flatten("", &value, &mut entries);
+
+    let mut issues = Vec::new();
+    for (path, value) in &entries {
+        if !SYSTEM_OVERRIDE_KEYS.contains(&path.as_str()) {
+            issues.push(ValidationIssue {
+                code: "override_not_allowed",
+                path: path.clone(),
+                message: format!(
+                    "'{path}' cannot be overridden, allowed keys: {}",
+                    SYSTEM_OVERRIDE_KEYS.join(", ")
+                ),
+            });
+        } else if let Some(message) = check_override_value(path, value) {
+            issues.push(ValidationIssue {
+                code: "invalid_override_value",
+                path: path.clone(),
+                message,
+            });
+        }
+    }
+
+    if issues.is_empty() {
+        Ok(entries)
+    } else {
+        Err(issues)
+    }
+}
+
+fn set_path(root: &mut Value, path: &str, value: Value) {
+    let mut current = root;
+    let mut segments = path.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        if !current.is_mapping() {
+            *current = Value::Mapping(Mapping::new());
+        }
+        let Some(map) = current.as_mapping_mut() else {
+            return;
+        };
+        let key = Value::String(segment.to_string());
+        if segments.peek().is_none() {
+            map.insert(key, value);
+            return;
+        }
+        current = map.entry(key).or_insert(Value::Null);
+    }
+}
+
+fn read_system_overrides(root: &Path) -> Result<String, String> {
+    let path = system_overrides_path(root);
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(content),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(err) => Err(format!("failed to read {}: {err}", path.display())),
+    }
+}
+
+/// 合并时使用的系统层：内嵌 system.yaml 叠加 system-overrides.yaml 中的白名单字段。
+///
+/// 覆盖文件被手工改坏时仅记录日志并忽略，避免系统层本身无法生成。
+pub(crate) fn load_system_value(root: &Path) -> Result<Value, String> {
+    let mut system: Value = serde_yaml::from_str(SYSTEM_YAML)
+        .map_err(|err| format!("failed to parse embedded system.yaml: {err}"))?;
+
+    match read_system_overrides(root).map(|content| parse_system_overrides(&content)) {
+        Ok(Ok(entries)) => {
+            for (path, value) in entries {
+                set_path(&mut system, &path, value);
+            }
+        }
+        Ok(Err(issues)) => {
+            tracing::warn!("ignore {}", summarize_issues("system overrides", &issues));
+        }
+        Err(err) => tracing::warn!("{err}"),
+    }
+
+    Ok(system)
+}
+
+/// 系统层中 camofy 自身需要频繁读取的字段。
+#[derive(Clone)]
+struct SystemValues {
+    controller_socket: String,
+    dns_listen_port: u16,
+}
+
+/// 按数据目录缓存的派生值；覆盖文件或 merged.yaml 被替换时通过 `invalidate_cached_values` 清空。
+static SYSTEM_VALUES_CACHE: Mutex<Option<(PathBuf, SystemValues)>> = Mutex::new(None);
+static MIXED_PORT_CACHE: Mutex<Option<(PathBuf, u16)>> = Mutex::new(None);
+
+/// defaults.yaml 模板中的 `mixed-port`，merged.yaml 尚未生成时使用。
+const DEFAULT_MIXED_PORT: u16 = 7897;
+
+/// 清空缓存的系统层字段与生效端口，在 system-overrides.yaml / merged.yaml 被替换后调用。
+pub(crate) fn invalidate_cached_values() {
+    *SYSTEM_VALUES_CACHE.lock().expect("system values cache poisoned") = None;
+    *MIXED_PORT_CACHE.lock().expect("mixed port cache poisoned") = None;
+}
+
+fn system_values(root: &Path) -> SystemValues {
+    let mut cache = SYSTEM_VALUES_CACHE.lock().expect("system values cache poisoned");
+    if let Some((cached_root, values)) = cache.as_ref()
+        && cached_root == root
+    {
+        return values.clone();
+    }
+
+    let system = load_system_value(root).unwrap_or(Value::Null);
+    let values = SystemValues {
+        controller_socket: system
+            .get("external-controller-unix")
+            .and_then(|p| p.as_str())
+            .unwrap_or("/tmp/verge/clash-verge-service.sock")
+            .to_string(),
+        dns_listen_port: system
+            .get("dns")
+            .and_then(|d| d.get("listen"))
+            .and_then(|l| l.as_str())
+            .and_then(|s| s.rsplit_once(':'))
+            .and_then(|(_, port)| port.parse().ok())
+            .unwrap_or(1053),
+    };
+    *cache = Some((root.to_path_buf(), values.clone()));
+    values
+}
+
+/// Mihomo 控制接口的 Unix Socket 路径（system.yaml 的 `external-controller-unix`）。
+pub(crate) fn controller_socket_path(root: &Path) -> String {
+    system_values(root).controller_socket
+}
+
+/// 内核 HTTP / SOCKS 混合代理端口：取当前 merged.yaml 中生效的 `mixed-port`
+/// （默认来自 defaults.yaml，可被订阅或用户 profile 覆盖）。
+pub(crate) fn mixed_port(root: &Path) -> u16 {
+    let mut cache = MIXED_PORT_CACHE.lock().expect("mixed port cache poisoned");
+    if let Some((cached_root, port)) = cache.as_ref()
+        && cached_root == root
+    {
+        return *port;
+    }
+
+    let merged_path = crate::user_profiles::merged_config_path(&root.to_path_buf());
+    let port = fs::read_to_string(&merged_path)
+        .ok()
+        .and_then(|content| serde_yaml::from_str::<Value>(&content).ok())
+        .and_then(|v| v.get("mixed-port").and_then(|p| p.as_u64()))
+        .and_then(|port| u16::try_from(port).ok())
+        .unwrap_or(DEFAULT_MIXED_PORT);
+    *cache = Some((root.to_path_buf(), port));
+    port
+}
+
+/// 内核 DNS 监听端口（system.yaml 的 `dns.listen`），用于 DNS 重定向规则。
+pub(crate) fn dns_listen_port(root: &Path) -> u16 {
+    system_values(root).dns_listen_port
+}
+
+/// 写入新的配置层文件并重新生成 merged.yaml；生成失败时恢复原文件内容。
+async fn replace_layer_file(
+    root: &PathBuf,
+    path: &Path,
+    content: &str,
+) -> Result<(), Json<ApiResponse<serde_json::Value>>> {
+    let previous = match fs::read_to_string(path) {
+        Ok(c) => Some(c),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => {
+            return Err(Json(ApiResponse {
+                code: "config_read_failed".to_string(),
+                message: format!("failed to read {}: {err}", path.display()),
+                data: None,
+            }));
+        }
+    };
+
+    if let Err(err) = write_file_atomically(path, content) {
+        tracing::error!("{err}");
+        return Err(Json(ApiResponse {
+            code: "config_write_failed".to_string(),
+            message: err,
+            data: None,
+        }));
+    }
+
+    if let Err(err) = crate::user_profiles::generate_merged_config(root).await {
+        tracing::error!(
+            "failed to generate merged config with new {}: {err}",
+            path.display()
+        );
+        let restored = match &previous {
+            Some(c) => write_file_atomically(path, c),
+            None => fs::remove_file(path).map_err(|e| e.to_string()),
+        };
+        if let Err(restore_err) = restored {
+            tracing::error!("failed to restore {}: {restore_err}", path.display());
+        }
+        invalidate_cached_values();
+        return Err(Json(ApiResponse {
+            code: err.code().to_string(),
+            message: err.to_string(),
+            data: err.issues_data(),
+        }));
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub struct DefaultsConfigDto {
+    pub content: String,
+    /// 磁盘上尚未生成 defaults.yaml，返回的是内置模板
+    pub is_builtin: bool,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateLayerContentRequest {
+    pub content: String,
+}
+
+pub async fn get_defaults_config() -> Json<ApiResponse<DefaultsConfigDto>> {
+    let state = app_state();
+    let path = crate::user_profiles::defaults_path(&state.data_root);
+
+    let (content, is_builtin) = match fs::read_to_string(&path) {
+        Ok(c) => (c, false),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            (crate::user_profiles::DEFAULTS_YAML.to_string(), true)
+        }
+        Err(err) => {
+            let msg = format!("failed to read {}: {err}", path.display());
+            tracing::error!("{msg}");
+            return Json(ApiResponse {
+                code: "config_read_failed".to_string(),
+                message: msg,
+                data: None,
+            });
+        }
+    };
+
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "success".to_string(),
+        data: Some(DefaultsConfigDto {
+            content,
+            is_builtin,
+        }),
+    })
+}
+
+pub async fn update_defaults_config(
+    Json(body): Json<UpdateLayerContentRequest>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let state = app_state();
+
+    let trimmed = body.content.trim();
+    if !trimmed.is_empty() {
+        match serde_yaml::from_str::<Value>(trimmed) {
+            Ok(Value::Mapping(_)) | Ok(Value::Null) => {}
+            Ok(_) => {
+                return Json(ApiResponse {
+                    code: "defaults_invalid_yaml".to_string(),
+                    message: "defaults.yaml root must be a mapping".to_string(),
+                    data: None,
+                });
+            }
+            Err(err) => {
+                return Json(ApiResponse {
+                    code: "defaults_invalid_yaml".to_string(),
+                    message: format!("invalid defaults yaml: {err}"),
+                    data: None,
+                });
+            }
+        }
+    }
+
+    let path = crate::user_profiles::defaults_path(&state.data_root);
+    if let Err(resp) = replace_layer_file(&state.data_root, &path, &body.content).await {
+        return resp;
+    }
+
+    let core_reload = config_manager::reload_core_if_running(ConfigChangeReason::DefaultsUpdated).await;
+
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "updated".to_string(),
+        data: Some(serde_json::json!({ "core_reload": core_reload })),
+    })
+}
+
+#[derive(Serialize)]
+pub struct SystemOverridesDto {
+    pub content: String,
+    pub allowed_keys: Vec<&'static str>,
+    /// 白名单字段的当前生效值（已叠加覆盖项）
+    pub effective: serde_json::Map<String, serde_json::Value>,
+}
+
+fn effective_override_values(root: &Path) -> serde_json::Map<String, serde_json::Value> {
+    let system = load_system_value(root).unwrap_or(Value::Null);
+    SYSTEM_OVERRIDE_KEYS
+        .iter()
+        .map(|key| {
+            let value = key
+                .split('.')
+                .try_fold(&system, |v, segment| v.get(segment))
+                .and_then(|v| serde_json::to_value(v).ok())
+                .unwrap_or(serde_json::Value::Null);
+            (key.to_string(), value)
+        })
+        .collect()
+}
+
+pub async fn get_system_overrides() -> Json<ApiResponse<SystemOverridesDto>> {
+    let state = app_state();
+
+    let content = match read_system_overrides(&state.data_root) {
+        Ok(c) => c,
+        Err(err) => {
+            tracing::error!("{err}");
+            return Json(ApiResponse {
+                code: "config_read_failed".to_string(),
+                message: err,
+                data: None,
+            });
+        }
+    };
+
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "success".to_string(),
+        data: Some(SystemOverridesDto {
+            content,
+            allowed_keys: SYSTEM_OVERRIDE_KEYS.to_vec(),
+            effective: effective_override_values(&state.data_root),
+        }),
+    })
+}
+
+/// 更新 system 覆盖项。
+///
+/// DNS 监听端口或控制接口 socket 路径变化时，仅重载配置无法让 DNS 重定向规则与控制通道
+/// 跟随变化，因此内核运行中会改为重启内核。
+pub async fn update_system_overrides(
+    Json(body): Json<UpdateLayerContentRequest>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let state = app_state();
+    let root = &state.data_root;
+
+    if let Err(issues) = parse_system_overrides(&body.content) {
+        return Json(ApiResponse {
+            code: "system_overrides_invalid".to_string(),
+            message: summarize_issues("system overrides", &issues),
+            data: Some(serde_json::json!({ "issues": issues })),
+        });
+    }
+
+    let before = (dns_listen_port(root), controller_socket_path(root));
+
+    let path = system_overrides_path(root);
+    if let Err(resp) = replace_layer_file(root, &path, &body.content).await {
+        return resp;
+    }
+
+    let after = (dns_listen_port(root), controller_socket_path(root));
+    let (running, _) = crate::core::core_running_status(root);
+
+    if running && before != after {
+        tracing::info!("system overrides changed dns listen / controller socket, restart core");
+        let Json(resp) = crate::core_async::restart_core_async().await;
+        return Json(ApiResponse {
+            code: resp.code,
+            message: resp.message,
+            data: Some(serde_json::json!({ "core_restart": true })),
+        });
+    }
+
+    let core_reload =
+        config_manager::reload_core_if_running(ConfigChangeReason::SystemOverridesUpdated).await;
+
+    Json(ApiResponse {
+        code: "ok".to_string(),
+        message: "updated".to_string(),
+        data: Some(serde_json::json!({ "core_reload": core_reload })),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_system_value, parse_system_overrides, system_overrides_path};
+
+    #[test]
+    fn overrides_apply_whitelisted_keys_only() {
+        let root = crate::test_support::TempRoot::new("system-overrides");
+        std::fs::create_dir_all(root.join("config")).unwrap();
+
+        // mixed-port 属于 defaults.yaml，不在 system 覆盖白名单内
+        let issues =
+            parse_system_overrides("mode: global\nmixed-port: 7898\ntun:\n  stack: lwip\n")
+                .unwrap_err();
+        let codes: Vec<_> = issues.iter().map(|i| (i.code, i.path.as_str())).collect();
+        assert_eq!(
+            codes,
+            vec![
+                ("override_not_allowed", "mode"),
+                ("override_not_allowed", "mixed-port"),
+                ("invalid_override_value", "tun.stack")
+            ]
+        );
+
+        std::fs::write(
+            system_overrides_path(&root),
+            "tun:\n  stack: mixed\ndns:\n  listen: 0.0.0.0:5353\n",
+        )
+        .unwrap();
+        let system = load_system_value(&root).unwrap();
+        assert_eq!(system["tun"]["stack"].as_str(), Some("mixed"));
+        // 同一对象下的其他字段仍保持强制值
+        assert_eq!(system["tun"]["auto-route"].as_bool(), Some(true));
+        assert_eq!(system["dns"]["listen"].as_str(), Some("0.0.0.0:5353"));
+        assert_eq!(super::dns_listen_port(&root), 5353);
+        assert_eq!(system["mode"].as_str(), Some("rule"));
+    }
+}
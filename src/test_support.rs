@@ -0,0 +1,38 @@
+//! 单元测试共用的辅助工具。
+
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_TEMP_ID: AtomicU64 = AtomicU64::new(0);
+
+/// 测试用的临时目录，离开作用域时连同其中的文件一并删除。
+pub(crate) struct TempRoot(PathBuf);
+
+impl TempRoot {
+    /// 在系统临时目录下创建 `camofy-test-<name>-<pid>-<序号>`，并发执行的测试互不冲突。
+    pub(crate) fn new(name: &str) -> Self {
+        let id = NEXT_TEMP_ID.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!(
+            "camofy-test-{name}-{}-{id}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        TempRoot(dir)
+    }
+}
+
+impl Deref for TempRoot {
+    type Target = PathBuf;
+
+    fn deref(&self) -> &PathBuf {
+        &self.0
+    }
+}
+
+impl Drop for TempRoot {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -6,13 +6,19 @@ use uuid::Uuid;
 
 use crate::app::{app_state, current_timestamp};
 use crate::{ApiResponse, AppConfig, ConfigChangeReason, ProfileMeta, ProfileType, config_manager};
-use crate::{load_app_config, save_app_config};
+use crate::{load_app_config, save_app_config, with_app_config_mut};
+use crate::history::RevisionKind;
+use crate::validate::{ValidationIssue, summarize_issues, validate_merged_config};
 
 #[derive(Serialize)]
 pub struct UserProfileSummary {
     pub id: String,
     pub name: String,
+    /// 是否已启用（位于用户 profile 链中）
     pub is_active: bool,
+    /// 在用户 profile 链中的位置（从 0 开始，越靠后优先级越高）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_index: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_modified_time: Option<String>,
 }
@@ -20,6 +26,8 @@ pub struct UserProfileSummary {
 #[derive(Serialize)]
 pub struct UserProfileListResponse {
     pub user_profiles: Vec<UserProfileSummary>,
+    /// 已启用的用户 profile id，按合并顺序排列
+    pub chain: Vec<String>,
 }
 
 #[derive(Serialize)]
@@ -28,6 +36,8 @@ pub struct UserProfileDetail {
     pub name: String,
     pub is_active: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub last_modified_time: Option<String>,
     pub content: String,
 }
@@ -45,6 +55,12 @@ pub struct UpdateUserProfileRequest {
     pub content: String,
 }
 
+#[derive(Deserialize)]
+pub struct UpdateUserProfileChainRequest {
+    /// 新的用户 profile 链（按合并顺序），未出现在列表中的 profile 视为禁用
+    pub ids: Vec<String>,
+}
+
 fn user_profile_path(root: &PathBuf, id: &str) -> PathBuf {
     let mut path = root.clone();
     path.push("config");
@@ -68,23 +84,28 @@ fn to_user_profile_summary(
         return None;
     }
 
-    let active_id = config.active_user_profile_id.as_deref();
+    let chain_index = chain_position(config, &profile.id);
 
     Some(UserProfileSummary {
         id: profile.id.clone(),
         name: profile.name.clone(),
-        is_active: active_id == Some(profile.id.as_str()),
+        is_active: chain_index.is_some(),
+        chain_index,
         last_modified_time: profile.last_modified_time.clone(),
     })
 }
 
+fn chain_position(config: &AppConfig, id: &str) -> Option<usize> {
+    config.active_user_profile_ids.iter().position(|p| p == id)
+}
+
 pub async fn get_user_profile(Path(id): Path<String>) -> Json<ApiResponse<UserProfileDetail>> {
     use std::fs;
 
     let state = app_state();
 
     // 从全局配置中获取 profile 元数据和活跃状态
-    let (profile_meta, active_id) = {
+    let (profile_meta, chain_index) = {
         let guard = state.app_config.read().expect("app config rwlock poisoned");
         let config: &AppConfig = &guard;
 
@@ -100,7 +121,7 @@ pub async fn get_user_profile(Path(id): Path<String>) -> Json<ApiResponse<UserPr
             });
         };
 
-        (profile.clone(), config.active_user_profile_id.clone())
+        (profile.clone(), chain_position(config, &id))
     };
 
     let path = profile_file_path(&state.data_root, &profile_meta);
@@ -117,12 +138,11 @@ pub async fn get_user_profile(Path(id): Path<String>) -> Json<ApiResponse<UserPr
         }
     };
 
-    let active_id = active_id.as_deref();
-
     let detail = UserProfileDetail {
         id: profile_meta.id.clone(),
         name: profile_meta.name.clone(),
-        is_active: active_id == Some(profile_meta.id.as_str()),
+        is_active: chain_index.is_some(),
+        chain_index,
         last_modified_time: profile_meta.last_modified_time.clone(),
         content,
     };
@@ -149,13 +169,16 @@ pub async fn list_user_profiles() -> Json<ApiResponse<UserProfileListResponse>>
     Json(ApiResponse {
         code: "ok".to_string(),
         message: "success".to_string(),
-        data: Some(UserProfileListResponse { user_profiles }),
+        data: Some(UserProfileListResponse {
+            user_profiles,
+            chain: config.active_user_profile_ids.clone(),
+        }),
     })
 }
 
 pub async fn create_user_profile(
     Json(body): Json<CreateUserProfileRequest>,
-) -> Json<ApiResponse<UserProfileSummary>> {
+) -> Json<ApiResponse<serde_json::Value>> {
     let state = app_state();
 
     // 如果提供了内容且非空，先校验 YAML 格式；空内容视为一个空配置
@@ -195,11 +218,20 @@ pub async fn create_user_profile(
             last_fetch_time: None,
             last_fetch_status: None,
             last_modified_time: Some(current_timestamp()),
+            subscription_format: None,
+            userinfo: None,
+            update_interval_hours: None,
+            remote_filename: None,
+            mirror_urls: Vec::new(),
+            fetch_cache: None,
+            fetch_options: Default::default(),
+            schedule: Default::default(),
+            usage_warnings: Vec::new(),
         };
 
-        // 如果当前没有活跃用户 profile，则将新建的设为活跃
-        if config.active_user_profile_id.is_none() {
-            config.active_user_profile_id = Some(id.clone());
+        // 如果当前没有启用任何用户 profile，则自动启用新建的 profile
+        if config.active_user_profile_ids.is_empty() {
+            config.active_user_profile_ids.push(id.clone());
         }
 
         config.profiles.push(profile.clone());
@@ -243,7 +275,7 @@ pub async fn create_user_profile(
             });
         }
     }
-    if let Err(err) = std::fs::write(&path, content_to_write) {
+    if let Err(err) = std::fs::write(&path, &content_to_write) {
         let msg = format!("failed to write user profile {}: {err}", path.display());
         tracing::error!("{msg}");
         return Json(ApiResponse {
@@ -252,37 +284,53 @@ pub async fn create_user_profile(
             data: None,
         });
     }
-
-    // 如果新建的用户配置当前就是活跃配置，则尝试基于最新配置生成 merged.yaml 并通知内核重载。
+    // 如果新建的用户配置已被启用，则尝试基于最新配置生成 merged.yaml 并通知内核重载。
     let should_apply = {
         let cfg = crate::get_app_config_snapshot();
-        cfg.active_user_profile_id
-            .as_deref()
-            .is_some_and(|active| active == profile.id.as_str())
+        cfg.active_user_profile_ids.contains(&profile.id)
     };
 
     if should_apply {
-        if let Err(err) = generate_merged_config(&state.data_root) {
+        // 合并失败时撤销本次创建，避免留下一个让 merged.yaml 无法生成的已启用 profile
+        if let Err(err) = generate_merged_config(&state.data_root).await {
             tracing::error!(
                 "failed to generate merged config after creating active user profile: {err}"
             );
-        } else {
-            let _ = config_manager::reload_core_if_running(ConfigChangeReason::UserProfileUpdated)
-                .await;
+            if let Err(save_err) = with_app_config_mut(|config: &mut AppConfig| {
+                config.profiles.retain(|p| p.id != profile.id);
+                config.active_user_profile_ids.retain(|active| active != &profile.id);
+            }) {
+                tracing::error!("{save_err}");
+            }
+            restore_user_profile_file(&path, None);
+            return Json(ApiResponse {
+                code: err.code().to_string(),
+                message: err.to_string(),
+                data: err.issues_data(),
+            });
         }
+        let _ =
+            config_manager::reload_core_if_running(ConfigChangeReason::UserProfileUpdated).await;
     }
 
+    crate::history::record_revision_or_warn(
+        &state.data_root,
+        RevisionKind::UserProfile,
+        Some(&profile.id),
+        &content_to_write,
+    );
+
     Json(ApiResponse {
         code: "ok".to_string(),
         message: "created".to_string(),
-        data: Some(summary),
+        data: serde_json::to_value(summary).ok(),
     })
 }
 
 pub async fn update_user_profile(
     Path(id): Path<String>,
     Json(body): Json<UpdateUserProfileRequest>,
-) -> Json<ApiResponse<UserProfileDetail>> {
+) -> Json<ApiResponse<serde_json::Value>> {
     use std::fs;
 
     let state = app_state();
@@ -304,48 +352,19 @@ pub async fn update_user_profile(
         body.content
     };
 
-    // 更新全局配置中的 profile 元数据
-    let (updated_profile, is_active) = {
-        let mut guard = state
-            .app_config
-            .write()
-            .expect("app config rwlock poisoned");
-        let config: &mut AppConfig = &mut guard;
-
-        let Some(profile) = config
-            .profiles
-            .iter_mut()
-            .find(|p| matches!(p.profile_type, ProfileType::User) && p.id == id)
-        else {
-            return Json(ApiResponse {
-                code: "user_profile_not_found".to_string(),
-                message: "user profile not found".to_string(),
-                data: None,
-            });
-        };
-
-        profile.name = body.name;
-        profile.last_modified_time = Some(current_timestamp());
-
-        let updated_profile = profile.clone();
-        let is_active = config
-            .active_user_profile_id
-            .as_deref()
-            .is_some_and(|active| active == updated_profile.id.as_str());
-
-        if let Err(err) = save_app_config(&state.data_root, config) {
-            tracing::error!("{err}");
-            return Json(ApiResponse {
-                code: "config_save_failed".to_string(),
-                message: err,
-                data: None,
-            });
-        }
-
-        (updated_profile, is_active)
+    let Some(profile) = crate::get_app_config_snapshot()
+        .profiles
+        .into_iter()
+        .find(|p| matches!(p.profile_type, ProfileType::User) && p.id == id)
+    else {
+        return Json(ApiResponse {
+            code: "user_profile_not_found".to_string(),
+            message: "user profile not found".to_string(),
+            data: None,
+        });
     };
 
-    let path = profile_file_path(&state.data_root, &updated_profile);
+    let path = profile_file_path(&state.data_root, &profile);
     if let Some(parent) = path.parent() {
         if let Err(err) = fs::create_dir_all(parent) {
             let msg = format!(
@@ -361,6 +380,9 @@ pub async fn update_user_profile(
         }
     }
 
+    // 保留旧内容：位于已启用链中的 profile 若合并失败，需要恢复原文件
+    let previous_content = fs::read_to_string(&path).ok();
+
     if let Err(err) = fs::write(&path, &content_to_write) {
         let msg = format!("failed to write user profile {}: {err}", path.display());
         tracing::error!("{msg}");
@@ -371,36 +393,90 @@ pub async fn update_user_profile(
         });
     }
 
-    let detail = UserProfileDetail {
-        id: updated_profile.id.clone(),
-        name: updated_profile.name.clone(),
-        is_active,
-        last_modified_time: updated_profile.last_modified_time.clone(),
-        content: content_to_write,
-    };
+    // 如果更新的用户配置位于已启用的链中，则先生成 merged.yaml；
+    // 校验或内核测试未通过时恢复原文件，拒绝本次修改。
+    let is_active = chain_position(&crate::get_app_config_snapshot(), &id).is_some();
+    if is_active && let Err(err) = generate_merged_config(&state.data_root).await {
+        tracing::error!("failed to generate merged config after user profile update: {err}");
+        restore_user_profile_file(&path, previous_content.as_deref());
+        return Json(ApiResponse {
+            code: err.code().to_string(),
+            message: err.to_string(),
+            data: err.issues_data(),
+        });
+    }
 
-    // 如果更新的是当前活跃的用户配置，则在成功写入后生成 merged.yaml 并尝试重载内核配置。
-    if is_active {
-        if let Err(err) = generate_merged_config(&state.data_root) {
-            tracing::error!("failed to generate merged config after user profile update: {err}");
+    // 更新全局配置中的 profile 元数据
+    let result = with_app_config_mut(|config: &mut AppConfig| {
+        let profile = config
+            .profiles
+            .iter_mut()
+            .find(|p| matches!(p.profile_type, ProfileType::User) && p.id == id)?;
+        profile.name = body.name.clone();
+        profile.last_modified_time = Some(current_timestamp());
+        let updated_profile = profile.clone();
+        let chain_index = chain_position(config, &updated_profile.id);
+        Some((updated_profile, chain_index))
+    });
+
+    let (updated_profile, chain_index) = match result {
+        Ok(Some(updated)) => updated,
+        Ok(None) => {
             return Json(ApiResponse {
-                code: "config_merge_failed".to_string(),
+                code: "user_profile_not_found".to_string(),
+                message: "user profile not found".to_string(),
+                data: None,
+            });
+        }
+        Err(err) => {
+            tracing::error!("{err}");
+            return Json(ApiResponse {
+                code: "config_save_failed".to_string(),
                 message: err,
                 data: None,
             });
         }
+    };
 
+    crate::history::record_revision_or_warn(
+        &state.data_root,
+        RevisionKind::UserProfile,
+        Some(&updated_profile.id),
+        &content_to_write,
+    );
+
+    if is_active {
         let _ =
             config_manager::reload_core_if_running(ConfigChangeReason::UserProfileUpdated).await;
     }
 
+    let detail = UserProfileDetail {
+        id: updated_profile.id.clone(),
+        name: updated_profile.name.clone(),
+        is_active: chain_index.is_some(),
+        chain_index,
+        last_modified_time: updated_profile.last_modified_time.clone(),
+        content: content_to_write,
+    };
+
     Json(ApiResponse {
         code: "ok".to_string(),
         message: "updated".to_string(),
-        data: Some(detail),
+        data: serde_json::to_value(detail).ok(),
     })
 }
 
+/// 合并失败后恢复用户 profile 文件：有旧内容则写回，原本不存在则删除。
+fn restore_user_profile_file(path: &std::path::Path, previous: Option<&str>) {
+    let result = match previous {
+        Some(content) => std::fs::write(path, content),
+        None => std::fs::remove_file(path),
+    };
+    if let Err(err) = result {
+        tracing::error!("failed to restore user profile {}: {err}", path.display());
+    }
+}
+
 pub async fn delete_user_profile(Path(id): Path<String>) -> Json<ApiResponse<serde_json::Value>> {
     let state = app_state();
 
@@ -426,13 +502,9 @@ pub async fn delete_user_profile(Path(id): Path<String>) -> Json<ApiResponse<ser
         } else {
             removed = true;
 
-            if config
-                .active_user_profile_id
-                .as_ref()
-                .is_some_and(|active| active == &id)
-            {
+            if config.active_user_profile_ids.contains(&id) {
                 was_active = true;
-                config.active_user_profile_id = None;
+                config.active_user_profile_ids.retain(|active| active != &id);
             }
 
             if let Err(err) = save_app_config(&state.data_root, config) {
@@ -464,12 +536,15 @@ pub async fn delete_user_profile(Path(id): Path<String>) -> Json<ApiResponse<ser
         }
     }
 
-    // 如果删除的是当前活跃的用户配置，则尝试基于最新配置生成 merged.yaml 并通知内核重载。
+    // 如果删除的用户配置位于已启用的链中，则尝试基于最新配置生成 merged.yaml 并通知内核重载。
+    // 删除本身不会因合并失败而撤销，但校验问题会随响应返回。
+    let mut issues = None;
     if was_active {
-        if let Err(err) = generate_merged_config(&state.data_root) {
+        if let Err(err) = generate_merged_config(&state.data_root).await {
             tracing::error!(
                 "failed to generate merged config after deleting active user profile: {err}"
             );
+            issues = err.issues_data();
         } else {
             let _ = config_manager::reload_core_if_running(ConfigChangeReason::UserProfileDeleted)
                 .await;
@@ -479,68 +554,153 @@ pub async fn delete_user_profile(Path(id): Path<String>) -> Json<ApiResponse<ser
     Json(ApiResponse {
         code: "ok".to_string(),
         message: "deleted".to_string(),
-        data: Some(serde_json::json!({})),
+        data: Some(issues.unwrap_or_else(|| serde_json::json!({}))),
     })
 }
 
+/// 启用指定用户 profile：若尚未位于用户 profile 链中，则追加到链尾（最高优先级）。
 pub async fn activate_user_profile(Path(id): Path<String>) -> Json<ApiResponse<serde_json::Value>> {
-    let mut found = false;
-
-    let state = app_state();
+    let result = update_user_profile_chain_with(|config| {
+        if !config
+            .profiles
+            .iter()
+            .any(|p| matches!(p.profile_type, ProfileType::User) && p.id == id)
+        {
+            return Err(id.clone());
+        }
+        if !config.active_user_profile_ids.contains(&id) {
+            config.active_user_profile_ids.push(id.clone());
+        }
+        Ok(())
+    });
 
-    {
-        let mut guard = state
-            .app_config
-            .write()
-            .expect("app config rwlock poisoned");
-        let config: &mut AppConfig = &mut guard;
+    respond_user_profile_chain_change(result, "activated").await
+}
 
+/// 禁用指定用户 profile：将其从用户 profile 链中移除（不删除文件）。
+pub async fn deactivate_user_profile(
+    Path(id): Path<String>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let result = update_user_profile_chain_with(|config| {
         if !config
             .profiles
             .iter()
             .any(|p| matches!(p.profile_type, ProfileType::User) && p.id == id)
         {
-            // not found
-        } else {
-            found = true;
-            config.active_user_profile_id = Some(id.clone());
+            return Err(id.clone());
+        }
+        config.active_user_profile_ids.retain(|active| active != &id);
+        Ok(())
+    });
 
-            if let Err(err) = save_app_config(&state.data_root, config) {
-                tracing::error!("{err}");
-                return Json(ApiResponse {
-                    code: "config_save_failed".to_string(),
-                    message: err,
-                    data: None,
-                });
+    respond_user_profile_chain_change(result, "deactivated").await
+}
+
+/// 整体替换用户 profile 链，可同时完成排序、启用与禁用。
+pub async fn update_user_profile_chain(
+    Json(body): Json<UpdateUserProfileChainRequest>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let result = update_user_profile_chain_with(|config| {
+        let mut chain: Vec<String> = Vec::with_capacity(body.ids.len());
+        for id in &body.ids {
+            if !config
+                .profiles
+                .iter()
+                .any(|p| matches!(p.profile_type, ProfileType::User) && &p.id == id)
+            {
+                return Err(id.clone());
+            }
+            if !chain.contains(id) {
+                chain.push(id.clone());
             }
         }
-    }
+        config.active_user_profile_ids = chain;
+        Ok(())
+    });
 
-    if !found {
-        return Json(ApiResponse {
-            code: "user_profile_not_found".to_string(),
-            message: "user profile not found".to_string(),
-            data: None,
-        });
+    respond_user_profile_chain_change(result, "updated").await
+}
+
+enum ChainUpdateError {
+    /// 引用了不存在的用户 profile
+    NotFound(String),
+    /// 持久化 app.json 失败
+    SaveFailed(String),
+}
+
+/// 在写锁内修改用户 profile 链并持久化；返回链是否发生了变化。
+fn update_user_profile_chain_with<F>(f: F) -> Result<bool, ChainUpdateError>
+where
+    F: FnOnce(&mut AppConfig) -> Result<(), String>,
+{
+    let state = app_state();
+
+    let mut guard = state
+        .app_config
+        .write()
+        .expect("app config rwlock poisoned");
+    let config: &mut AppConfig = &mut guard;
+
+    let before = config.active_user_profile_ids.clone();
+    f(config).map_err(ChainUpdateError::NotFound)?;
+
+    if config.active_user_profile_ids == before {
+        return Ok(false);
     }
 
-    // 激活用户配置后，基于最新配置生成 merged.yaml，并尝试通知内核重载。
-    if let Err(err) = generate_merged_config(&state.data_root) {
-        tracing::error!("failed to generate merged config after user profile activate: {err}");
-        return Json(ApiResponse {
-            code: "config_merge_failed".to_string(),
-            message: err,
-            data: None,
-        });
+    if let Err(err) = save_app_config(&state.data_root, config) {
+        tracing::error!("{err}");
+        return Err(ChainUpdateError::SaveFailed(err));
     }
 
-    let _ =
-        config_manager::reload_core_if_running(ConfigChangeReason::ActiveUserProfileChanged).await;
+    Ok(true)
+}
+
+async fn respond_user_profile_chain_change(
+    result: Result<bool, ChainUpdateError>,
+    message: &str,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let state = app_state();
+
+    let changed = match result {
+        Ok(changed) => changed,
+        Err(ChainUpdateError::NotFound(id)) => {
+            return Json(ApiResponse {
+                code: "user_profile_not_found".to_string(),
+                message: format!("user profile not found: {id}"),
+                data: None,
+            });
+        }
+        Err(ChainUpdateError::SaveFailed(err)) => {
+            return Json(ApiResponse {
+                code: "config_save_failed".to_string(),
+                message: err,
+                data: None,
+            });
+        }
+    };
+
+    let chain = crate::get_app_config_snapshot().active_user_profile_ids;
+
+    // 用户 profile 链变化后，基于最新配置生成 merged.yaml，并尝试通知内核重载。
+    if changed {
+        if let Err(err) = generate_merged_config(&state.data_root).await {
+            tracing::error!("failed to generate merged config after user profile chain change: {err}");
+            return Json(ApiResponse {
+                code: err.code().to_string(),
+                message: err.to_string(),
+                data: err.issues_data(),
+            });
+        }
+
+        let _ = config_manager::reload_core_if_running(ConfigChangeReason::ActiveUserProfileChanged)
+            .await;
+    }
 
     Json(ApiResponse {
         code: "ok".to_string(),
-        message: "activated".to_string(),
-        data: Some(serde_json::json!({})),
+        message: message.to_string(),
+        data: Some(serde_json::json!({ "chain": chain })),
     })
 }
 
@@ -558,10 +718,78 @@ fn load_yaml_file(path: &std::path::Path) -> Result<serde_yaml::Value, String> {
         .map_err(|err| format!("failed to parse yaml at {}: {err}", path.display()))
 }
 
+/// 用户 profile 中仅作为合并指令使用的字段，不会原样出现在 `merged.yaml` 中。
+const MERGE_DIRECTIVE_KEYS: &[&str] = &[
+    "prepend-rules",
+    "append-rules",
+    "prepend-proxies",
+    "append-proxies",
+    "prepend-proxy-groups",
+    "append-proxy-groups",
+    "patch-proxy-groups",
+    "delete-proxy-groups",
+    "insert-into-group",
+    "filter-proxies",
+    "rename-proxies",
+    "delete-rules",
+    "rewrite-rule-targets",
+    "script",
+];
+
+/// 合并过程中因 `rename-proxies` 产生的节点改名记录：(原名称, 最终名称)。
+type ProxyRenames = Vec<(String, String)>;
+
+/// 将一层产生的改名并入累计记录；同一节点在多层被依次改名时只保留原名称到最终名称的映射。
+fn chain_renames(all: &mut ProxyRenames, layer: ProxyRenames) {
+    for (old, new) in layer {
+        match all.iter_mut().find(|(_, current)| *current == old) {
+            Some(entry) => entry.1 = new,
+            None => all.push((old, new)),
+        }
+    }
+}
+
+/// 将一个用户 profile 叠加到基础配置上：
+/// 先对基础配置执行用户 profile 中的 `script` 变换步骤（见 `enhance` 模块），
+/// 再按常规规则合并用户 profile 的其余字段。
+///
+/// 同时返回该层（`script` 与 `rename-proxies` 指令）产生的节点改名记录。
+fn apply_user_layer(
+    base: &serde_yaml::Value,
+    user: Option<&serde_yaml::Value>,
+) -> Result<(serde_yaml::Value, ProxyRenames), String> {
+    use serde_yaml::Value;
+
+    let script = user.and_then(|u| u.get("script"));
+    let Some(script) = script else {
+        return merge_yaml_configs_with_renames(Some(base), user);
+    };
+
+    let mut base_map = match base {
+        Value::Mapping(m) => m.clone(),
+        Value::Null => serde_yaml::Mapping::new(),
+        other => return Err(format!("base config root must be mapping, got {other:?}")),
+    };
+    let mut renames = crate::enhance::run_script(&mut base_map, script)?;
+
+    let (merged, layer_renames) =
+        merge_yaml_configs_with_renames(Some(&Value::Mapping(base_map)), user)?;
+    chain_renames(&mut renames, layer_renames);
+    Ok((merged, renames))
+}
+
 fn merge_yaml_configs(
     remote: Option<&serde_yaml::Value>,
     user: Option<&serde_yaml::Value>,
 ) -> Result<serde_yaml::Value, String> {
+    merge_yaml_configs_with_renames(remote, user).map(|(merged, _)| merged)
+}
+
+/// 同 `merge_yaml_configs`，额外返回 `rename-proxies` 指令产生的节点改名记录。
+fn merge_yaml_configs_with_renames(
+    remote: Option<&serde_yaml::Value>,
+    user: Option<&serde_yaml::Value>,
+) -> Result<(serde_yaml::Value, ProxyRenames), String> {
     use serde_yaml::{Mapping, Sequence, Value};
     fn to_mapping(value: Option<&Value>, label: &str) -> Result<Mapping, String> {
         match value {
@@ -650,15 +878,7 @@ fn merge_yaml_configs(
 
         // 特殊字段仅作为合并指令使用，不直接写入结果
         if let Some(name) = key_str {
-            if matches!(
-                name,
-                "prepend-rules"
-                    | "append-rules"
-                    | "prepend-proxies"
-                    | "append-proxies"
-                    | "prepend-proxy-groups"
-                    | "append-proxy-groups"
-            ) {
+            if MERGE_DIRECTIVE_KEYS.contains(&name) {
                 continue;
             }
         }
@@ -727,196 +947,1162 @@ fn merge_yaml_configs(
         }
     }
 
-    Ok(Value::Mapping(final_map))
-}
-
-pub(crate) fn merged_config_path(root: &PathBuf) -> PathBuf {
-    let mut path = root.clone();
-    path.push("config");
-    path.push("merged.yaml");
-    path
-}
+    // 过滤 / 重命名节点（作用于合成后的 proxies，并同步更新代理组成员）
+    let renames = apply_proxy_directives(&mut final_map, &user_map)?;
 
-fn save_merged_config(root: &PathBuf, value: &serde_yaml::Value) -> Result<(), String> {
-    use std::fs;
-    use std::io::Write;
+    // 按组名定点修改 proxy-groups：先删除、再修改、最后插入节点
+    let deleted_groups = apply_proxy_group_directives(&mut final_map, &user_map)?;
 
-    let path = merged_config_path(root);
-    let parent = path
-        .parent()
-        .ok_or_else(|| format!("invalid merged.yaml path: {}", path.display()))?;
+    // 删除 / 改写规则，并保证 MATCH 位于最后
+    apply_rule_directives(&mut final_map, &user_map)?;
 
-    fs::create_dir_all(parent)
-        .map_err(|err| format!("failed to create config dir at {}: {err}", parent.display()))?;
+    // 仍指向已删除代理组的规则改为指向 MATCH 的目标
+    retarget_rules_of_deleted_groups(&mut final_map, &deleted_groups)?;
 
-    let content = serde_yaml::to_string(value)
-        .map_err(|err| format!("failed to serialize merged config: {err}"))?;
+    Ok((Value::Mapping(final_map), renames))
+}
 
-    let tmp_path = path.with_extension("yaml.tmp");
-    {
-        let mut file = fs::File::create(&tmp_path).map_err(|err| {
-            format!(
-                "failed to create temp merged config at {}: {err}",
-                tmp_path.display()
-            )
-        })?;
-        file.write_all(content.as_bytes()).map_err(|err| {
-            format!(
-                "failed to write temp merged config at {}: {err}",
-                tmp_path.display()
-            )
-        })?;
-        file.flush().map_err(|err| {
-            format!(
-                "failed to flush temp merged config at {}: {err}",
-                tmp_path.display()
-            )
-        })?;
-    }
-
-    fs::rename(&tmp_path, &path).map_err(|err| {
-        format!(
-            "failed to move temp merged config from {} to {}: {err}",
-            tmp_path.display(),
-            path.display()
-        )
-    })
+/// `delete-rules` 的条目：完整规则字符串（逗号两侧空白不敏感），或 `{ match: 正则 }`。
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DeleteRule {
+    Exact(String),
+    Match {
+        #[serde(rename = "match")]
+        pattern: String,
+    },
 }
 
-fn defaults_path(root: &PathBuf) -> PathBuf {
-    let mut path = root.clone();
-    path.push("config");
-    path.push("defaults.yaml");
-    path
+/// `rewrite-rule-targets` 的条目：将目标为 `from`（精确）或匹配 `match`（正则）的规则改为指向 `to`。
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RewriteRuleTarget {
+    #[serde(default)]
+    from: Option<String>,
+    #[serde(default, rename = "match")]
+    pattern: Option<String>,
+    to: String,
 }
 
-const DEFAULTS_YAML: &str = include_str!("./defaults.yaml");
-const SYSTEM_YAML: &str = include_str!("./system.yaml");
+/// 去除规则各字段两侧的空白，用于精确匹配。
+fn normalize_rule(rule: &str) -> String {
+    rule.split(',').map(str::trim).collect::<Vec<_>>().join(",")
+}
 
-pub fn generate_merged_config(root: &PathBuf) -> Result<(), String> {
-    let config = load_app_config(root)?;
+fn is_match_rule(rule: &serde_yaml::Value) -> bool {
+    rule.as_str()
+        .is_some_and(|r| crate::validate::split_rule(r).rule_type == "MATCH")
+}
 
-    // 远程订阅侧配置（可选：如果未设置活跃订阅，则视为无远程配置）
-    let remote_value = if let Some(active_id) = config.active_subscription_id.as_deref() {
-        let Some(profile) = config
-            .profiles
-            .iter()
-            .find(|p| matches!(p.profile_type, ProfileType::Remote) && p.id == active_id)
-        else {
-            return Err("active_subscription_not_found".to_string());
-        };
+/// 处理用户 profile 中的 `delete-rules` / `rewrite-rule-targets` 指令。
+///
+/// 该层使用了任一规则指令（含 `prepend-rules` / `append-rules`）时，会整理 MATCH 规则：
+/// 只保留最后出现的一条并移到列表末尾，使追加在其后的规则生效；
+/// 若 `delete-rules` 删除了全部 MATCH 规则，则恢复删除前的最后一条（仍会参与目标改写）。
+fn apply_rule_directives(
+    config: &mut serde_yaml::Mapping,
+    user: &serde_yaml::Mapping,
+) -> Result<(), String> {
+    use serde_yaml::Value;
+
+    let deletes: Vec<DeleteRule> = parse_directive(user, "delete-rules")?;
+    let rewrites: Vec<RewriteRuleTarget> = parse_directive(user, "rewrite-rule-targets")?;
+    let uses_rule_directives = ["prepend-rules", "append-rules", "delete-rules", "rewrite-rule-targets"]
+        .iter()
+        .any(|key| user.contains_key(*key));
+    if !uses_rule_directives {
+        return Ok(());
+    }
 
-        let path = profile_file_path(root, profile);
-        if !path.is_file() {
+    let rules_key = Value::String("rules".into());
+    let mut rules = match config.get(&rules_key) {
+        Some(Value::Sequence(seq)) => seq.clone(),
+        Some(Value::Null) | None => serde_yaml::Sequence::new(),
+        Some(other) => {
             return Err(format!(
-                "subscription config file not found at {}",
-                path.display()
+                "field 'rules' must be a sequence when present, got {other:?}"
             ));
         }
-
-        Some(load_yaml_file(&path)?)
-    } else {
-        None
     };
+    let fallback = rules.iter().rev().find(|r| is_match_rule(r)).cloned();
+
+    // 1. 删除规则
+    let mut exact = Vec::new();
+    let mut patterns = Vec::new();
+    for entry in deletes {
+        match entry {
+            DeleteRule::Exact(rule) => exact.push(normalize_rule(&rule)),
+            DeleteRule::Match { pattern } => patterns.push(
+                regex::Regex::new(&pattern)
+                    .map_err(|err| format!("invalid 'delete-rules' match regex '{pattern}': {err}"))?,
+            ),
+        }
+    }
+    if !exact.is_empty() || !patterns.is_empty() {
+        rules.retain(|rule| {
+            let Some(rule) = rule.as_str() else {
+                return true;
+            };
+            let normalized = normalize_rule(rule);
+            !exact.contains(&normalized) && !patterns.iter().any(|re| re.is_match(&normalized))
+        });
+    }
+    if let Some(rule) = fallback
+        && !rules.iter().any(is_match_rule)
+    {
+        rules.push(rule);
+    }
 
-    // 用户侧配置（可选）
-    let user_value = if let Some(active_id) = config.active_user_profile_id.as_deref() {
-        let Some(profile) = config
-            .profiles
-            .iter()
-            .find(|p| matches!(p.profile_type, ProfileType::User) && p.id == active_id)
-        else {
-            return Err("active_user_profile_not_found".to_string());
-        };
-
-        let path = profile_file_path(root, profile);
-        if !path.is_file() {
-            return Err(format!(
-                "user profile config file not found at {}",
-                path.display()
-            ));
+    // 2. 改写规则目标（子规则的目标是子规则名而非策略，不参与改写）
+    for entry in rewrites {
+        if entry.from.is_some() == entry.pattern.is_some() {
+            return Err(
+                "each 'rewrite-rule-targets' entry must have exactly one of 'from' or 'match'"
+                    .to_string(),
+            );
+        }
+        let matcher = GroupTarget::new(entry.from, entry.pattern, "rewrite-rule-targets")?;
+        for rule in rules.iter_mut() {
+            let Some(text) = rule.as_str() else {
+                continue;
+            };
+            let parts = crate::validate::split_rule(text);
+            if parts.rule_type == "SUB-RULE" {
+                continue;
+            }
+            let Some(range) = parts.target else {
+                continue;
+            };
+            if matcher.matches(&text[range.clone()]) {
+                let rewritten = format!("{}{}{}", &text[..range.start], entry.to, &text[range.end..]);
+                *rule = Value::String(rewritten);
+            }
         }
+    }
 
-        Some(load_yaml_file(&path)?)
-    } else {
-        None
-    };
+    // 3. 保证 MATCH 为最后一条规则
+    let last_match = rules.iter().rev().find(|r| is_match_rule(r)).cloned();
+    rules.retain(|rule| !is_match_rule(rule));
+    if let Some(rule) = last_match {
+        rules.push(rule);
+    }
 
-    // 逐层合并顺序：
-    // 1. defaults.yaml       —— 全局默认配置（最低优先级，可由远程/用户配置覆盖）
-    // 2. 远程订阅配置        —— 当前活跃订阅
-    // 3. 用户配置            —— 当前活跃用户 profile
-    // 4. system.yaml         —— 系统级配置（最高优先级，用于强制覆盖关键字段）
-    //
-    // merge_yaml_configs(remote, user) 的语义是：
-    // - remote 为“基础配置”
-    // - user   为“用户配置”，在标量/对象上覆盖 remote，在列表上按规则处理
-    //
-    // 因此最终合并顺序为：
-    // defaults -> remote -> user -> system
+    config.insert(rules_key, Value::Sequence(rules));
+    Ok(())
+}
 
-    // 1. 加载 defaults.yaml（若不存在则写入内置模板）
-    let defaults_path = defaults_path(root);
-    let defaults_value_opt = {
-        use std::fs;
-        use std::io::Write;
-
-        if !defaults_path.is_file() {
-            if let Some(parent) = defaults_path.parent() {
-                if let Err(err) = fs::create_dir_all(parent) {
-                    tracing::error!(
-                        "failed to create defaults dir {}: {err}",
-                        parent.display()
-                    );
+/// 读取取值可以是单个对象或对象列表的指令。
+fn parse_one_or_many<T: serde::de::DeserializeOwned>(
+    user: &serde_yaml::Mapping,
+    key: &str,
+) -> Result<Vec<T>, String> {
+    use serde_yaml::Value;
+
+    match user.get(key) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(value @ Value::Mapping(_)) => serde_yaml::from_value(value.clone())
+            .map(|item| vec![item])
+            .map_err(|err| format!("invalid '{key}': {err}")),
+        Some(value @ Value::Sequence(_)) => serde_yaml::from_value(value.clone())
+            .map_err(|err| format!("invalid '{key}': {err}")),
+        Some(other) => Err(format!(
+            "field '{key}' must be a mapping or a sequence, got {other:?}"
+        )),
+    }
+}
+
+/// 处理用户 profile 中的 `filter-proxies` / `rename-proxies` 指令（先过滤，后重命名）。
+fn apply_proxy_directives(
+    config: &mut serde_yaml::Mapping,
+    user: &serde_yaml::Mapping,
+) -> Result<ProxyRenames, String> {
+    let filters: Vec<crate::enhance::ProxyFilter> = parse_one_or_many(user, "filter-proxies")?;
+    let rules: Vec<crate::enhance::RenameRule> = parse_one_or_many(user, "rename-proxies")?;
+
+    for filter in &filters {
+        crate::enhance::filter_proxies(config, filter)
+            .map_err(|err| format!("filter-proxies failed: {err}"))?;
+    }
+
+    let mut renames = ProxyRenames::new();
+    for rule in &rules {
+        let pairs = crate::enhance::rename_proxies(config, rule)
+            .map_err(|err| format!("rename-proxies failed: {err}"))?;
+        chain_renames(&mut renames, pairs);
+    }
+
+    Ok(renames)
+}
+
+/// 按名称（`name`）或正则（`match`）定位代理组。
+enum GroupTarget {
+    Exact(String),
+    Pattern(regex::Regex),
+}
+
+impl GroupTarget {
+    fn new(name: Option<String>, pattern: Option<String>, directive: &str) -> Result<Self, String> {
+        match (name, pattern) {
+            (Some(name), None) => Ok(GroupTarget::Exact(name)),
+            (None, Some(pattern)) => regex::Regex::new(&pattern)
+                .map(GroupTarget::Pattern)
+                .map_err(|err| format!("invalid '{directive}' match regex '{pattern}': {err}")),
+            _ => Err(format!(
+                "each '{directive}' entry must have exactly one of 'name' or 'match'"
+            )),
+        }
+    }
+
+    fn matches(&self, group_name: &str) -> bool {
+        match self {
+            GroupTarget::Exact(name) => name == group_name,
+            GroupTarget::Pattern(re) => re.is_match(group_name),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            GroupTarget::Exact(name) => name.clone(),
+            GroupTarget::Pattern(re) => format!("/{}/", re.as_str()),
+        }
+    }
+}
+
+/// `delete-proxy-groups` 的条目：直接写组名，或 `{ match: 正则 }`。
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DeleteProxyGroup {
+    Name(String),
+    Match {
+        #[serde(rename = "match")]
+        pattern: String,
+    },
+}
+
+/// `patch-proxy-groups` 的条目：修改匹配代理组的字段，或按正则剔除组内节点。
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PatchProxyGroup {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default, rename = "match")]
+    pattern: Option<String>,
+    /// 覆盖到组上的字段，如 `type` / `url` / `interval`（不允许修改 `name`）
+    #[serde(default)]
+    set: Option<serde_yaml::Mapping>,
+    /// 从组成员中剔除名称匹配该正则的节点
+    #[serde(default)]
+    exclude: Option<String>,
+}
+
+/// `insert-into-group` 的条目：向匹配的代理组插入节点。
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InsertIntoGroup {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default, rename = "match")]
+    pattern: Option<String>,
+    /// 显式指定的节点 / 代理组名称
+    #[serde(default)]
+    proxies: Vec<String>,
+    /// 从最终 `proxies` 中按名称正则挑选节点
+    #[serde(default)]
+    select: Option<String>,
+    /// `prepend` 或 `append`（默认）
+    #[serde(default)]
+    position: Option<String>,
+}
+
+fn parse_directive<T: serde::de::DeserializeOwned>(
+    user: &serde_yaml::Mapping,
+    key: &str,
+) -> Result<Vec<T>, String> {
+    match user.get(key) {
+        None | Some(serde_yaml::Value::Null) => Ok(Vec::new()),
+        Some(value @ serde_yaml::Value::Sequence(_)) => serde_yaml::from_value(value.clone())
+            .map_err(|err| format!("invalid '{key}': {err}")),
+        Some(other) => Err(format!("field '{key}' must be a sequence, got {other:?}")),
+    }
+}
+
+fn group_name(group: &serde_yaml::Value) -> Option<&str> {
+    group.get("name").and_then(|v| v.as_str())
+}
+
+fn group_members_mut(group: &mut serde_yaml::Value) -> Option<&mut serde_yaml::Sequence> {
+    let map = group.as_mapping_mut()?;
+    let members = map
+        .entry(serde_yaml::Value::String("proxies".into()))
+        .or_insert_with(|| serde_yaml::Value::Sequence(Vec::new()));
+    if !members.is_sequence() {
+        *members = serde_yaml::Value::Sequence(Vec::new());
+    }
+    members.as_sequence_mut()
+}
+
+/// 处理用户 profile 中的 `delete-proxy-groups` / `patch-proxy-groups` / `insert-into-group` 指令。
+///
+/// 名称精确匹配但找不到对应代理组时仅记录日志并跳过，避免订阅调整组名后整体合并失败。
+/// 返回被删除的代理组名称，供规则处理完成后改写仍指向这些组的规则。
+fn apply_proxy_group_directives(
+    config: &mut serde_yaml::Mapping,
+    user: &serde_yaml::Mapping,
+) -> Result<Vec<String>, String> {
+    use serde_yaml::Value;
+
+    let deletes: Vec<DeleteProxyGroup> = parse_directive(user, "delete-proxy-groups")?;
+    let patches: Vec<PatchProxyGroup> = parse_directive(user, "patch-proxy-groups")?;
+    let inserts: Vec<InsertIntoGroup> = parse_directive(user, "insert-into-group")?;
+    if deletes.is_empty() && patches.is_empty() && inserts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let proxy_names: Vec<String> = match config.get("proxies") {
+        Some(Value::Sequence(seq)) => seq
+            .iter()
+            .filter_map(group_name)
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let groups_key = Value::String("proxy-groups".into());
+    let mut groups = match config.get(&groups_key) {
+        Some(Value::Sequence(seq)) => seq.clone(),
+        Some(Value::Null) | None => serde_yaml::Sequence::new(),
+        Some(other) => {
+            return Err(format!(
+                "field 'proxy-groups' must be a sequence when present, got {other:?}"
+            ));
+        }
+    };
+
+    let warn_unmatched = |directive: &str, target: &GroupTarget, matched: bool| {
+        if !matched && matches!(target, GroupTarget::Exact(_)) {
+            tracing::warn!(
+                "{directive}: proxy group '{}' not found, skipped",
+                target.describe()
+            );
+        }
+    };
+
+    // 1. 删除代理组，并移除其他组中对它的引用
+    let mut deleted = Vec::new();
+    for entry in deletes {
+        let target = match entry {
+            DeleteProxyGroup::Name(name) => GroupTarget::new(Some(name), None, "delete-proxy-groups")?,
+            DeleteProxyGroup::Match { pattern } => {
+                GroupTarget::new(None, Some(pattern), "delete-proxy-groups")?
+            }
+        };
+        let removed: Vec<String> = groups
+            .iter()
+            .filter_map(group_name)
+            .filter(|name| target.matches(name))
+            .map(str::to_string)
+            .collect();
+        warn_unmatched("delete-proxy-groups", &target, !removed.is_empty());
+
+        groups.retain(|g| group_name(g).is_none_or(|name| !removed.iter().any(|r| r == name)));
+        for group in groups.iter_mut() {
+            if let Some(Value::Sequence(members)) = group.get_mut("proxies") {
+                members.retain(|m| m.as_str().is_none_or(|name| !removed.iter().any(|r| r == name)));
+            }
+        }
+        deleted.extend(removed);
+    }
+
+    // 2. 修改代理组字段 / 剔除组内节点
+    for entry in patches {
+        let target = GroupTarget::new(entry.name, entry.pattern, "patch-proxy-groups")?;
+        if let Some(set) = &entry.set
+            && set.contains_key("name")
+        {
+            return Err(format!(
+                "patch-proxy-groups: renaming group '{}' via 'set.name' is not supported",
+                target.describe()
+            ));
+        }
+        let exclude = entry
+            .exclude
+            .as_deref()
+            .map(|p| {
+                regex::Regex::new(p)
+                    .map_err(|err| format!("invalid 'patch-proxy-groups' exclude regex '{p}': {err}"))
+            })
+            .transpose()?;
+
+        let mut matched = false;
+        for group in groups.iter_mut() {
+            if !group_name(group).is_some_and(|name| target.matches(name)) {
+                continue;
+            }
+            matched = true;
+            if let (Some(set), Some(map)) = (&entry.set, group.as_mapping_mut()) {
+                for (k, v) in set {
+                    map.insert(k.clone(), v.clone());
                 }
             }
-            if let Err(err) = fs::File::create(&defaults_path)
-                .and_then(|mut f| f.write_all(DEFAULTS_YAML.as_bytes()))
+            if let Some(re) = &exclude
+                && let Some(Value::Sequence(members)) = group.get_mut("proxies")
             {
+                members.retain(|m| m.as_str().is_none_or(|name| !re.is_match(name)));
+            }
+        }
+        warn_unmatched("patch-proxy-groups", &target, matched);
+    }
+
+    // 3. 向代理组插入节点（已存在的成员不会重复插入）
+    for entry in inserts {
+        let target = GroupTarget::new(entry.name, entry.pattern, "insert-into-group")?;
+        let prepend = match entry.position.as_deref() {
+            None | Some("append") => false,
+            Some("prepend") => true,
+            Some(other) => {
+                return Err(format!(
+                    "insert-into-group: unsupported position '{other}', expected 'prepend' or 'append'"
+                ));
+            }
+        };
+
+        let mut candidates = entry.proxies;
+        if let Some(pattern) = entry.select.as_deref() {
+            let re = regex::Regex::new(pattern).map_err(|err| {
+                format!("invalid 'insert-into-group' select regex '{pattern}': {err}")
+            })?;
+            candidates.extend(proxy_names.iter().filter(|n| re.is_match(n)).cloned());
+        }
+
+        let mut matched = false;
+        for group in groups.iter_mut() {
+            let Some(name) = group_name(group).map(str::to_string) else {
+                continue;
+            };
+            if !target.matches(&name) {
+                continue;
+            }
+            matched = true;
+            let Some(members) = group_members_mut(group) else {
+                continue;
+            };
+            let mut added = serde_yaml::Sequence::new();
+            for candidate in &candidates {
+                // 不把组插入到自身中
+                if *candidate == name
+                    || members.iter().chain(added.iter()).any(|m| m.as_str() == Some(candidate))
+                {
+                    continue;
+                }
+                added.push(Value::String(candidate.clone()));
+            }
+            if prepend {
+                added.append(members);
+                *members = added;
+            } else {
+                members.extend(added);
+            }
+        }
+        warn_unmatched("insert-into-group", &target, matched);
+    }
+
+    config.insert(groups_key, Value::Sequence(groups));
+    Ok(deleted)
+}
+
+/// `delete-proxy-groups` 删除代理组后，将仍以这些组为目标的规则改为指向 MATCH 规则的目标。
+///
+/// 在 `delete-rules` / `rewrite-rule-targets` 之后执行，用户可先自行处理这些规则；
+/// 若 MATCH 本身也指向被删除的组（或不存在 MATCH），则报错并列出这些规则。
+fn retarget_rules_of_deleted_groups(
+    config: &mut serde_yaml::Mapping,
+    deleted: &[String],
+) -> Result<(), String> {
+    use serde_yaml::Value;
+
+    if deleted.is_empty() {
+        return Ok(());
+    }
+    let Some(Value::Sequence(rules)) = config.get_mut("rules") else {
+        return Ok(());
+    };
+
+    let deleted_target = |rule: &Value| {
+        let text = rule.as_str()?;
+        let parts = crate::validate::split_rule(text);
+        if parts.rule_type == "SUB-RULE" {
+            return None;
+        }
+        let range = parts.target?;
+        deleted
+            .iter()
+            .any(|name| *name == text[range.clone()])
+            .then_some(range)
+    };
+
+    let dangling: Vec<usize> = (0..rules.len())
+        .filter(|&i| deleted_target(&rules[i]).is_some())
+        .collect();
+    if dangling.is_empty() {
+        return Ok(());
+    }
+
+    let fallback = rules
+        .iter()
+        .rev()
+        .find(|r| is_match_rule(r))
+        .filter(|r| deleted_target(r).is_none())
+        .and_then(|r| {
+            let text = r.as_str()?;
+            let range = crate::validate::split_rule(text).target?;
+            Some(text[range].to_string())
+        });
+    let Some(fallback) = fallback else {
+        let listed: Vec<&str> = dangling.iter().filter_map(|&i| rules[i].as_str()).collect();
+        return Err(format!(
+            "delete-proxy-groups: rules still target deleted groups and there is no MATCH rule to fall back to: {}",
+            listed.join("; ")
+        ));
+    };
+
+    for i in dangling {
+        let Some(range) = deleted_target(&rules[i]) else {
+            continue;
+        };
+        let Some(text) = rules[i].as_str() else {
+            continue;
+        };
+        let rewritten = format!("{}{}{}", &text[..range.start], fallback, &text[range.end..]);
+        tracing::info!("delete-proxy-groups: rule '{text}' now targets '{fallback}'");
+        rules[i] = Value::String(rewritten);
+    }
+    Ok(())
+}
+
+pub(crate) fn merged_config_path(root: &PathBuf) -> PathBuf {
+    let mut path = root.clone();
+    path.push("config");
+    path.push("merged.yaml");
+    path
+}
+
+/// 待内核测试通过后才会替换 merged.yaml 的暂存文件；每次合并使用不同的文件名。
+fn staged_merged_config_path(root: &PathBuf) -> PathBuf {
+    merged_config_path(root).with_file_name(format!(
+        "merged.staged-{}.yaml",
+        crate::app::unique_temp_suffix()
+    ))
+}
+
+/// 串行化 merged.yaml 的生成：合并、暂存、内核测试与替换必须整体完成，
+/// 避免并发的合并（用户编辑、订阅 / provider 定时刷新、历史恢复）相互覆盖。
+static MERGE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// 将合并结果写入暂存文件并交由 Mihomo（`mihomo -t`）测试，通过后再原子替换 merged.yaml。
+/// 测试失败时保留原有的 merged.yaml 不变。调用方需持有 [`MERGE_LOCK`]。
+async fn stage_and_swap_merged_config(root: &PathBuf, content: &str) -> Result<(), MergeError> {
+    use std::fs;
+
+    let staged = staged_merged_config_path(root);
+    crate::app::write_file_atomically(&staged, content).map_err(MergeError::Failed)?;
+
+    if let Err(output) = crate::core::test_core_config(root, &staged).await {
+        if let Err(err) = fs::remove_file(&staged) {
+            tracing::warn!("failed to remove staged merged config {}: {err}", staged.display());
+        }
+        return Err(MergeError::CoreTestFailed(output));
+    }
+
+    let path = merged_config_path(root);
+    fs::rename(&staged, &path).map_err(|err| {
+        MergeError::Failed(format!(
+            "failed to move staged merged config from {} to {}: {err}",
+            staged.display(),
+            path.display()
+        ))
+    })?;
+    crate::system_config::invalidate_cached_values();
+
+    if let Ok(content) = fs::read_to_string(&path) {
+        crate::history::record_revision_or_warn(root, RevisionKind::Merged, None, &content);
+    }
+    Ok(())
+}
+
+pub(crate) fn defaults_path(root: &PathBuf) -> PathBuf {
+    let mut path = root.clone();
+    path.push("config");
+    path.push("defaults.yaml");
+    path
+}
+
+pub(crate) const DEFAULTS_YAML: &str = include_str!("./defaults.yaml");
+
+/// combined 模式下，将主订阅与其余参与合并的订阅组合为一份远程配置。
+///
+/// 尚未成功拉取（文件不存在）的订阅会被跳过，避免阻塞整体合并。
+fn load_combined_subscriptions(
+    root: &PathBuf,
+    config: &AppConfig,
+    primary: &ProfileMeta,
+    primary_value: &serde_yaml::Value,
+) -> Result<serde_yaml::Value, String> {
+    use crate::combine::{CombineSource, combine_subscriptions};
+
+    let mut others: Vec<(&str, serde_yaml::Value)> = Vec::new();
+    for id in &config.combined_subscription.subscription_ids {
+        if id == &primary.id {
+            continue;
+        }
+        let Some(profile) = config
+            .profiles
+            .iter()
+            .find(|p| matches!(p.profile_type, ProfileType::Remote) && &p.id == id)
+        else {
+            return Err(format!("combined_subscription_not_found: {id}"));
+        };
+
+        let path = profile_file_path(root, profile);
+        if !path.is_file() {
+            tracing::warn!(
+                "skip combined subscription {} without local config at {}",
+                profile.name,
+                path.display()
+            );
+            continue;
+        }
+
+        others.push((profile.name.as_str(), load_yaml_file(&path)?));
+    }
+
+    let sources: Vec<CombineSource<'_>> = others
+        .iter()
+        .map(|(name, value)| CombineSource {
+            name,
+            config: value,
+        })
+        .collect();
+
+    combine_subscriptions(
+        CombineSource {
+            name: &primary.name,
+            config: primary_value,
+        },
+        &sources,
+    )
+}
+
+/// 合并流水线中的一层，用于溯源（explain）输出。
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MergeLayer {
+    Defaults,
+    Subscription {
+        id: String,
+        name: String,
+        combined: bool,
+    },
+    UserProfile {
+        id: String,
+        name: String,
+    },
+    /// 托管的 rule-provider / proxy-provider（见 `providers` 模块）
+    Providers,
+    System,
+}
+
+/// 溯源模式下记录的每层合并结果快照。
+type MergeTrace = Vec<(MergeLayer, serde_yaml::Value)>;
+
+fn record_layer(trace: &mut Option<&mut MergeTrace>, layer: MergeLayer, value: &serde_yaml::Value) {
+    if let Some(trace) = trace.as_deref_mut() {
+        trace.push((layer, value.clone()));
+    }
+}
+
+/// 生成 merged.yaml 失败的原因。
+#[derive(Debug)]
+pub(crate) enum MergeError {
+    /// 合并过程本身失败（读取、解析或写入文件等）
+    Failed(String),
+    /// 合并结果未通过语义校验，拒绝写入与应用
+    Invalid(Vec<ValidationIssue>),
+    /// Mihomo 内核测试（`mihomo -t`）未通过，携带内核输出
+    CoreTestFailed(String),
+}
+
+impl MergeError {
+    /// 对应 API 响应中的错误码。
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            MergeError::Failed(_) => "config_merge_failed",
+            MergeError::Invalid(_) => "config_validation_failed",
+            MergeError::CoreTestFailed(_) => "config_core_test_failed",
+        }
+    }
+
+    /// 校验失败时的结构化问题列表，可放入 API 响应的 `data` 中。
+    pub(crate) fn issues_data(&self) -> Option<serde_json::Value> {
+        match self {
+            MergeError::Failed(_) | MergeError::CoreTestFailed(_) => None,
+            MergeError::Invalid(issues) => Some(serde_json::json!({ "issues": issues })),
+        }
+    }
+}
+
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeError::Failed(err) => f.write_str(err),
+            MergeError::Invalid(issues) => f.write_str(&summarize_issues("merged config", issues)),
+            MergeError::CoreTestFailed(output) => {
+                write!(f, "mihomo rejected merged config: {output}")
+            }
+        }
+    }
+}
+
+/// 重新生成 merged.yaml：合并各层配置，通过语义校验与内核测试后才会替换磁盘上的文件。
+///
+/// 生成成功后，若有节点被 `rename-proxies` 改名，同步更新当前配置组合下已保存的代理选择。
+pub async fn generate_merged_config(root: &PathBuf) -> Result<(), MergeError> {
+    let _merge_guard = MERGE_LOCK.lock().await;
+    let mut renames = ProxyRenames::new();
+    let merged = run_merge_pipeline(root, None, Some(&mut renames)).map_err(MergeError::Failed)?;
+
+    let issues = validate_merged_config(&merged);
+    if !issues.is_empty() {
+        return Err(MergeError::Invalid(issues));
+    }
+
+    let content = serde_yaml::to_string(&merged)
+        .map_err(|err| MergeError::Failed(format!("failed to serialize merged config: {err}")))?;
+    stage_and_swap_merged_config(root, &content).await?;
+
+    if !renames.is_empty()
+        && crate::app::try_app_state().is_some()
+        && let Err(err) = crate::rename_saved_proxy_selections(&renames)
+    {
+        tracing::warn!("failed to update saved proxy selections after renaming proxies: {err}");
+    }
+    Ok(())
+}
+
+/// 直接以给定内容替换 merged.yaml（如从历史修订恢复），与重新生成一样经过语义校验与内核测试。
+pub(crate) async fn apply_merged_config_content(
+    root: &PathBuf,
+    content: &str,
+) -> Result<(), MergeError> {
+    let _merge_guard = MERGE_LOCK.lock().await;
+    let value: serde_yaml::Value = serde_yaml::from_str(content)
+        .map_err(|err| MergeError::Failed(format!("failed to parse merged config: {err}")))?;
+
+    let issues = validate_merged_config(&value);
+    if !issues.is_empty() {
+        return Err(MergeError::Invalid(issues));
+    }
+
+    stage_and_swap_merged_config(root, content).await
+}
+
+/// 合并流水线的输入：参与合并的各层原始配置。
+struct MergeInputs {
+    defaults: serde_yaml::Value,
+    remote: Option<(MergeLayer, serde_yaml::Value)>,
+    users: Vec<(MergeLayer, serde_yaml::Value)>,
+    /// 已缓存的托管 provider，以 `type: file` 注入
+    providers: serde_yaml::Mapping,
+    /// 内嵌 system.yaml 叠加 system-overrides.yaml 后的系统层
+    system: serde_yaml::Value,
+}
+
+/// 加载指定远程订阅作为订阅侧配置（combined 模式下会合并其余订阅的节点）。
+fn load_subscription_layer(
+    root: &PathBuf,
+    config: &AppConfig,
+    subscription_id: &str,
+) -> Result<(MergeLayer, serde_yaml::Value), String> {
+    let Some(profile) = config
+        .profiles
+        .iter()
+        .find(|p| matches!(p.profile_type, ProfileType::Remote) && p.id == subscription_id)
+    else {
+        return Err("active_subscription_not_found".to_string());
+    };
+
+    let path = profile_file_path(root, profile);
+    if !path.is_file() {
+        return Err(format!(
+            "subscription config file not found at {}",
+            path.display()
+        ));
+    }
+
+    let primary_value = load_yaml_file(&path)?;
+
+    let combined = config.combined_subscription.enabled;
+    let layer = MergeLayer::Subscription {
+        id: profile.id.clone(),
+        name: profile.name.clone(),
+        combined,
+    };
+
+    if combined {
+        let value = load_combined_subscriptions(root, config, profile, &primary_value)?;
+        Ok((layer, value))
+    } else {
+        Ok((layer, primary_value))
+    }
+}
+
+/// 加载用户 profile 链中的一层。
+fn load_user_profile_layer(
+    root: &PathBuf,
+    config: &AppConfig,
+    profile_id: &str,
+) -> Result<(MergeLayer, serde_yaml::Value), String> {
+    let Some(profile) = config
+        .profiles
+        .iter()
+        .find(|p| matches!(p.profile_type, ProfileType::User) && p.id == profile_id)
+    else {
+        return Err(format!("active_user_profile_not_found: {profile_id}"));
+    };
+
+    let path = profile_file_path(root, profile);
+    if !path.is_file() {
+        return Err(format!(
+            "user profile config file not found at {}",
+            path.display()
+        ));
+    }
+
+    let layer = MergeLayer::UserProfile {
+        id: profile.id.clone(),
+        name: profile.name.clone(),
+    };
+    Ok((layer, load_yaml_file(&path)?))
+}
+
+/// 加载 defaults.yaml。
+///
+/// `write_missing` 为 true 时，若文件不存在则写入内置模板；
+/// 否则（如预览场景）直接使用内置模板而不落盘。
+fn load_defaults_value(root: &PathBuf, write_missing: bool) -> serde_yaml::Value {
+    use std::fs;
+    use std::io::Write;
+
+    let defaults_path = defaults_path(root);
+
+    let value = if defaults_path.is_file() {
+        match load_yaml_file(&defaults_path) {
+            Ok(v) => Some(v),
+            Err(err) => {
+                tracing::error!("failed to load defaults.yaml: {err}");
+                None
+            }
+        }
+    } else if !write_missing {
+        match serde_yaml::from_str::<serde_yaml::Value>(DEFAULTS_YAML) {
+            Ok(v) => Some(v),
+            Err(err) => {
+                tracing::error!("failed to parse embedded defaults.yaml: {err}");
+                None
+            }
+        }
+    } else {
+        if let Some(parent) = defaults_path.parent() {
+            if let Err(err) = fs::create_dir_all(parent) {
                 tracing::error!(
-                    "failed to write default defaults.yaml at {}: {err}",
-                    defaults_path.display()
+                    "failed to create defaults dir {}: {err}",
+                    parent.display()
                 );
-                None
-            } else {
-                match load_yaml_file(&defaults_path) {
-                    Ok(v) => Some(v),
-                    Err(err) => {
-                        tracing::error!("failed to parse default defaults.yaml: {err}");
-                        None
-                    }
-                }
             }
+        }
+        if let Err(err) = fs::File::create(&defaults_path)
+            .and_then(|mut f| f.write_all(DEFAULTS_YAML.as_bytes()))
+        {
+            tracing::error!(
+                "failed to write default defaults.yaml at {}: {err}",
+                defaults_path.display()
+            );
+            None
         } else {
             match load_yaml_file(&defaults_path) {
                 Ok(v) => Some(v),
                 Err(err) => {
-                    tracing::error!("failed to load defaults.yaml: {err}");
+                    tracing::error!("failed to parse default defaults.yaml: {err}");
                     None
                 }
             }
         }
     };
 
-    let defaults_value = defaults_value_opt.unwrap_or(serde_yaml::Value::Null);
+    value.unwrap_or(serde_yaml::Value::Null)
+}
+
+/// 按当前 AppConfig 加载全部合并输入。
+fn load_merge_inputs(root: &PathBuf, config: &AppConfig) -> Result<MergeInputs, String> {
+    // 远程订阅侧配置（可选：如果未设置活跃订阅，则视为无远程配置）
+    let remote = match config.active_subscription_id.as_deref() {
+        Some(active_id) => Some(load_subscription_layer(root, config, active_id)?),
+        None => None,
+    };
+
+    // 用户侧配置链（可选）：按 active_user_profile_ids 的顺序依次叠加
+    let users = config
+        .active_user_profile_ids
+        .iter()
+        .map(|id| load_user_profile_layer(root, config, id))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(MergeInputs {
+        defaults: load_defaults_value(root, true),
+        remote,
+        users,
+        providers: crate::providers::providers_layer(root, config),
+        system: crate::system_config::load_system_value(root)?,
+    })
+}
+
+/// 按当前配置依次合并各层得到最终配置（不写入 merged.yaml）。
+fn run_merge_pipeline(
+    root: &PathBuf,
+    trace: Option<&mut MergeTrace>,
+    renames: Option<&mut ProxyRenames>,
+) -> Result<serde_yaml::Value, String> {
+    let config = load_app_config(root)?;
+    let inputs = load_merge_inputs(root, &config)?;
+    fold_merge_layers(inputs, trace, renames)
+}
+
+/// 在内存中逐层合并。
+///
+/// 传入 `trace` 时进入溯源模式：每合并完一层就记录一次当前结果的快照，
+/// 供 `explain_merged_config` 推断每个字段来自哪一层。
+/// 传入 `renames` 时收集各用户层产生的节点改名记录。
+fn fold_merge_layers(
+    inputs: MergeInputs,
+    mut trace: Option<&mut MergeTrace>,
+    mut renames: Option<&mut ProxyRenames>,
+) -> Result<serde_yaml::Value, String> {
+    // 逐层合并顺序：
+    // 1. defaults.yaml       —— 全局默认配置（最低优先级，可由远程/用户配置覆盖）
+    // 2. 远程订阅配置        —— 当前活跃订阅
+    // 3. 用户配置链          —— 已启用的用户 profile，按链中顺序依次叠加
+    // 4. 托管 provider       —— 以本地缓存文件替换同名 provider
+    // 5. system.yaml         —— 系统级配置（最高优先级，用于强制覆盖关键字段）
+    //
+    // merge_yaml_configs(remote, user) 的语义是：
+    // - remote 为“基础配置”
+    // - user   为“用户配置”，在标量/对象上覆盖 remote，在列表上按规则处理
+    //
+    // 因此最终合并顺序为：
+    // defaults -> remote -> user[0] -> user[1] -> ... -> providers -> system
+    let MergeInputs {
+        defaults: defaults_value,
+        remote: remote_value,
+        users: user_values,
+        providers,
+        system: system_value,
+    } = inputs;
+
+    record_layer(&mut trace, MergeLayer::Defaults, &defaults_value);
+
+    // 1. defaults -> remote
+    let mut merged = merge_yaml_configs(
+        Some(&defaults_value),
+        remote_value.as_ref().map(|(_, value)| value),
+    )
+    .map_err(|err| format!("config merge failed: {err}"))?;
+    if let Some((layer, _)) = remote_value {
+        record_layer(&mut trace, layer, &merged);
+    }
+
+    // 2. (defaults+remote) -> user[0] -> user[1] -> ...
+    //    每一层都先执行该用户 profile 中的 script 变换步骤
+    for (layer, user_value) in user_values {
+        let (layer_merged, layer_renames) = apply_user_layer(&merged, Some(&user_value))
+            .map_err(|err| format!("config merge failed: {err}"))?;
+        merged = layer_merged;
+        if let Some(all) = renames.as_deref_mut() {
+            chain_renames(all, layer_renames);
+        }
+        record_layer(&mut trace, layer, &merged);
+    }
+
+    // 3. 注入托管 provider（未登记任何 provider 时不产生这一层）
+    if !providers.is_empty() {
+        crate::providers::inject_providers(&mut merged, &providers)
+            .map_err(|err| format!("config merge failed: {err}"))?;
+        record_layer(&mut trace, MergeLayer::Providers, &merged);
+    }
+
+    // 4. (defaults+remote+user+providers) -> system（system 为最高优先级）
+    merged = merge_yaml_configs(Some(&merged), Some(&system_value))
+        .map_err(|err| format!("config merge failed: {err}"))?;
+    clamp_core_log_level(&mut merged);
+    record_layer(&mut trace, MergeLayer::System, &merged);
+
+    Ok(merged)
+}
+
+/// 会被提升为 `warning` 的内核日志级别。
+const VERBOSE_CORE_LOG_LEVELS: [&str; 2] = ["debug", "info"];
+
+/// 内核输出写入 tmpfs 上的 spool 文件，camofy 未运行时无人读取、也无法限制其大小，
+/// 因此由系统层强制内核日志级别不低于 `warning`。
+fn clamp_core_log_level(config: &mut serde_yaml::Value) {
+    let Some(map) = config.as_mapping_mut() else {
+        return;
+    };
+    let key = serde_yaml::Value::from("log-level");
+    if map
+        .get(&key)
+        .and_then(|v| v.as_str())
+        .is_some_and(|level| VERBOSE_CORE_LOG_LEVELS.contains(&level))
+    {
+        map.insert(key, serde_yaml::Value::from("warning"));
+    }
+}
+
+#[derive(Serialize)]
+pub struct OverriddenValue {
+    pub layer: MergeLayer,
+    pub value: serde_json::Value,
+}
+
+/// 合并结果中单个键路径的来源说明。
+#[derive(Serialize)]
+pub struct MergedKeyOrigin {
+    /// 以 `.` 连接的键路径，例如 `dns.enhanced-mode`
+    pub path: String,
+    /// 最终值来自哪一层
+    pub layer: MergeLayer,
+    /// 最终值（对象类型的键不展开，由其子路径分别说明）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    /// 被更高优先级层覆盖掉的历史值，按合并顺序排列
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub overridden: Vec<OverriddenValue>,
+}
+
+#[derive(Serialize)]
+pub struct MergeExplainResponse {
+    pub layers: Vec<MergeLayer>,
+    pub keys: Vec<MergedKeyOrigin>,
+}
+
+fn yaml_to_json(value: &serde_yaml::Value) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_else(|_| {
+        serde_json::Value::String(serde_yaml::to_string(value).unwrap_or_default())
+    })
+}
+
+fn yaml_key_to_string(key: &serde_yaml::Value) -> String {
+    match key.as_str() {
+        Some(s) => s.to_string(),
+        None => serde_yaml::to_string(key)
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn yaml_value_at<'a>(
+    root: &'a serde_yaml::Value,
+    path: &[serde_yaml::Value],
+) -> Option<&'a serde_yaml::Value> {
+    path.iter()
+        .try_fold(root, |node, key| node.as_mapping().and_then(|m| m.get(key)))
+}
+
+fn collect_key_paths(
+    value: &serde_yaml::Value,
+    prefix: &mut Vec<serde_yaml::Value>,
+    out: &mut Vec<Vec<serde_yaml::Value>>,
+) {
+    let Some(map) = value.as_mapping() else {
+        return;
+    };
+    for (k, v) in map {
+        prefix.push(k.clone());
+        out.push(prefix.clone());
+        collect_key_paths(v, prefix, out);
+        prefix.pop();
+    }
+}
+
+/// 基于各层快照推断最终配置中每个键路径的来源。
+///
+/// 某一层合并后该路径的值发生变化（新增或被改写），即视为由该层提供；
+/// 最后一次变化所在的层即为最终来源，之前的变化记为被覆盖的值。
+/// 对象类型的键仅在其首次出现（或由非对象变为对象）时记为变化。
+fn explain_trace(trace: &MergeTrace) -> MergeExplainResponse {
+    let layers = trace.iter().map(|(layer, _)| layer.clone()).collect();
+
+    let Some((_, final_value)) = trace.last() else {
+        return MergeExplainResponse {
+            layers,
+            keys: Vec::new(),
+        };
+    };
 
-    // 2. 解析系统级配置 system.yaml（最高优先级，内嵌于二进制）
-    let system_value: serde_yaml::Value = serde_yaml::from_str(SYSTEM_YAML)
-        .map_err(|err| format!("failed to parse embedded system.yaml: {err}"))?;
+    let mut paths = Vec::new();
+    collect_key_paths(final_value, &mut Vec::new(), &mut paths);
 
-    // 3. defaults -> remote
-    let mut merged = merge_yaml_configs(Some(&defaults_value), remote_value.as_ref())
-        .map_err(|err| format!("config merge failed: {err}"))?;
+    let keys = paths
+        .iter()
+        .filter_map(|path| {
+            let mut changes: Vec<(&MergeLayer, &serde_yaml::Value)> = Vec::new();
+            let mut prev: Option<&serde_yaml::Value> = None;
+            for (layer, snapshot) in trace {
+                let current = yaml_value_at(snapshot, path);
+                if let Some(value) = current {
+                    let changed = if value.is_mapping() {
+                        !prev.is_some_and(|p| p.is_mapping())
+                    } else {
+                        prev != Some(value)
+                    };
+                    if changed {
+                        changes.push((layer, value));
+                    }
+                }
+                prev = current;
+            }
 
-    // 4. (defaults+remote) -> user
-    merged = merge_yaml_configs(Some(&merged), user_value.as_ref())
-        .map_err(|err| format!("config merge failed: {err}"))?;
+            let (layer, value) = changes.pop()?;
+            let is_leaf = !value.is_mapping();
+            Some(MergedKeyOrigin {
+                path: path
+                    .iter()
+                    .map(yaml_key_to_string)
+                    .collect::<Vec<_>>()
+                    .join("."),
+                layer: layer.clone(),
+                value: is_leaf.then(|| yaml_to_json(value)),
+                overridden: if is_leaf {
+                    changes
+                        .into_iter()
+                        .map(|(layer, value)| OverriddenValue {
+                            layer: layer.clone(),
+                            value: yaml_to_json(value),
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                },
+            })
+        })
+        .collect();
 
-    // 5. (defaults+remote+user) -> system（system 为最高优先级）
-    merged = merge_yaml_configs(Some(&merged), Some(&system_value))
-        .map_err(|err| format!("config merge failed: {err}"))?;
+    MergeExplainResponse { layers, keys }
+}
 
-    save_merged_config(root, &merged)
+/// 以溯源模式运行合并流水线，说明最终配置中每个键的来源层。
+pub(crate) fn explain_merged_config(root: &PathBuf) -> Result<MergeExplainResponse, String> {
+    let mut trace = MergeTrace::new();
+    run_merge_pipeline(root, Some(&mut trace), None)?;
+    Ok(explain_trace(&trace))
 }
 
 pub async fn get_merged_config() -> Json<ApiResponse<serde_json::Value>> {
@@ -952,29 +2138,197 @@ pub async fn get_merged_config() -> Json<ApiResponse<serde_json::Value>> {
     })
 }
 
+#[derive(Deserialize)]
+pub struct ConfigPreviewRequest {
+    /// 候选的用户 profile YAML 内容
+    pub content: String,
+    /// 候选内容对应的用户 profile：若已在链中则替换该层，否则作为链尾新增一层
+    #[serde(default)]
+    pub user_profile_id: Option<String>,
+    /// 使用指定订阅代替当前活跃订阅进行预览
+    #[serde(default)]
+    pub subscription_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ConfigPreviewResponse {
+    /// 预览得到的完整合并配置
+    pub content: String,
+    /// 相对当前 merged.yaml 的 unified diff（无变化时为空）
+    pub diff: String,
+    pub changed: bool,
+    /// 语义校验发现的问题；非空时实际保存将被拒绝
+    pub issues: Vec<ValidationIssue>,
+}
+
+/// 生成 unified diff 文本。
+pub(crate) fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+/// 以候选用户 profile 内容在内存中运行完整合并流程，不写入任何文件、不触发内核重载。
+pub(crate) fn preview_merged_config(
+    root: &PathBuf,
+    request: &ConfigPreviewRequest,
+) -> Result<ConfigPreviewResponse, String> {
+    use std::fs;
+
+    let candidate = if request.content.trim().is_empty() {
+        serde_yaml::Value::Null
+    } else {
+        serde_yaml::from_str::<serde_yaml::Value>(&request.content)
+            .map_err(|err| format!("invalid user profile yaml: {err}"))?
+    };
+
+    let config = load_app_config(root)?;
+
+    let remote = match request
+        .subscription_id
+        .as_deref()
+        .or(config.active_subscription_id.as_deref())
+    {
+        Some(id) => Some(load_subscription_layer(root, &config, id)?),
+        None => None,
+    };
+
+    let candidate_layer = match request.user_profile_id.as_deref() {
+        Some(id) => {
+            let Some(profile) = config
+                .profiles
+                .iter()
+                .find(|p| matches!(p.profile_type, ProfileType::User) && p.id == id)
+            else {
+                return Err(format!("user_profile_not_found: {id}"));
+            };
+            MergeLayer::UserProfile {
+                id: profile.id.clone(),
+                name: profile.name.clone(),
+            }
+        }
+        None => MergeLayer::UserProfile {
+            id: String::new(),
+            name: "preview".to_string(),
+        },
+    };
+
+    let mut users = Vec::with_capacity(config.active_user_profile_ids.len() + 1);
+    let mut replaced = false;
+    for id in &config.active_user_profile_ids {
+        if request.user_profile_id.as_deref() == Some(id.as_str()) {
+            users.push((candidate_layer.clone(), candidate.clone()));
+            replaced = true;
+        } else {
+            users.push(load_user_profile_layer(root, &config, id)?);
+        }
+    }
+    if !replaced {
+        users.push((candidate_layer, candidate));
+    }
+
+    let merged = fold_merge_layers(
+        MergeInputs {
+            defaults: load_defaults_value(root, false),
+            remote,
+            users,
+            providers: crate::providers::providers_layer(root, &config),
+            system: crate::system_config::load_system_value(root)?,
+        },
+        None,
+        None,
+    )?;
+
+    let content = serde_yaml::to_string(&merged)
+        .map_err(|err| format!("failed to serialize merged config: {err}"))?;
+
+    let current = match fs::read_to_string(merged_config_path(root)) {
+        Ok(c) => c,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(format!("failed to read merged.yaml: {err}")),
+    };
+
+    let changed = current != content;
+    let diff = if changed {
+        unified_diff(&current, &content, "merged.yaml", "preview.yaml")
+    } else {
+        String::new()
+    };
+
+    Ok(ConfigPreviewResponse {
+        content,
+        diff,
+        changed,
+        issues: validate_merged_config(&merged),
+    })
+}
+
+pub async fn preview_config(
+    Json(body): Json<ConfigPreviewRequest>,
+) -> Json<ApiResponse<ConfigPreviewResponse>> {
+    let state = app_state();
+
+    match preview_merged_config(&state.data_root, &body) {
+        Ok(data) => Json(ApiResponse {
+            code: "ok".to_string(),
+            message: "success".to_string(),
+            data: Some(data),
+        }),
+        Err(err) => {
+            let code = if err.starts_with("invalid user profile yaml") {
+                "user_profile_invalid_yaml"
+            } else if err.starts_with("user_profile_not_found") {
+                "user_profile_not_found"
+            } else {
+                "config_merge_failed"
+            };
+            tracing::warn!("config preview failed: {err}");
+            Json(ApiResponse {
+                code: code.to_string(),
+                message: err,
+                data: None,
+            })
+        }
+    }
+}
+
+pub async fn get_merged_config_explain() -> Json<ApiResponse<MergeExplainResponse>> {
+    let state = app_state();
+
+    match explain_merged_config(&state.data_root) {
+        Ok(data) => Json(ApiResponse {
+            code: "ok".to_string(),
+            message: "success".to_string(),
+            data: Some(data),
+        }),
+        Err(err) => {
+            tracing::error!("failed to explain merged config: {err}");
+            Json(ApiResponse {
+                code: "config_merge_failed".to_string(),
+                message: err,
+                data: None,
+            })
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{generate_merged_config, merged_config_path};
+    use super::{
+        ConfigPreviewRequest, MergeLayer, explain_merged_config, generate_merged_config,
+        apply_user_layer, merge_yaml_configs, merged_config_path, preview_merged_config,
+    };
+    use crate::test_support::TempRoot;
     use crate::{AppConfig, ProfileMeta, ProfileType, save_app_config};
     use std::fs;
-    use std::path::PathBuf;
 
-    fn temp_root(suffix: &str) -> PathBuf {
-        let mut dir = std::env::temp_dir();
-        let nanos = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
-        dir.push(format!("camofy-test-{suffix}-{nanos}"));
-        fs::create_dir_all(&dir).unwrap();
-        dir
-    }
-
-    #[test]
-    fn core_defaults_applied_when_no_profiles() {
-        let root = temp_root("core-defaults");
+    #[tokio::test]
+    async fn core_defaults_applied_when_no_profiles() {
+        let root = TempRoot::new("core-defaults");
 
-        generate_merged_config(&root).expect("generate_merged_config failed");
+        generate_merged_config(&root).await.expect("generate_merged_config failed");
 
         let path = merged_config_path(&root);
         let content = fs::read_to_string(&path).expect("read merged.yaml");
@@ -997,9 +2351,9 @@ mod tests {
         );
     }
 
-    #[test]
-    fn user_profile_overrides_defaults_for_custom_fields() {
-        let root = temp_root("core-overrides");
+    #[tokio::test]
+    async fn user_profile_overrides_defaults_for_custom_fields() {
+        let root = TempRoot::new("core-overrides");
 
         let profile_id = "user1".to_string();
         let profile = ProfileMeta {
@@ -1011,11 +2365,20 @@ mod tests {
             last_fetch_time: None,
             last_fetch_status: None,
             last_modified_time: None,
+            subscription_format: None,
+            userinfo: None,
+            update_interval_hours: None,
+            remote_filename: None,
+            mirror_urls: Vec::new(),
+            fetch_cache: None,
+            fetch_options: Default::default(),
+            schedule: Default::default(),
+            usage_warnings: Vec::new(),
         };
 
         let mut app_cfg = AppConfig::default();
         app_cfg.profiles.push(profile);
-        app_cfg.active_user_profile_id = Some(profile_id);
+        app_cfg.active_user_profile_ids = vec![profile_id];
         save_app_config(&root, &app_cfg).expect("save_app_config failed");
 
         let mut profile_dir = root.clone();
@@ -1031,7 +2394,7 @@ mod tests {
         )
         .expect("write user profile");
 
-        generate_merged_config(&root).expect("generate_merged_config failed");
+        generate_merged_config(&root).await.expect("generate_merged_config failed");
 
         let merged_path = merged_config_path(&root);
         let merged_content =
@@ -1039,11 +2402,602 @@ mod tests {
         let value: serde_yaml::Value =
             serde_yaml::from_str(&merged_content).expect("parse merged.yaml after override");
 
-        // system.yaml 中定义的字段应保持系统值（用户无法覆盖）
-        assert_eq!(value.get("mixed-port").and_then(|v| v.as_i64()), Some(7897));
+        // defaults.yaml 中的字段可被用户覆盖，system.yaml 中定义的字段保持系统值
+        assert_eq!(value.get("mixed-port").and_then(|v| v.as_i64()), Some(8888));
         assert_eq!(value.get("mode").and_then(|v| v.as_str()), Some("rule"));
+        assert_eq!(crate::system_config::mixed_port(&root), 8888);
 
         // 用户新增的自定义字段应当被保留
         assert_eq!(value.get("custom-key").and_then(|v| v.as_i64()), Some(42));
     }
+
+    fn user_profile_meta(id: &str) -> ProfileMeta {
+        ProfileMeta {
+            id: id.to_string(),
+            name: id.to_string(),
+            profile_type: ProfileType::User,
+            path: format!("user-profiles/{id}.yaml"),
+            url: None,
+            last_fetch_time: None,
+            last_fetch_status: None,
+            last_modified_time: None,
+            subscription_format: None,
+            userinfo: None,
+            update_interval_hours: None,
+            remote_filename: None,
+            mirror_urls: Vec::new(),
+            fetch_cache: None,
+            fetch_options: Default::default(),
+            schedule: Default::default(),
+            usage_warnings: Vec::new(),
+        }
+    }
+
+    fn merged_value(root: &std::path::PathBuf) -> serde_yaml::Value {
+        let content = fs::read_to_string(merged_config_path(root)).expect("read merged.yaml");
+        serde_yaml::from_str(&content).expect("parse merged.yaml")
+    }
+
+    #[tokio::test]
+    async fn active_user_profiles_are_folded_in_chain_order() {
+        let root = TempRoot::new("profile-chain");
+
+        let profile_dir = root.join("config").join("user-profiles");
+        fs::create_dir_all(&profile_dir).unwrap();
+        fs::write(profile_dir.join("a.yaml"), "shared: from-a
+only-a: 1
+").unwrap();
+        fs::write(profile_dir.join("b.yaml"), "shared: from-b
+only-b: 2
+").unwrap();
+
+        // 链中靠后的 profile 优先级更高；两者各自独有的字段都保留
+        let mut app_cfg = AppConfig {
+            profiles: vec![user_profile_meta("a"), user_profile_meta("b")],
+            active_user_profile_ids: vec!["a".to_string(), "b".to_string()],
+            ..Default::default()
+        };
+        save_app_config(&root, &app_cfg).expect("save_app_config failed");
+        generate_merged_config(&root).await.expect("generate_merged_config failed");
+        let value = merged_value(&root);
+        assert_eq!(value["shared"].as_str(), Some("from-b"));
+        assert_eq!(value["only-a"].as_i64(), Some(1));
+        assert_eq!(value["only-b"].as_i64(), Some(2));
+
+        app_cfg.active_user_profile_ids = vec!["b".to_string(), "a".to_string()];
+        save_app_config(&root, &app_cfg).expect("save_app_config failed");
+        generate_merged_config(&root).await.expect("generate_merged_config failed");
+        assert_eq!(merged_value(&root)["shared"].as_str(), Some("from-a"));
+
+        // 未启用的 profile 不参与合并
+        app_cfg.active_user_profile_ids = vec!["a".to_string()];
+        save_app_config(&root, &app_cfg).expect("save_app_config failed");
+        generate_merged_config(&root).await.expect("generate_merged_config failed");
+        let value = merged_value(&root);
+        assert_eq!(value["shared"].as_str(), Some("from-a"));
+        assert!(value.get("only-b").is_none());
+    }
+
+    #[test]
+    fn legacy_active_user_profile_id_is_migrated_to_chain() {
+        let root = TempRoot::new("profile-migrate");
+
+        let write_app_json = |extra: serde_json::Value| {
+            let app_cfg = AppConfig {
+                profiles: vec![user_profile_meta("legacy"), user_profile_meta("new")],
+                ..Default::default()
+            };
+            save_app_config(&root, &app_cfg).expect("save_app_config failed");
+            let path = root.join("config").join("app.json");
+            let mut json: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+            for (key, value) in extra.as_object().unwrap() {
+                json[key] = value.clone();
+            }
+            fs::write(&path, serde_json::to_string(&json).unwrap()).unwrap();
+        };
+
+        // 旧版本只有单一活跃 profile
+        write_app_json(serde_json::json!({ "active_user_profile_id": "legacy" }));
+        let config = crate::load_app_config(&root).expect("load_app_config failed");
+        assert_eq!(config.active_user_profile_ids, vec!["legacy".to_string()]);
+
+        // 旧字段与新链并存时，旧 profile 放在链首（最低优先级），且不重复
+        write_app_json(serde_json::json!({
+            "active_user_profile_id": "legacy",
+            "active_user_profile_ids": ["new"],
+        }));
+        let config = crate::load_app_config(&root).expect("load_app_config failed");
+        assert_eq!(
+            config.active_user_profile_ids,
+            vec!["legacy".to_string(), "new".to_string()]
+        );
+        write_app_json(serde_json::json!({
+            "active_user_profile_id": "new",
+            "active_user_profile_ids": ["legacy", "new"],
+        }));
+        let config = crate::load_app_config(&root).expect("load_app_config failed");
+        assert_eq!(
+            config.active_user_profile_ids,
+            vec!["legacy".to_string(), "new".to_string()]
+        );
+
+        // 迁移后保存的 app.json 不再包含旧字段
+        save_app_config(&root, &config).expect("save_app_config failed");
+        let saved = fs::read_to_string(root.join("config").join("app.json")).unwrap();
+        assert!(!saved.contains("\"active_user_profile_id\""));
+    }
+
+    fn group_members(value: &serde_yaml::Value, group: &str) -> Vec<String> {
+        value["proxy-groups"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .find(|g| g["name"].as_str() == Some(group))
+            .unwrap_or_else(|| panic!("group {group} not found"))["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect()
+    }
+
+    fn merge_str(remote: &str, user: &str) -> Result<serde_yaml::Value, String> {
+        let remote: serde_yaml::Value = serde_yaml::from_str(remote).unwrap();
+        let user: serde_yaml::Value = serde_yaml::from_str(user).unwrap();
+        merge_yaml_configs(Some(&remote), Some(&user))
+    }
+
+    const GROUPS_REMOTE: &str = r#"
+proxies:
+  - { name: HK-01, type: ss }
+  - { name: HK-02, type: ss }
+  - { name: JP-01, type: ss }
+  - { name: 剩余流量 10G, type: ss }
+proxy-groups:
+  - { name: 节点选择, type: select, proxies: [自动选择, 广告拦截, HK-01, JP-01, 剩余流量 10G] }
+  - { name: 自动选择, type: url-test, url: http://a, interval: 600, proxies: [HK-01, JP-01] }
+  - { name: 广告拦截, type: select, proxies: [REJECT, DIRECT] }
+  - { name: 官网导航, type: select, proxies: [DIRECT] }
+"#;
+
+    #[test]
+    fn proxy_group_directives_delete_patch_and_insert() {
+        let merged = merge_str(
+            GROUPS_REMOTE,
+            r#"
+append-proxies:
+  - { name: 我的节点, type: socks5 }
+delete-proxy-groups:
+  - 广告拦截
+  - match: "^官网"
+patch-proxy-groups:
+  - name: 自动选择
+    set: { type: fallback, interval: 300 }
+  - match: "选择$"
+    exclude: "剩余流量"
+insert-into-group:
+  - name: 节点选择
+    position: prepend
+    proxies: [我的节点]
+  - name: 自动选择
+    select: "^HK-"
+"#,
+        )
+        .expect("merge with group directives");
+
+        let names: Vec<&str> = merged["proxy-groups"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|g| g["name"].as_str())
+            .collect();
+        assert_eq!(names, vec!["节点选择", "自动选择"]);
+
+        // 被删除的组同时从其他组的成员中移除，exclude 剔除了匹配的节点
+        assert_eq!(
+            group_members(&merged, "节点选择"),
+            vec!["我的节点", "自动选择", "HK-01", "JP-01"]
+        );
+        // select 挑选节点时跳过已存在的成员
+        assert_eq!(group_members(&merged, "自动选择"), vec!["HK-01", "JP-01", "HK-02"]);
+
+        let auto = &merged["proxy-groups"][1];
+        assert_eq!(auto["type"].as_str(), Some("fallback"));
+        assert_eq!(auto["interval"].as_i64(), Some(300));
+        assert_eq!(auto["url"].as_str(), Some("http://a"));
+
+        for key in ["delete-proxy-groups", "patch-proxy-groups", "insert-into-group"] {
+            assert!(merged.get(key).is_none(), "{key} should not leak into merged config");
+        }
+    }
+
+    #[test]
+    fn proxy_group_directives_reject_invalid_entries() {
+        let err = merge_str(GROUPS_REMOTE, "patch-proxy-groups:\n  - { set: { type: select } }\n")
+            .unwrap_err();
+        assert!(err.contains("exactly one of 'name' or 'match'"), "{err}");
+
+        let err = merge_str(
+            GROUPS_REMOTE,
+            "patch-proxy-groups:\n  - { name: 自动选择, set: { name: 新名字 } }\n",
+        )
+        .unwrap_err();
+        assert!(err.contains("set.name"), "{err}");
+
+        let err = merge_str(GROUPS_REMOTE, "insert-into-group:\n  - { match: \"(\", proxies: [A] }\n")
+            .unwrap_err();
+        assert!(err.contains("invalid 'insert-into-group' match regex"), "{err}");
+
+        // 找不到精确名称的组时跳过，不影响合并
+        let merged = merge_str(GROUPS_REMOTE, "delete-proxy-groups: [不存在]\n").unwrap();
+        assert_eq!(merged["proxy-groups"].as_sequence().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn proxy_filter_and_rename_directives_update_groups() {
+        let remote: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+proxies:
+  - { name: "剩余流量：10G", type: ss, server: info.example.com }
+  - { name: "官网 example.com", type: ss, server: 1.1.1.1 }
+  - { name: "[HK] 01", type: ss, server: hk.example.net }
+  - { name: "[JP] 01", type: vmess, server: jp.example.net }
+  - { name: "[US] 01", type: http, server: us.example.net }
+  - { name: "🇸🇬 SG 01", type: trojan, server: sg.example.net }
+proxy-groups:
+  - name: 节点选择
+    type: select
+    proxies: ["剩余流量：10G", "官网 example.com", "[HK] 01", "[JP] 01", "[US] 01", "🇸🇬 SG 01"]
+"#,
+        )
+        .unwrap();
+        // script 先去掉方括号，顶层 rename-proxies 再插入旗帜：改名记录应串联为 原名 -> 最终名
+        let user: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+script:
+  - op: rename-proxies
+    pattern: "^\\[(\\w+)\\]\\s*"
+    replace: "$1 "
+filter-proxies:
+  - exclude: "剩余流量|官网"
+  - exclude-types: [http]
+    server: "example\\.net$"
+rename-proxies:
+  flag: true
+"#,
+        )
+        .unwrap();
+
+        let (merged, renames) = apply_user_layer(&remote, Some(&user)).expect("apply user layer");
+
+        let proxies: Vec<&str> = merged["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|p| p["name"].as_str())
+            .collect();
+        assert_eq!(proxies, vec!["🇭🇰 HK 01", "🇯🇵 JP 01", "🇸🇬 SG 01"]);
+        assert_eq!(
+            group_members(&merged, "节点选择"),
+            vec!["🇭🇰 HK 01", "🇯🇵 JP 01", "🇸🇬 SG 01"]
+        );
+        assert_eq!(
+            renames,
+            vec![
+                ("[HK] 01".to_string(), "🇭🇰 HK 01".to_string()),
+                ("[JP] 01".to_string(), "🇯🇵 JP 01".to_string()),
+                ("[US] 01".to_string(), "US 01".to_string()),
+            ]
+        );
+        assert!(merged.get("filter-proxies").is_none());
+        assert!(merged.get("rename-proxies").is_none());
+    }
+
+    fn rules(value: &serde_yaml::Value) -> Vec<&str> {
+        value["rules"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .filter_map(|r| r.as_str())
+            .collect()
+    }
+
+    const RULES_REMOTE: &str = r#"
+rules:
+  - DOMAIN-SUFFIX,google.com,节点选择
+  - GEOIP,CN,国内直连
+  - GEOIP, CN ,节点选择
+  - AND,((DOMAIN,a.com),(NETWORK,UDP)),节点选择
+  - SUB-RULE,(NETWORK,tcp),节点选择
+  - MATCH,节点选择
+"#;
+
+    #[test]
+    fn rule_directives_delete_rewrite_and_keep_match_last() {
+        let merged = merge_str(
+            RULES_REMOTE,
+            r#"
+append-rules:
+  - DOMAIN,example.com,DIRECT
+delete-rules:
+  - "GEOIP,CN,节点选择"
+  - match: "^DOMAIN-SUFFIX,google"
+rewrite-rule-targets:
+  - from: 节点选择
+    to: 我的选择
+"#,
+        )
+        .expect("merge with rule directives");
+
+        assert_eq!(
+            rules(&merged),
+            vec![
+                "GEOIP,CN,国内直连",
+                "AND,((DOMAIN,a.com),(NETWORK,UDP)),我的选择",
+                // 子规则的目标是子规则名，不参与改写
+                "SUB-RULE,(NETWORK,tcp),节点选择",
+                "DOMAIN,example.com,DIRECT",
+                "MATCH,我的选择",
+            ]
+        );
+        assert!(merged.get("delete-rules").is_none());
+        assert!(merged.get("rewrite-rule-targets").is_none());
+    }
+
+    #[test]
+    fn deleted_proxy_group_rules_fall_back_to_match_target() {
+        let remote = format!(
+            "{GROUPS_REMOTE}rules:\n  - DOMAIN-SUFFIX,ads.com,广告拦截\n  - GEOIP,CN,官网导航,no-resolve\n  - MATCH,节点选择\n"
+        );
+
+        let merged = merge_str(
+            &remote,
+            "delete-proxy-groups:\n  - 广告拦截\n  - 官网导航\n",
+        )
+        .expect("merge failed");
+        assert_eq!(
+            rules(&merged),
+            vec![
+                "DOMAIN-SUFFIX,ads.com,节点选择",
+                "GEOIP,CN,节点选择,no-resolve",
+                "MATCH,节点选择",
+            ]
+        );
+
+        // 同一层中显式改写过的规则不受影响
+        let merged = merge_str(
+            &remote,
+            "delete-proxy-groups: [广告拦截]\nrewrite-rule-targets:\n  - { from: 广告拦截, to: REJECT }\n",
+        )
+        .expect("merge failed");
+        assert_eq!(rules(&merged)[0], "DOMAIN-SUFFIX,ads.com,REJECT");
+
+        // MATCH 也指向被删除的组时无法回退，报错并列出规则
+        let err = merge_str(&remote, "delete-proxy-groups: [节点选择]\n").unwrap_err();
+        assert!(err.contains("MATCH,节点选择"), "error: {err}");
+    }
+
+    #[test]
+    fn rule_directives_keep_a_single_match_rule() {
+        // 追加的 MATCH 覆盖订阅中的 MATCH
+        let merged = merge_str(RULES_REMOTE, "append-rules: [\"MATCH,DIRECT\"]\n").unwrap();
+        let all = rules(&merged);
+        assert_eq!(all.last(), Some(&"MATCH,DIRECT"));
+        assert_eq!(all.iter().filter(|r| r.starts_with("MATCH")).count(), 1);
+
+        // 删除全部 MATCH 后仍会恢复兜底规则，并参与目标改写
+        let merged = merge_str(
+            RULES_REMOTE,
+            "delete-rules: [{ match: \"^MATCH\" }]\nrewrite-rule-targets: [{ match: \"^节点\", to: PROXY }]\n",
+        )
+        .unwrap();
+        assert_eq!(rules(&merged).last(), Some(&"MATCH,PROXY"));
+
+        let err = merge_str(RULES_REMOTE, "rewrite-rule-targets: [{ to: PROXY }]\n").unwrap_err();
+        assert!(err.contains("exactly one of 'from' or 'match'"), "{err}");
+    }
+
+    #[test]
+    fn explain_reports_origin_layer_and_overridden_values() {
+        let root = TempRoot::new("explain");
+
+        let profile = ProfileMeta {
+            id: "user1".to_string(),
+            name: "test".to_string(),
+            profile_type: ProfileType::User,
+            path: "user-profiles/user1.yaml".to_string(),
+            url: None,
+            last_fetch_time: None,
+            last_fetch_status: None,
+            last_modified_time: None,
+            subscription_format: None,
+            userinfo: None,
+            update_interval_hours: None,
+            remote_filename: None,
+            mirror_urls: Vec::new(),
+            fetch_cache: None,
+            fetch_options: Default::default(),
+            schedule: Default::default(),
+            usage_warnings: Vec::new(),
+        };
+        let user_layer = MergeLayer::UserProfile {
+            id: "user1".to_string(),
+            name: "test".to_string(),
+        };
+
+        let mut app_cfg = AppConfig::default();
+        app_cfg.profiles.push(profile);
+        app_cfg.active_user_profile_ids = vec!["user1".to_string()];
+        save_app_config(&root, &app_cfg).expect("save_app_config failed");
+
+        let profile_dir = root.join("config").join("user-profiles");
+        fs::create_dir_all(&profile_dir).unwrap();
+        fs::write(
+            profile_dir.join("user1.yaml"),
+            "log-level: error\nmode: global\n",
+        )
+        .expect("write user profile");
+
+        let explain = explain_merged_config(&root).expect("explain_merged_config failed");
+        assert_eq!(
+            explain.layers,
+            vec![MergeLayer::Defaults, user_layer.clone(), MergeLayer::System]
+        );
+
+        let find = |path: &str| {
+            explain
+                .keys
+                .iter()
+                .find(|k| k.path == path)
+                .unwrap_or_else(|| panic!("missing key path {path}"))
+        };
+
+        let log_level = find("log-level");
+        assert_eq!(log_level.layer, user_layer);
+        assert_eq!(log_level.value, Some(serde_json::json!("error")));
+        assert_eq!(log_level.overridden.len(), 1);
+        assert_eq!(log_level.overridden[0].layer, MergeLayer::Defaults);
+        assert_eq!(log_level.overridden[0].value, serde_json::json!("warning"));
+
+        let mode = find("mode");
+        assert_eq!(mode.layer, MergeLayer::System);
+        assert_eq!(mode.overridden[0].layer, user_layer);
+
+        let dns = find("dns");
+        assert_eq!(dns.layer, MergeLayer::Defaults);
+        assert!(dns.value.is_none());
+        assert_eq!(find("dns.enhanced-mode").layer, MergeLayer::System);
+    }
+
+    #[tokio::test]
+    async fn preview_merges_candidate_without_touching_disk() {
+        let root = TempRoot::new("preview");
+
+        generate_merged_config(&root).await.expect("generate_merged_config failed");
+        let merged_path = merged_config_path(&root);
+        let before = fs::read_to_string(&merged_path).expect("read merged.yaml");
+
+        let request = ConfigPreviewRequest {
+            content: "log-level: error\n".to_string(),
+            user_profile_id: None,
+            subscription_id: None,
+        };
+        let preview = preview_merged_config(&root, &request).expect("preview failed");
+
+        assert!(preview.changed);
+        assert!(preview.content.contains("log-level: error"));
+        assert!(preview.diff.contains("-log-level: warning"));
+        assert!(preview.diff.contains("+log-level: error"));
+
+        // 比 warning 更详细的内核日志级别会被系统层提升为 warning
+        let request = ConfigPreviewRequest {
+            content: "log-level: debug\n".to_string(),
+            user_profile_id: None,
+            subscription_id: None,
+        };
+        let preview = preview_merged_config(&root, &request).expect("preview failed");
+        assert!(!preview.changed);
+        assert!(preview.content.contains("log-level: warning"));
+
+        // 预览不应修改 merged.yaml
+        let after = fs::read_to_string(&merged_path).expect("read merged.yaml");
+        assert_eq!(before, after);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn core_test_failure_keeps_previous_merged_config() {
+        use super::MergeError;
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = TempRoot::new("core-test");
+
+        generate_merged_config(&root).await.expect("generate_merged_config failed");
+        let merged_path = merged_config_path(&root);
+        let before = fs::read_to_string(&merged_path).expect("read merged.yaml");
+
+        // 伪造一个总是拒绝配置的内核；stderr 输出远超管道缓冲区，确认不会因读取顺序卡住
+        let core_dir = root.join("core");
+        fs::create_dir_all(&core_dir).unwrap();
+        let core_path = core_dir.join("mihomo");
+        fs::write(
+            &core_path,
+            "#!/bin/sh\nhead -c 200000 /dev/zero | tr '\\0' x >&2\n\
+             echo 'level=error msg=proxy group not found' >&2\nexit 1\n",
+        )
+        .unwrap();
+        fs::set_permissions(&core_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        fs::write(root.join("config").join("defaults.yaml"), "log-level: error\n").unwrap();
+
+        let started = std::time::Instant::now();
+        match generate_merged_config(&root).await {
+            Err(MergeError::CoreTestFailed(output)) => {
+                assert!(started.elapsed() < std::time::Duration::from_secs(10));
+                assert!(output.contains("proxy group not found"), "output: {output}");
+            }
+            other => panic!("expected core test failure, got {other:?}"),
+        }
+
+        let after = fs::read_to_string(&merged_path).expect("read merged.yaml");
+        assert_eq!(before, after);
+        assert!(staged_files(&root).is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalid_merged_content_is_not_applied() {
+        use super::{MergeError, apply_merged_config_content};
+
+        let root = TempRoot::new("apply-merged");
+        generate_merged_config(&root).await.expect("generate_merged_config failed");
+        let merged_path = merged_config_path(&root);
+        let before = fs::read_to_string(&merged_path).expect("read merged.yaml");
+
+        let result = apply_merged_config_content(&root, "proxies:\n  - type: ss\n").await;
+        assert!(matches!(result, Err(MergeError::Invalid(_))), "got {result:?}");
+        assert_eq!(fs::read_to_string(&merged_path).unwrap(), before);
+        assert!(staged_files(&root).is_empty());
+    }
+
+    fn staged_files(root: &std::path::Path) -> Vec<String> {
+        fs::read_dir(root.join("config"))
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| name.starts_with("merged.staged") || name.ends_with(".tmp"))
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn concurrent_merges_are_serialized() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = TempRoot::new("merge-lock");
+        // 伪造的内核在测试期间占用一个目录；若有两次测试同时进行，mkdir 失败即拒绝配置
+        let core_dir = root.join("core");
+        fs::create_dir_all(&core_dir).unwrap();
+        let core_path = core_dir.join("mihomo");
+        fs::write(
+            &core_path,
+            "#!/bin/sh
+busy=\"$(dirname \"$0\")/busy\"
+mkdir \"$busy\" || exit 1
+             sleep 0.3
+rmdir \"$busy\"
+exit 0
+",
+        )
+        .unwrap();
+        fs::set_permissions(&core_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (first, second) = tokio::join!(
+            generate_merged_config(&root),
+            generate_merged_config(&root)
+        );
+        first.expect("first merge failed");
+        second.expect("second merge failed");
+        assert!(merged_config_path(&root).is_file());
+        assert!(staged_files(&root).is_empty());
+    }
 }
@@ -0,0 +1,448 @@
+//! 合并结果的语义校验。
+//!
+//! YAML 能解析并不代表 Mihomo 能接受：代理组引用了不存在的节点、规则指向
+//! 不存在的策略、端口与系统配置冲突等问题都会在内核重载时才暴露。
+//! 这里在写入 `merged.yaml` / 通知内核之前做一次静态检查，返回结构化的问题列表。
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+/// Mihomo 内置策略，可直接出现在代理组成员或规则目标中。
+const BUILTIN_POLICIES: &[&str] = &[
+    "DIRECT",
+    "REJECT",
+    "REJECT-DROP",
+    "PASS",
+    "COMPATIBLE",
+    "GLOBAL",
+];
+
+/// 其余顶层监听端口字段（mixed-port 与 dns.listen 为内核主要监听端口，单独优先登记）。
+const PORT_KEYS: &[&str] = &["port", "socks-port", "redir-port", "tproxy-port"];
+
+/// 包含嵌套规则载荷的逻辑规则类型，目标位于最外层括号之后。
+const LOGICAL_RULE_TYPES: &[&str] = &["AND", "OR", "NOT"];
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ValidationIssue {
+    /// 问题类型，例如 `unknown_group_member`、`port_conflict`
+    pub code: &'static str,
+    /// 出问题的位置，例如 `proxy-groups[2].proxies[0]`、`rules[10]`
+    pub path: String,
+    pub message: String,
+}
+
+fn issue(code: &'static str, path: String, message: String) -> ValidationIssue {
+    ValidationIssue {
+        code,
+        path,
+        message,
+    }
+}
+
+fn sequence<'a>(map: &'a Mapping, key: &str) -> &'a [Value] {
+    map.get(key)
+        .and_then(|v| v.as_sequence())
+        .map(|s| s.as_slice())
+        .unwrap_or(&[])
+}
+
+fn mapping_keys(map: &Mapping, key: &str) -> HashSet<String> {
+    map.get(key)
+        .and_then(|v| v.as_mapping())
+        .map(|m| m.keys().filter_map(|k| k.as_str()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn port_number(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        // 形如 "0.0.0.0:1053" / ":1053" / "1053"
+        Value::String(s) => s.rsplit(':').next().and_then(|p| p.trim().parse().ok()),
+        _ => None,
+    }
+}
+
+/// 对合并后的配置做语义校验，返回发现的全部问题（为空表示通过）。
+pub(crate) fn validate_merged_config(config: &Value) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    let Some(root) = config.as_mapping() else {
+        return issues;
+    };
+
+    let proxies = sequence(root, "proxies");
+    let groups = sequence(root, "proxy-groups");
+    let proxy_providers = mapping_keys(root, "proxy-providers");
+    let rule_providers = mapping_keys(root, "rule-providers");
+    let sub_rules = mapping_keys(root, "sub-rules");
+
+    // 节点与代理组共享同一命名空间
+    let mut policies: HashSet<String> = BUILTIN_POLICIES.iter().map(|s| s.to_string()).collect();
+
+    for (idx, proxy) in proxies.iter().enumerate() {
+        let path = format!("proxies[{idx}]");
+        let Some(name) = proxy.get("name").and_then(|v| v.as_str()) else {
+            issues.push(issue("proxy_missing_name", path, "proxy has no name".into()));
+            continue;
+        };
+        if !policies.insert(name.to_string()) {
+            issues.push(issue(
+                "duplicate_proxy",
+                path,
+                format!("duplicate proxy name '{name}'"),
+            ));
+        }
+    }
+
+    for (idx, group) in groups.iter().enumerate() {
+        let path = format!("proxy-groups[{idx}]");
+        let Some(name) = group.get("name").and_then(|v| v.as_str()) else {
+            issues.push(issue("proxy_group_missing_name", path, "proxy group has no name".into()));
+            continue;
+        };
+        if !policies.insert(name.to_string()) {
+            issues.push(issue(
+                "duplicate_proxy_group",
+                path,
+                format!("proxy group name '{name}' is already used by another proxy or group"),
+            ));
+        }
+    }
+
+    check_groups(groups, &policies, &proxy_providers, &mut issues);
+
+    let ctx = RuleContext {
+        policies: &policies,
+        rule_providers: &rule_providers,
+        sub_rules: &sub_rules,
+    };
+    for (idx, rule) in sequence(root, "rules").iter().enumerate() {
+        check_rule(rule, format!("rules[{idx}]"), &ctx, &mut issues);
+    }
+    if let Some(Value::Mapping(map)) = root.get("sub-rules") {
+        for (name, rules) in map {
+            let name = name.as_str().unwrap_or_default();
+            for (idx, rule) in rules.as_sequence().map(|s| s.as_slice()).unwrap_or(&[]).iter().enumerate() {
+                check_rule(rule, format!("sub-rules.{name}[{idx}]"), &ctx, &mut issues);
+            }
+        }
+    }
+
+    check_ports(root, &mut issues);
+
+    issues
+}
+
+fn check_groups(
+    groups: &[Value],
+    policies: &HashSet<String>,
+    proxy_providers: &HashSet<String>,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    for (idx, group) in groups.iter().enumerate() {
+        let name = group.get("name").and_then(|v| v.as_str()).unwrap_or_default();
+        let members = group
+            .get("proxies")
+            .and_then(|v| v.as_sequence())
+            .map(|s| s.as_slice())
+            .unwrap_or(&[]);
+        let uses = group
+            .get("use")
+            .and_then(|v| v.as_sequence())
+            .map(|s| s.as_slice())
+            .unwrap_or(&[]);
+
+        for (member_idx, member) in members.iter().enumerate() {
+            let member = member.as_str().unwrap_or_default();
+            if !policies.contains(member) {
+                issues.push(issue(
+                    "unknown_group_member",
+                    format!("proxy-groups[{idx}].proxies[{member_idx}]"),
+                    format!("proxy group '{name}' references unknown proxy or group '{member}'"),
+                ));
+            }
+        }
+
+        for (use_idx, provider) in uses.iter().enumerate() {
+            let provider = provider.as_str().unwrap_or_default();
+            if !proxy_providers.contains(provider) {
+                issues.push(issue(
+                    "unknown_proxy_provider",
+                    format!("proxy-groups[{idx}].use[{use_idx}]"),
+                    format!("proxy group '{name}' uses unknown proxy provider '{provider}'"),
+                ));
+            }
+        }
+
+        let includes_all = ["include-all", "include-all-proxies", "include-all-providers"]
+            .iter()
+            .any(|key| group.get(*key).and_then(|v| v.as_bool()) == Some(true));
+        if members.is_empty() && uses.is_empty() && !includes_all {
+            issues.push(issue(
+                "empty_proxy_group",
+                format!("proxy-groups[{idx}]"),
+                format!("proxy group '{name}' has no proxies"),
+            ));
+        }
+    }
+}
+
+struct RuleContext<'a> {
+    policies: &'a HashSet<String>,
+    rule_providers: &'a HashSet<String>,
+    sub_rules: &'a HashSet<String>,
+}
+
+/// 一条规则字符串拆分后的各部分。
+pub(crate) struct RuleParts<'a> {
+    /// 规则类型（大写），例如 `DOMAIN-SUFFIX`、`MATCH`
+    pub rule_type: String,
+    /// 规则载荷；逻辑规则与子规则为包含括号的整段条件
+    pub payload: &'a str,
+    /// 目标策略（子规则为子规则名）在原字符串中的位置，已去除首尾空白
+    pub target: Option<Range<usize>>,
+}
+
+/// 拆分规则字符串，定位其类型、载荷与目标。
+///
+/// - 普通规则：`DOMAIN,a.com,DIRECT[,no-resolve]`
+/// - 兜底规则：`MATCH,DIRECT`
+/// - 逻辑规则：`AND,((DOMAIN,a.com),(NETWORK,UDP)),DIRECT`
+/// - 子规则：  `SUB-RULE,(NETWORK,tcp),sub-rule-name`
+pub(crate) fn split_rule(rule: &str) -> RuleParts<'_> {
+    let (rule_type, rest, rest_start) = match rule.find(',') {
+        Some(idx) => (&rule[..idx], &rule[idx + 1..], idx + 1),
+        None => (rule, "", rule.len()),
+    };
+    let rule_type = rule_type.trim().to_ascii_uppercase();
+
+    // 从 start 开始到下一个逗号（或结尾）为止的字段
+    let field_at = |start: usize| {
+        let end = rule[start..].find(',').map_or(rule.len(), |idx| start + idx);
+        start..end
+    };
+
+    let (payload, target) = if LOGICAL_RULE_TYPES.contains(&rule_type.as_str())
+        || rule_type == "SUB-RULE"
+    {
+        match rest.rfind(')') {
+            Some(end) => {
+                let mut start = rest_start + end + 1;
+                while rule[start..].starts_with(',') {
+                    start += 1;
+                }
+                (&rest[..=end], Some(field_at(start)))
+            }
+            None => (rest, None),
+        }
+    } else if rule_type == "MATCH" {
+        ("", Some(field_at(rest_start)))
+    } else {
+        match rest.find(',') {
+            Some(idx) => (&rest[..idx], Some(field_at(rest_start + idx + 1))),
+            None => (rest, None),
+        }
+    };
+
+    let target = target
+        .map(|range| {
+            let field = &rule[range.clone()];
+            let start = range.start + (field.len() - field.trim_start().len());
+            let end = range.end - (field.len() - field.trim_end().len());
+            start..end.max(start)
+        })
+        .filter(|range| !range.is_empty());
+
+    RuleParts {
+        rule_type,
+        payload,
+        target,
+    }
+}
+
+fn check_rule(rule: &Value, path: String, ctx: &RuleContext<'_>, issues: &mut Vec<ValidationIssue>) {
+    let Some(rule) = rule.as_str() else {
+        issues.push(issue("invalid_rule", path, "rule must be a string".into()));
+        return;
+    };
+
+    let RuleParts {
+        rule_type,
+        payload,
+        target,
+    } = split_rule(rule);
+
+    let Some(target) = target.map(|range| &rule[range]) else {
+        issues.push(issue(
+            "invalid_rule",
+            path,
+            format!("rule '{rule}' has no target policy"),
+        ));
+        return;
+    };
+
+    if rule_type == "SUB-RULE" {
+        if !ctx.sub_rules.contains(target) {
+            issues.push(issue(
+                "unknown_sub_rule",
+                path.clone(),
+                format!("rule '{rule}' references unknown sub-rule '{target}'"),
+            ));
+        }
+    } else if !ctx.policies.contains(target) {
+        issues.push(issue(
+            "unknown_rule_target",
+            path.clone(),
+            format!("rule '{rule}' targets unknown policy '{target}'"),
+        ));
+    }
+
+    // RULE-SET 可直接出现，也可嵌套在逻辑规则的载荷中
+    let providers: Vec<&str> = if rule_type == "RULE-SET" {
+        vec![payload.trim()]
+    } else {
+        payload
+            .match_indices("RULE-SET,")
+            .map(|(pos, m)| {
+                let tail = &payload[pos + m.len()..];
+                tail.split([',', ')']).next().unwrap_or_default().trim()
+            })
+            .collect()
+    };
+    for provider in providers {
+        if !ctx.rule_providers.contains(provider) {
+            issues.push(issue(
+                "unknown_rule_provider",
+                path.clone(),
+                format!("rule '{rule}' references unknown rule provider '{provider}'"),
+            ));
+        }
+    }
+}
+
+fn check_ports(root: &Mapping, issues: &mut Vec<ValidationIssue>) {
+    let mut listeners: Vec<(String, u64)> = Vec::new();
+
+    // 先登记系统占用的端口，冲突时报告在用户自定义的字段上
+    if let Some(port) = root.get("mixed-port").and_then(port_number) {
+        listeners.push(("mixed-port".to_string(), port));
+    }
+    if let Some(port) = root
+        .get("dns")
+        .and_then(|dns| dns.get("listen"))
+        .and_then(port_number)
+    {
+        listeners.push(("dns.listen".to_string(), port));
+    }
+    for key in PORT_KEYS {
+        if let Some(port) = root.get(*key).and_then(port_number) {
+            listeners.push((key.to_string(), port));
+        }
+    }
+    for (idx, listener) in sequence(root, "listeners").iter().enumerate() {
+        if let Some(port) = listener.get("port").and_then(port_number) {
+            listeners.push((format!("listeners[{idx}].port"), port));
+        }
+    }
+
+    let mut seen: HashMap<u64, String> = HashMap::new();
+    for (path, port) in listeners {
+        // 0 表示关闭该监听
+        if port == 0 {
+            continue;
+        }
+        if let Some(first) = seen.get(&port) {
+            issues.push(issue(
+                "port_conflict",
+                path.clone(),
+                format!("port {port} of '{path}' conflicts with '{first}'"),
+            ));
+        } else {
+            seen.insert(port, path);
+        }
+    }
+}
+
+/// 将问题列表格式化为一行摘要，用于日志与错误信息；`subject` 为被校验对象，如 `merged config`。
+pub(crate) fn summarize_issues(subject: &str, issues: &[ValidationIssue]) -> String {
+    let details: Vec<String> = issues
+        .iter()
+        .map(|i| format!("{}: {}", i.path, i.message))
+        .collect();
+    format!(
+        "{subject} failed validation with {} issue(s): {}",
+        issues.len(),
+        details.join("; ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_merged_config;
+    use serde_yaml::Value;
+
+    fn codes(yaml: &str) -> Vec<(&'static str, String)> {
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        validate_merged_config(&value)
+            .into_iter()
+            .map(|i| (i.code, i.path))
+            .collect()
+    }
+
+    #[test]
+    fn valid_config_has_no_issues() {
+        let issues = codes(
+            r#"
+mixed-port: 7897
+dns: { listen: 0.0.0.0:1053 }
+proxies:
+  - { name: HK, type: ss }
+proxy-providers:
+  extra: { type: http }
+rule-providers:
+  ads: { type: http }
+proxy-groups:
+  - { name: 节点选择, type: select, proxies: [HK, DIRECT], use: [extra] }
+rules:
+  - RULE-SET,ads,REJECT
+  - AND,((RULE-SET,ads),(NETWORK,UDP)),节点选择
+  - IP-CIDR,10.0.0.0/8,DIRECT,no-resolve
+  - MATCH,节点选择
+"#,
+        );
+        assert!(issues.is_empty(), "unexpected issues: {issues:?}");
+    }
+
+    #[test]
+    fn reports_references_duplicates_and_port_conflicts() {
+        let issues = codes(
+            r#"
+mixed-port: 7897
+socks-port: 1053
+dns: { listen: 0.0.0.0:1053 }
+proxies:
+  - { name: HK, type: ss }
+  - { name: HK, type: vmess }
+proxy-groups:
+  - { name: 节点选择, type: select, proxies: [HK, JP] }
+rules:
+  - RULE-SET,missing,DIRECT
+  - DOMAIN-SUFFIX,example.com,不存在
+  - MATCH,节点选择
+"#,
+        );
+        assert_eq!(
+            issues,
+            vec![
+                ("duplicate_proxy", "proxies[1]".to_string()),
+                ("unknown_group_member", "proxy-groups[0].proxies[1]".to_string()),
+                ("unknown_rule_provider", "rules[0]".to_string()),
+                ("unknown_rule_target", "rules[1]".to_string()),
+                ("port_conflict", "socks-port".to_string()),
+            ]
+        );
+    }
+}